@@ -0,0 +1,250 @@
+use std::env;
+use std::io::{self, Read, Write};
+use std::mem;
+
+use libc;
+
+use super::{Backend, Input, Key, Style, DEFAULT_COLORS};
+
+/// Draws with plain ANSI escape sequences, needing nothing but a terminal emulator.
+pub struct Ansi {
+    /// Terminal settings to restore when done.
+    original: libc::termios,
+    /// Output collected until the next refresh.
+    buffer: String,
+    /// Bytes read from the terminal but not turned into keys yet.
+    pending: Vec<u8>,
+    /// Escape sequence going back to the screen colors after styled text.
+    colors: String,
+    /// Whether the terminal takes 24-bit colors, rather than the 256 of xterm.
+    truecolor: bool,
+}
+
+impl Ansi {
+    pub fn new() -> io::Result<Ansi> {
+        let original = unsafe {
+            let mut original: libc::termios = mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = original;
+            libc::cfmakeraw(&mut raw);
+            // Reads return straight away, with or without input.
+            raw.c_cc[libc::VMIN] = 0;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            original
+        };
+
+        let mut ansi = Ansi {
+            original,
+            buffer: String::new(),
+            pending: Vec::new(),
+            colors: DEFAULT_COLORS.to_string(),
+            // The usual way for terminals to say so, there is no way to ask them.
+            truecolor: env::var("COLORTERM")
+                .map(|value| value == "truecolor" || value == "24bit")
+                .unwrap_or(false),
+        };
+        // Alternate screen, hidden cursor, mouse button reports in SGR form, black on white,
+        // cleared.
+        ansi.buffer
+            .push_str("\x1b[?1049h\x1b[?25l\x1b[?1000h\x1b[?1006h");
+        ansi.buffer.push_str(DEFAULT_COLORS);
+        ansi.buffer.push_str("\x1b[2J");
+        ansi.refresh();
+        Ok(ansi)
+    }
+
+    fn read_input(&mut self) {
+        let mut buf = [0; 64];
+        if let Ok(n) = io::stdin().read(&mut buf) {
+            self.pending.extend_from_slice(&buf[..n]);
+        }
+    }
+}
+
+impl Drop for Ansi {
+    fn drop(&mut self) {
+        // When done, restore the defaults to avoid messing with the terminal.
+        self.buffer
+            .push_str("\x1b[0m\x1b[?1006l\x1b[?1000l\x1b[?25h\x1b[?1049l");
+        self.refresh();
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+impl Backend for Ansi {
+    fn size(&self) -> (u16, u16) {
+        match window_size() {
+            Some(size) if size.ws_col > 0 => (size.ws_col, size.ws_row),
+            _ => (80, 24),
+        }
+    }
+
+    fn pixel_size(&self) -> Option<(u16, u16)> {
+        match window_size() {
+            Some(size) if size.ws_xpixel > 0 && size.ws_ypixel > 0 => {
+                Some((size.ws_xpixel, size.ws_ypixel))
+            }
+            _ => None,
+        }
+    }
+
+    fn put(&mut self, row: u16, column: u16, text: &str) {
+        self.buffer
+            .push_str(&format!("\x1b[{};{}H{}", row + 1, column + 1, text));
+    }
+
+    fn put_styled(&mut self, row: u16, column: u16, text: &str, style: Style) {
+        let sgr = super::sgr(style, self.truecolor);
+        self.buffer.push_str(&format!(
+            "\x1b[{};{}H{}{}{}",
+            row + 1,
+            column + 1,
+            sgr,
+            text,
+            self.colors
+        ));
+    }
+
+    fn set_colors(&mut self, style: Style) {
+        self.colors = format!("{}{}", DEFAULT_COLORS, super::sgr(style, self.truecolor));
+        self.buffer.push_str(&self.colors);
+    }
+
+    fn clear(&mut self) {
+        self.buffer.push_str("\x1b[2J");
+    }
+
+    fn refresh(&mut self) {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        let _ = stdout.write_all(self.buffer.as_bytes());
+        let _ = stdout.flush();
+        self.buffer.clear();
+    }
+
+    fn poll(&mut self) -> Option<Input> {
+        // Also read when a mouse report or function key is only partly there.
+        if self.pending.is_empty()
+            || self.pending.starts_with(b"\x1b[<")
+            || numbered(&self.pending)
+        {
+            self.read_input();
+        }
+        if self.pending.is_empty() {
+            return None;
+        }
+
+        // Mouse reports look like `ESC [ < button ; column ; row M`, with `m` for releases.
+        if self.pending.starts_with(b"\x1b[<") {
+            let end = self
+                .pending
+                .iter()
+                .position(|&b| b == b'M' || b == b'm')?;
+            let fields: Vec<u16> = String::from_utf8_lossy(&self.pending[3..end])
+                .split(';')
+                .filter_map(|field| field.parse().ok())
+                .collect();
+            let press = self.pending[end] == b'M';
+            self.pending.drain(..=end);
+            return match fields[..] {
+                // Skip the middle button, and motion and wheel reports, which set bits 32 and 64.
+                [button, column, row] if press && button & 0x60 == 0 && button & 3 != 1 => {
+                    Some(Input::Click {
+                        row: row.saturating_sub(1),
+                        column: column.saturating_sub(1),
+                        shift: button & 3 == 2 || button & 4 != 0,
+                    })
+                }
+                _ => None,
+            };
+        }
+
+        // Function keys past F4 arrive as `ESC [ 15 ~` and friends, with a modifier after a `;`
+        // while shift or control is held. Some terminals send F1 to F4 this way too, others as
+        // `ESC [ 1 ; modifier P` to `S` when modified.
+        if numbered(&self.pending) {
+            let end = self.pending[2..]
+                .iter()
+                .position(|&b| (0x40..=0x7E).contains(&b))?
+                + 2;
+            let number: Option<u8> = String::from_utf8_lossy(&self.pending[2..end])
+                .split(';')
+                .next()
+                .and_then(|number| number.parse().ok());
+            let last = self.pending[end];
+            self.pending.drain(..=end);
+            let key = match (last, number?) {
+                (b'P'..=b'S', 1) => last - b'P' + 1,
+                (b'~', number @ 11..=15) => number - 10,
+                (b'~', number @ 17..=21) => number - 11,
+                (b'~', number @ 23..=24) => number - 12,
+                _ => return None,
+            };
+            return Some(Input::Key(Key::F(key)));
+        }
+
+        // F1 to F4 arrive as `ESC O P` to `ESC O S`.
+        if self.pending.starts_with(b"\x1bO") && self.pending.len() >= 3 {
+            let key = match self.pending[2] {
+                code @ b'P'..=b'S' => Some(Key::F(code - b'P' + 1)),
+                _ => None,
+            };
+            self.pending.drain(..3);
+            return key.map(Input::Key);
+        }
+
+        // Arrow keys arrive as `ESC [ A` and friends.
+        if self.pending.starts_with(b"\x1b[") && self.pending.len() >= 3 {
+            let key = match self.pending[2] {
+                b'A' => Some(Key::Up),
+                b'B' => Some(Key::Down),
+                b'C' => Some(Key::Right),
+                b'D' => Some(Key::Left),
+                _ => None,
+            };
+            self.pending.drain(..3);
+            return key.map(Input::Key);
+        }
+
+        let len = utf8_len(self.pending[0]).min(self.pending.len());
+        let key = String::from_utf8_lossy(&self.pending[..len])
+            .chars()
+            .next()
+            .map(Key::Char);
+        self.pending.drain(..len);
+        key.map(Input::Key)
+    }
+}
+
+/// Whether `input` starts with a key sent as a number, like `ESC [ 15 ~`.
+fn numbered(input: &[u8]) -> bool {
+    input.starts_with(b"\x1b[") && input.get(2).is_some_and(u8::is_ascii_digit)
+}
+
+fn window_size() -> Option<libc::winsize> {
+    unsafe {
+        let mut size: libc::winsize = mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0 {
+            Some(size)
+        } else {
+            None
+        }
+    }
+}
+
+/// Length of the UTF-8 sequence starting with `byte`.
+fn utf8_len(byte: u8) -> usize {
+    match byte {
+        0xF0..=0xFF => 4,
+        0xE0..=0xEF => 3,
+        0xC0..=0xDF => 2,
+        _ => 1,
+    }
+}
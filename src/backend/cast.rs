@@ -0,0 +1,165 @@
+//! `--asciicast`: a recording of the screen in the asciicast v2 format, for asciinema to play.
+//!
+//! Wraps the backend drawing on the terminal and writes what was drawn between two refreshes
+//! as one output event, in ANSI escape sequences whichever backend drew it. A refresh showing
+//! only what the one before did is left out, so a paused run doesn't grow the file.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use super::{Backend, Input, Style, DEFAULT_COLORS};
+
+/// Records everything drawn on `inner`.
+pub struct Cast {
+    inner: Box<dyn Backend>,
+    path: PathBuf,
+    out: BufWriter<File>,
+    started: Instant,
+    /// What was drawn since the last refresh.
+    frame: String,
+    /// What was drawn before the last refresh.
+    last: String,
+    /// Escape sequence going back to the screen colors after styled text.
+    colors: String,
+    /// Columns and rows of the screen as last recorded.
+    size: (u16, u16),
+    /// The first thing that went wrong writing, reported by `close`.
+    error: Option<String>,
+}
+
+impl Cast {
+    /// Record what is drawn on `inner` into `file`, created at `path`.
+    pub fn new(inner: Box<dyn Backend>, path: &Path, file: File) -> Cast {
+        let size = inner.size();
+        let mut cast = Cast {
+            inner,
+            path: path.to_path_buf(),
+            out: BufWriter::new(file),
+            started: Instant::now(),
+            frame: format!("{}\x1b[?25l\x1b[2J", DEFAULT_COLORS),
+            last: String::new(),
+            colors: DEFAULT_COLORS.to_string(),
+            size,
+            error: None,
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let header = format!(
+            r#"{{"version":2,"width":{},"height":{},"timestamp":{}}}"#,
+            size.0, size.1, timestamp
+        );
+        cast.write(&header);
+        cast
+    }
+
+    /// Write `line` to the file, unless writing already failed.
+    fn write(&mut self, line: &str) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = writeln!(self.out, "{}", line) {
+            self.error = Some(format!("Could not write {}: {}", self.path.display(), e));
+        }
+    }
+
+    /// An event of `kind` with `data` at the current time.
+    fn event(&mut self, kind: &str, data: &str) {
+        let line = format!(
+            "[{:.6},\"{}\",{}]",
+            self.started.elapsed().as_secs_f64(),
+            kind,
+            json_string(data)
+        );
+        self.write(&line);
+    }
+}
+
+impl Backend for Cast {
+    fn size(&self) -> (u16, u16) {
+        self.inner.size()
+    }
+
+    fn pixel_size(&self) -> Option<(u16, u16)> {
+        self.inner.pixel_size()
+    }
+
+    fn put(&mut self, row: u16, column: u16, text: &str) {
+        self.frame
+            .push_str(&format!("\x1b[{};{}H{}", row + 1, column + 1, text));
+        self.inner.put(row, column, text);
+    }
+
+    fn put_styled(&mut self, row: u16, column: u16, text: &str, style: Style) {
+        self.frame.push_str(&format!(
+            "\x1b[{};{}H{}{}{}",
+            row + 1,
+            column + 1,
+            super::sgr(style, true),
+            text,
+            self.colors
+        ));
+        self.inner.put_styled(row, column, text, style);
+    }
+
+    fn set_colors(&mut self, style: Style) {
+        self.colors = format!("{}{}", DEFAULT_COLORS, super::sgr(style, true));
+        self.frame.push_str(&self.colors);
+        self.inner.set_colors(style);
+    }
+
+    fn clear(&mut self) {
+        self.frame.push_str("\x1b[2J");
+        self.inner.clear();
+    }
+
+    fn refresh(&mut self) {
+        self.inner.refresh();
+        let size = self.inner.size();
+        if size != self.size {
+            self.size = size;
+            self.event("r", &format!("{}x{}", size.0, size.1));
+        }
+        if !self.frame.is_empty() && self.frame != self.last {
+            let frame = std::mem::take(&mut self.frame);
+            self.event("o", &frame);
+            self.last = frame;
+        }
+        self.frame.clear();
+    }
+
+    fn poll(&mut self) -> Option<Input> {
+        self.inner.poll()
+    }
+
+    fn close(&mut self) -> Result<(), String> {
+        self.inner.close()?;
+        if self.error.is_none() {
+            if let Err(e) = self.out.flush() {
+                self.error = Some(format!("Could not write {}: {}", self.path.display(), e));
+            }
+        }
+        self.error.take().map_or(Ok(()), Err)
+    }
+}
+
+/// `text` as a JSON string, quotes included.
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 || c == '\u{7f}' => {
+                out.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use pancurses::{curs_set, endwin, initscr, noecho, Input, Window};
+use pancurses as pc;
+
+use super::{Backend, Input as Event, Key, Style};
+
+/// Mouse button state bits. pancurses doesn't export these, and the shift bit moved in
+/// ncurses' second mouse interface, which current ncurses uses.
+const BUTTON1_PRESSED: pc::mmask_t = 0x2;
+const BUTTON1_CLICKED: pc::mmask_t = 0x4;
+const BUTTON3_PRESSED: pc::mmask_t = 0x800;
+const BUTTON3_CLICKED: pc::mmask_t = 0x1000;
+#[cfg(unix)]
+const BUTTON_SHIFT: pc::mmask_t = 0x20_0000;
+#[cfg(windows)]
+const BUTTON_SHIFT: pc::mmask_t = 0x400_0000;
+
+/// Draws through ncurses, or pdcurses on Windows.
+pub struct Curses {
+    /// The terminal window
+    window: Window,
+    /// Whether the terminal shows colors at all.
+    colors: bool,
+    /// Curses colors of the screen, in color pair 1.
+    screen: (i16, i16),
+    /// Color pairs set up so far by foreground and background.
+    pairs: HashMap<(i16, i16), i16>,
+}
+
+impl Curses {
+    pub fn new() -> Curses {
+        let window = initscr();
+        noecho();
+        curs_set(0);
+
+        window.nodelay(true);
+        window.keypad(true);
+        pc::mousemask(
+            BUTTON1_PRESSED | BUTTON1_CLICKED | BUTTON3_PRESSED | BUTTON3_CLICKED | BUTTON_SHIFT,
+            std::ptr::null_mut(),
+        );
+
+        let colors = pc::has_colors();
+        if colors {
+            pc::start_color();
+        }
+
+        pc::init_pair(1, pc::COLOR_BLACK, pc::COLOR_WHITE);
+        window.bkgd(pc::COLOR_PAIR(1));
+
+        Curses {
+            window,
+            colors,
+            screen: (pc::COLOR_BLACK, pc::COLOR_WHITE),
+            pairs: HashMap::new(),
+        }
+    }
+
+    /// The curses color nearest to `rgb`.
+    fn color(rgb: [u8; 3]) -> i16 {
+        if pc::COLORS() >= 256 {
+            i16::from(super::xterm_color(rgb))
+        } else {
+            i16::from(super::basic_color(rgb))
+        }
+    }
+
+    /// The color pair for `style`, set up on first use. Once curses runs out of pairs, the
+    /// screen's colors are used.
+    fn pair(&mut self, style: Style) -> i16 {
+        let key = (
+            style.fg.map_or(self.screen.0, Curses::color),
+            style.bg.map_or(self.screen.1, Curses::color),
+        );
+        if key == self.screen {
+            return 1;
+        }
+        if let Some(&pair) = self.pairs.get(&key) {
+            return pair;
+        }
+        let pair = self.pairs.len() as i16 + 2;
+        if i32::from(pair) >= pc::COLOR_PAIRS() {
+            return 1;
+        }
+        pc::init_pair(pair, key.0, key.1);
+        self.pairs.insert(key, pair);
+        pair
+    }
+}
+
+impl Drop for Curses {
+    fn drop(&mut self) {
+        // When done, restore the defaults to avoid messing with the terminal.
+        endwin();
+    }
+}
+
+impl Backend for Curses {
+    fn size(&self) -> (u16, u16) {
+        let (rows, columns) = self.window.get_max_yx();
+        (columns as u16, rows as u16)
+    }
+
+    fn put(&mut self, row: u16, column: u16, text: &str) {
+        self.window.mvaddstr(i32::from(row), i32::from(column), text);
+    }
+
+    fn put_styled(&mut self, row: u16, column: u16, text: &str, style: Style) {
+        if !self.colors {
+            return self.put(row, column, text);
+        }
+        let pair = self.pair(style);
+        self.window.attrset(pc::COLOR_PAIR(pair as pc::chtype));
+        self.window.mvaddstr(i32::from(row), i32::from(column), text);
+        self.window.attrset(pc::COLOR_PAIR(1));
+    }
+
+    fn set_colors(&mut self, style: Style) {
+        if !self.colors {
+            return;
+        }
+        self.screen = (
+            style.fg.map_or(pc::COLOR_BLACK, Curses::color),
+            style.bg.map_or(pc::COLOR_WHITE, Curses::color),
+        );
+        pc::init_pair(1, self.screen.0, self.screen.1);
+        self.window.bkgd(pc::COLOR_PAIR(1));
+    }
+
+    fn clear(&mut self) {
+        self.window.erase();
+    }
+
+    fn refresh(&mut self) {
+        self.window.refresh();
+    }
+
+    fn poll(&mut self) -> Option<Event> {
+        let key = match self.window.getch()? {
+            Input::Character(c) => Key::Char(c),
+            Input::KeyUp => Key::Up,
+            Input::KeyDown => Key::Down,
+            Input::KeyLeft => Key::Left,
+            Input::KeyRight => Key::Right,
+            Input::KeyF1 => Key::F(1),
+            Input::KeyF2 => Key::F(2),
+            Input::KeyF3 => Key::F(3),
+            Input::KeyF4 => Key::F(4),
+            Input::KeyF5 => Key::F(5),
+            Input::KeyF6 => Key::F(6),
+            Input::KeyF7 => Key::F(7),
+            Input::KeyF8 => Key::F(8),
+            Input::KeyF9 => Key::F(9),
+            Input::KeyF10 => Key::F(10),
+            Input::KeyF11 => Key::F(11),
+            Input::KeyF12 => Key::F(12),
+            Input::KeyMouse => {
+                let event = pc::getmouse().ok()?;
+                let left = event.bstate & (BUTTON1_PRESSED | BUTTON1_CLICKED) != 0;
+                let right = event.bstate & (BUTTON3_PRESSED | BUTTON3_CLICKED) != 0;
+                if !left && !right {
+                    return None;
+                }
+                return Some(Event::Click {
+                    row: event.y as u16,
+                    column: event.x as u16,
+                    shift: right || event.bstate & BUTTON_SHIFT != 0,
+                });
+            }
+            _ => return None,
+        };
+        Some(Event::Key(key))
+    }
+}
@@ -0,0 +1,135 @@
+//! Printing the screen as plain text now and then, for `--dumb-tty` and whenever stdout isn't
+//! a terminal: pipes, CI logs, containers without a tty.
+//!
+//! Nothing is read, so there are no keys. The screen is `COLUMNS` by `LINES` characters if
+//! those are set, 80 by 24 otherwise. Colors are written as ANSI escape sequences, only where
+//! the theme has any.
+
+use std::env;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use super::{Backend, Input, Style};
+
+/// Shortest time between two printed screens.
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Keeps the screen in memory and prints it on refresh, at most once per `INTERVAL`.
+pub struct Dumb {
+    /// Every character of the screen and its colors, row by row.
+    cells: Vec<Vec<(char, Style)>>,
+    /// Colors of the screen, for `put` and for clearing.
+    screen: Style,
+    /// When the screen was last printed.
+    printed: Option<Instant>,
+    /// Whether something was drawn since the screen was last printed.
+    dirty: bool,
+}
+
+impl Dumb {
+    pub fn new() -> Dumb {
+        let size = |name: &str, default: u16| {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|&size| size > 0)
+                .unwrap_or(default)
+        };
+        let (columns, rows) = (size("COLUMNS", 80), size("LINES", 24));
+        Dumb {
+            cells: vec![vec![(' ', Style::default()); usize::from(columns)]; usize::from(rows)],
+            screen: Style::default(),
+            printed: None,
+            dirty: false,
+        }
+    }
+
+    /// Write the screen to stdout, a line per row.
+    fn print(&mut self) {
+        let mut out = String::new();
+        for row in &self.cells {
+            let mut style = Style::default();
+            for &(c, look) in row {
+                if look != style {
+                    out.push_str("\x1b[0m");
+                    out.push_str(&super::sgr(look, true));
+                    style = look;
+                }
+                out.push(c);
+            }
+            if style != Style::default() {
+                out.push_str("\x1b[0m");
+            }
+            // Trailing blanks only make the log wider.
+            let trimmed = out.trim_end_matches(' ').len();
+            out.truncate(trimmed);
+            out.push('\n');
+        }
+        out.push('\n');
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        let _ = stdout.write_all(out.as_bytes());
+        let _ = stdout.flush();
+        self.printed = Some(Instant::now());
+        self.dirty = false;
+    }
+}
+
+impl Drop for Dumb {
+    fn drop(&mut self) {
+        // The screen the run ended on, if it wasn't printed yet.
+        if self.dirty {
+            self.print();
+        }
+    }
+}
+
+impl Backend for Dumb {
+    fn size(&self) -> (u16, u16) {
+        (self.cells[0].len() as u16, self.cells.len() as u16)
+    }
+
+    fn put(&mut self, row: u16, column: u16, text: &str) {
+        let style = self.screen;
+        self.put_styled(row, column, text, style);
+    }
+
+    fn put_styled(&mut self, row: u16, column: u16, text: &str, style: Style) {
+        let row = match self.cells.get_mut(usize::from(row)) {
+            Some(row) => row,
+            None => return,
+        };
+        let style = Style {
+            fg: style.fg.or(self.screen.fg),
+            bg: style.bg.or(self.screen.bg),
+        };
+        for (cell, c) in row.iter_mut().skip(usize::from(column)).zip(text.chars()) {
+            *cell = (c, style);
+        }
+        self.dirty = true;
+    }
+
+    fn set_colors(&mut self, style: Style) {
+        self.screen = style;
+    }
+
+    fn clear(&mut self) {
+        let screen = self.screen;
+        for row in &mut self.cells {
+            for cell in row.iter_mut() {
+                *cell = (' ', screen);
+            }
+        }
+        self.dirty = true;
+    }
+
+    fn refresh(&mut self) {
+        if self.dirty && self.printed.is_none_or(|printed| printed.elapsed() >= INTERVAL) {
+            self.print();
+        }
+    }
+
+    fn poll(&mut self) -> Option<Input> {
+        None
+    }
+}
@@ -0,0 +1,160 @@
+//! Terminal backends the ant can be drawn with.
+
+#[cfg(unix)]
+pub mod ansi;
+pub mod cast;
+pub mod dumb;
+#[cfg(feature = "curses")]
+pub mod curses;
+
+/// The screen colors of the ANSI escape sequences until `set_colors` says otherwise, black on
+/// white.
+pub const DEFAULT_COLORS: &str = "\x1b[0;30;47m";
+
+/// A key press, independent of the backend that read it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    /// A function key, `F(1)` for F1.
+    F(u8),
+}
+
+/// Something the user did, independent of the backend that read it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Input {
+    Key(Key),
+    /// A press of the mouse button at a screen position.
+    ///
+    /// `shift` is also set for the right button, since many terminals keep shift-click for
+    /// selecting text.
+    Click { row: u16, column: u16, shift: bool },
+}
+
+/// Colors to draw with, `None` leaving the screen's own.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Style {
+    pub fg: Option<[u8; 3]>,
+    pub bg: Option<[u8; 3]>,
+}
+
+/// Something that can show cells and read keys and mouse clicks.
+///
+/// Setting the terminal up happens when a backend is created, and restoring it happens when it
+/// is dropped.
+pub trait Backend {
+    /// Number of columns and rows available for drawing.
+    fn size(&self) -> (u16, u16);
+    /// Width and height of the drawing area in pixels, if the terminal reports it.
+    fn pixel_size(&self) -> Option<(u16, u16)> {
+        None
+    }
+    /// Draw `text` starting at `row` and `column`.
+    fn put(&mut self, row: u16, column: u16, text: &str);
+    /// Draw `text` like `put`, in the colors of `style` as far as the terminal has them.
+    fn put_styled(&mut self, row: u16, column: u16, text: &str, style: Style) {
+        let _ = style;
+        self.put(row, column, text);
+    }
+    /// Use the colors of `style` for the screen, for `put` and for clearing. Black on white
+    /// where it leaves them out.
+    fn set_colors(&mut self, style: Style) {
+        let _ = style;
+    }
+    /// Blank the whole screen.
+    fn clear(&mut self);
+    /// Make everything drawn since the last refresh visible.
+    fn refresh(&mut self);
+    /// The next pending key press or click, without waiting for one.
+    fn poll(&mut self) -> Option<Input>;
+    /// Finish whatever the backend writes besides the screen, before it is dropped.
+    fn close(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// The ANSI escape sequence switching to the colors `style` has, in 24-bit colors with
+/// `truecolor` and otherwise in the 256 of xterm.
+pub fn sgr(style: Style, truecolor: bool) -> String {
+    let mut codes = Vec::new();
+    for &(base, color) in &[(38, style.fg), (48, style.bg)] {
+        match color {
+            Some(rgb) if truecolor => {
+                codes.push(format!("{};2;{};{};{}", base, rgb[0], rgb[1], rgb[2]))
+            }
+            Some(rgb) => codes.push(format!("{};5;{}", base, xterm_color(rgb))),
+            None => {}
+        }
+    }
+    if codes.is_empty() {
+        String::new()
+    } else {
+        format!("\x1b[{}m", codes.join(";"))
+    }
+}
+
+/// The nearest of the 256 colors of xterm and most other terminals to `rgb`, from the 6×6×6
+/// color cube or the gray ramp.
+pub fn xterm_color(rgb: [u8; 3]) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let nearest = |channel: u8| {
+        (0..LEVELS.len())
+            .min_by_key(|&i| (i32::from(LEVELS[i]) - i32::from(channel)).abs())
+            .unwrap_or(0)
+    };
+    let cube = [nearest(rgb[0]), nearest(rgb[1]), nearest(rgb[2])];
+    let gray_level = (rgb.iter().map(|&c| u32::from(c)).sum::<u32>() / 3).saturating_sub(3) / 10;
+    let gray_level = gray_level.min(23) as u8;
+
+    let distance = |other: [u8; 3]| {
+        rgb.iter()
+            .zip(&other)
+            .map(|(&a, &b)| (i32::from(a) - i32::from(b)).pow(2))
+            .sum::<i32>()
+    };
+    let cube_rgb = [LEVELS[cube[0]], LEVELS[cube[1]], LEVELS[cube[2]]];
+    let gray = 8 + 10 * gray_level;
+    if distance([gray, gray, gray]) < distance(cube_rgb) {
+        232 + gray_level
+    } else {
+        16 + 36 * cube[0] as u8 + 6 * cube[1] as u8 + cube[2] as u8
+    }
+}
+
+/// The nearest of the 8 basic terminal colors to `rgb`: bit 0 red, bit 1 green and bit 2 blue.
+#[cfg(feature = "curses")]
+pub fn basic_color(rgb: [u8; 3]) -> u8 {
+    rgb.iter()
+        .enumerate()
+        .fold(0, |color, (bit, &channel)| color | u8::from(channel >= 0x80) << bit)
+}
+
+/// Names accepted by `--backend`.
+pub const NAMES: &[&str] = &[
+    #[cfg(feature = "curses")]
+    "curses",
+    #[cfg(unix)]
+    "ansi",
+    "dumb",
+];
+
+/// Start the backend called `name`.
+pub fn open(name: &str) -> Result<Box<dyn Backend>, String> {
+    match name {
+        #[cfg(feature = "curses")]
+        "curses" => Ok(Box::new(curses::Curses::new())),
+        #[cfg(unix)]
+        "ansi" => ansi::Ansi::new()
+            .map(|backend| Box::new(backend) as Box<dyn Backend>)
+            .map_err(|e| format!("Could not set up the terminal: {}", e)),
+        "dumb" => Ok(Box::new(dumb::Dumb::new())),
+        _ => Err(format!(
+            "Unknown backend {}, expected one of: {}",
+            name,
+            NAMES.join(", ")
+        )),
+    }
+}
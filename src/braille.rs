@@ -0,0 +1,38 @@
+//! Packing 2×4 blocks of cells into Unicode braille characters.
+
+use langton_ant::simulation::Simulation;
+
+/// Cells covered by one character, as columns and rows.
+pub const CELL_WIDTH: usize = 2;
+pub const CELL_HEIGHT: usize = 4;
+
+/// The dot bit for each row and column within a character.
+const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// The braille character showing the cells behind terminal `row` and `column`, with a dot for
+/// every painted cell and wall.
+pub fn glyph(sim: &Simulation, row: usize, column: usize) -> char {
+    let mut bits = 0;
+    for (dy, dots) in DOTS.iter().enumerate() {
+        let y = row * CELL_HEIGHT + dy;
+        if y >= sim.height() {
+            break;
+        }
+        for (dx, dot) in dots.iter().enumerate() {
+            let x = column * CELL_WIDTH + dx;
+            if x >= sim.width() {
+                break;
+            }
+            let cell = sim.cell(x, y);
+            if cell.wall || !cell.state.is_blank() {
+                bits |= dot;
+            }
+        }
+    }
+    // Blank braille patterns render narrower than a space in some fonts.
+    if bits == 0 {
+        ' '
+    } else {
+        std::char::from_u32(0x2800 + bits).unwrap_or(' ')
+    }
+}
@@ -0,0 +1,140 @@
+//! Snapshots of a run written every few steps, so a long run survives a crash or power loss.
+//!
+//! Checkpoints are named by their step count, `checkpoint-120000000.ant`, and written next to
+//! the final name first so a checkpoint is never left half written. Only the newest few are
+//! kept.
+//!
+//! A checkpoint is a gzipped `Simulation::snapshot`.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use gzip::{self, GzipWriter};
+use simulation::Simulation;
+
+/// Number of checkpoints kept in the directory, older ones are removed.
+pub const KEEP: usize = 2;
+
+/// Checkpoints of a run going into one directory.
+#[derive(Clone, Debug)]
+pub struct Checkpoints {
+    dir: PathBuf,
+    every: u64,
+}
+
+impl Checkpoints {
+    /// Write a checkpoint into `dir` every `every` steps, creating the directory if needed.
+    pub fn new(dir: &Path, every: u64) -> Result<Checkpoints, String> {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+        Ok(Checkpoints {
+            dir: dir.to_path_buf(),
+            every: every.max(1),
+        })
+    }
+
+    /// Whether a checkpoint is due after `steps` steps.
+    pub fn due(&self, steps: u64) -> bool {
+        steps.is_multiple_of(self.every)
+    }
+
+    /// Save `sim` and remove checkpoints past the newest `KEEP`, returning the path written.
+    pub fn write(&self, sim: &Simulation) -> Result<PathBuf, String> {
+        let path = self.dir.join(format!("checkpoint-{}.ant", sim.steps));
+        save(&path, sim)?;
+        let mut old = list(&self.dir)?;
+        old.sort();
+        old.reverse();
+        for (_, stale) in old.into_iter().skip(KEEP) {
+            // A checkpoint that can't be removed is only wasted space.
+            let _ = fs::remove_file(stale);
+        }
+        Ok(path)
+    }
+}
+
+/// Write `sim` to `path`.
+pub fn save(path: &Path, sim: &Simulation) -> Result<(), String> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    GzipWriter::new(Vec::new())
+        .and_then(|mut out| {
+            out.write_all(&sim.snapshot())?;
+            out.finish()
+        })
+        .and_then(|bytes| fs::write(&partial, bytes))
+        .and_then(|_| fs::rename(&partial, path))
+        .map_err(|e| format!("Could not write checkpoint {}: {}", path.display(), e))
+}
+
+/// Read the checkpoint at `path`, or the newest one if `path` is a directory.
+pub fn load(path: &Path) -> Result<Simulation, String> {
+    let path = if path.is_dir() {
+        newest(path)?.ok_or_else(|| format!("No checkpoints in {}", path.display()))?
+    } else {
+        path.to_path_buf()
+    };
+    let bytes =
+        fs::read(&path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    let snapshot = gzip::gunzip(&bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Simulation::restore(&snapshot).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// The checkpoint in `dir` with the most steps.
+pub fn newest(dir: &Path) -> Result<Option<PathBuf>, String> {
+    Ok(list(dir)?.into_iter().max().map(|(_, path)| path))
+}
+
+/// Step count and path of every checkpoint in `dir`.
+fn list(dir: &Path) -> Result<Vec<(u64, PathBuf)>, String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Could not read {}: {}", dir.display(), e))?;
+    Ok(entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let steps = path
+                .file_name()?
+                .to_str()?
+                .strip_prefix("checkpoint-")?
+                .strip_suffix(".ant")?
+                .parse()
+                .ok()?;
+            Some((steps, path))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of its own for test `name`.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("langton-ant-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn checkpoints_are_gzipped_and_load_back() {
+        let dir = scratch("checkpoints");
+        let checkpoints = Checkpoints::new(&dir, 100).unwrap();
+        let mut sim = Simulation::new(30, 30);
+        for _ in 0..4 {
+            for _ in 0..100 {
+                sim.step().unwrap();
+            }
+            checkpoints.write(&sim).unwrap();
+        }
+        assert_eq!(list(&dir).unwrap().len(), KEEP);
+
+        let newest = newest(&dir).unwrap().unwrap();
+        assert_eq!(newest, dir.join("checkpoint-400.ant"));
+        let bytes = fs::read(&newest).unwrap();
+        assert_eq!(gzip::gunzip(&bytes).unwrap(), sim.snapshot());
+        assert_eq!(load(&dir).unwrap().snapshot(), sim.snapshot());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,557 @@
+//! Defaults loaded from a TOML config file, overridden by the command line.
+//!
+//! Only the subset of TOML a settings file needs is understood: `key = value` pairs with
+//! strings, numbers, booleans and arrays, `[table]` headers and `#` comments. Keys inside a
+//! table are flattened to `table.key`.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use keys::{self, Action};
+use langton_ant::rule::{self, Schedule};
+use {Options, Start};
+
+/// A TOML value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn as_bool(&self, key: &str) -> Result<bool, String> {
+        match *self {
+            Value::Boolean(b) => Ok(b),
+            _ => Err(format!("{} should be true or false", key)),
+        }
+    }
+
+    /// Parse the value the same way the matching command line flag is parsed.
+    pub fn parse<T: FromStr>(&self, key: &str) -> Result<T, String> {
+        let text = match *self {
+            Value::String(ref s) => s.clone(),
+            Value::Array(_) => return Err(format!("{} can't be an array", key)),
+            ref other => other.to_string(),
+        };
+        text.parse()
+            .map_err(|_| format!("Invalid value for {}: {}", key, self))
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::String(ref s) => write!(f, "{:?}", s),
+            Value::Integer(i) => write!(f, "{}", i),
+            // Keep a decimal point so the value reads back as a float.
+            Value::Float(x) => write!(f, "{:?}", x),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Array(ref values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/langtons_ant`, falling back to `~/.config`.
+pub fn dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("langtons_ant"))
+}
+
+/// `config.toml` in `dir`.
+pub fn default_path() -> Option<PathBuf> {
+    Some(dir()?.join("config.toml"))
+}
+
+/// Read and parse the config file at `path`.
+pub fn load(path: &Path) -> Result<Vec<(String, Value)>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Parse TOML text into flattened keys and values, in file order.
+pub fn parse(text: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut entries = Vec::new();
+    let mut table = String::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let error = |e: String| format!("line {}: {}", number + 1, e);
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            if !line.ends_with(']') {
+                return Err(error("Unclosed table header".to_string()));
+            }
+            table = line[1..line.len() - 1].trim().to_string();
+            continue;
+        }
+
+        let eq = line
+            .find('=')
+            .ok_or_else(|| error("Expected key = value".to_string()))?;
+        let key = line[..eq].trim().trim_matches('"');
+        if key.is_empty() {
+            return Err(error("Missing key".to_string()));
+        }
+        let (value, rest) = parse_value(line[eq + 1..].trim()).map_err(error)?;
+        if !rest.trim().is_empty() {
+            return Err(error(format!("Unexpected {:?} after value", rest.trim())));
+        }
+
+        let key = if table.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", table, key)
+        };
+        entries.push((key, value));
+    }
+
+    Ok(entries)
+}
+
+/// Cut a `#` comment off `line`, leaving any inside strings alone.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' if in_string => {
+                escaped = !escaped;
+                continue;
+            }
+            '"' if !escaped => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+/// Parse one value from the start of `text`, returning it and whatever follows.
+fn parse_value(text: &str) -> Result<(Value, &str), String> {
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Ok((Value::String(value), &rest[i + 1..])),
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(c @ '"') | Some(c @ '\\') => value.push(c),
+                    other => return Err(format!("Unknown escape \\{}", other.unwrap_or(' '))),
+                },
+                c => value.push(c),
+            }
+        }
+        return Err("Unclosed string".to_string());
+    }
+
+    if let Some(mut rest) = text.strip_prefix('[') {
+        let mut values = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(values), after));
+            }
+            let (value, after) = parse_value(rest)?;
+            values.push(value);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err("Expected , or ] in array".to_string());
+            }
+        }
+    }
+
+    let end = text
+        .find(|c: char| c == ',' || c == ']' || c.is_whitespace())
+        .unwrap_or(text.len());
+    let (word, rest) = text.split_at(end);
+    let value = match word {
+        "true" => Value::Boolean(true),
+        "false" => Value::Boolean(false),
+        _ => {
+            let number = word.replace('_', "");
+            if let Ok(i) = number.parse() {
+                Value::Integer(i)
+            } else if let Ok(x) = number.parse() {
+                Value::Float(x)
+            } else {
+                return Err(format!("Invalid value {:?}", word));
+            }
+        }
+    };
+    Ok((value, rest))
+}
+
+/// Set the options named by config `entries`.
+pub fn apply(options: &mut Options, entries: &[(String, Value)]) -> Result<(), String> {
+    for (key, value) in entries {
+        match key.as_str() {
+            "backend" => options.backend = value.parse(key)?,
+            "pixels" => options.pixels = value.as_bool(key)?,
+            "braille" => options.braille = value.as_bool(key)?,
+            "ascii" => options.ascii = Some(value.as_bool(key)?),
+            "ascii-cells" => {
+                options.ascii_cells = ::theme::parse_ascii_cells(&value.parse::<String>(key)?)?
+            }
+            "zoom" => options.zoom = value.parse(key)?,
+            "zoom-blend" => options.zoom_blend = value.parse(key)?,
+            "autofit" => options.autofit = value.as_bool(key)?,
+            "edit" => options.edit = value.as_bool(key)?,
+            "speed" => options.speed = value.parse(key)?,
+            "delay" => options.speed = ::speed_from_delay(value.parse(key)?),
+            "path" => options.show_path = value.as_bool(key)?,
+            "counter" => options.show_counter = value.as_bool(key)?,
+            "stats-json" => options.stats_json = value.as_bool(key)?,
+            "random-fill" => options.random_fill = Some(value.parse(key)?),
+            "seed" => options.seed = Some(value.parse(key)?),
+            "start" => {
+                let text: String = value.parse(key)?;
+                options.start = Some(if text == "random" {
+                    Start::Random
+                } else {
+                    let (x, y) = ::parse_point(&text)
+                        .ok_or_else(|| format!("Invalid value for start: {}", value))?;
+                    Start::At(x, y)
+                });
+            }
+            "heading" => options.heading = value.parse(key)?,
+            "rule" => options.rule = rule::parse(&value.parse::<String>(key)?)?,
+            "schedule" => {
+                let schedule: Schedule = value.parse::<String>(key)?.parse()?;
+                options.rule = schedule.entries()[0].1.clone();
+                options.schedule = Some(schedule);
+            }
+            "preset" => ::use_preset(options, &value.parse::<String>(key)?)?,
+            "ants" => {
+                let specs = match *value {
+                    Value::Array(ref values) => values,
+                    _ => return Err(format!("{} should be an array of ants", key)),
+                };
+                options.ants = specs
+                    .iter()
+                    .map(|spec| spec.parse::<String>(key)?.parse())
+                    .collect::<Result<_, String>>()?;
+            }
+            "symmetry" => options.symmetry = value.parse(key)?,
+            "on-wall" => options.on_wall = value.parse(key)?,
+            "boundary" => options.boundary = value.parse(key)?,
+            "background" => options.background = value.parse(key)?,
+            "collision" => options.collision = value.parse(key)?,
+            "life-every" => options.life_every = value.parse(key)?,
+            "noise" => options.noise = value.parse(key)?,
+            "fade" => options.fade = value.parse(key)?,
+            "detect-cycles" => options.detect_cycles = value.as_bool(key)?,
+            "slowmo-on" => {
+                let events = match *value {
+                    Value::Array(ref values) => values,
+                    _ => return Err(format!("{} should be an array of events", key)),
+                };
+                options.slowmo_on = events
+                    .iter()
+                    .map(|event| event.parse::<String>(key)?.parse())
+                    .collect::<Result<_, String>>()?;
+            }
+            "checkpoint-every" => options.checkpoint_every = value.parse(key)?,
+            "checkpoint-dir" => options.checkpoint_dir = PathBuf::from(value.parse::<String>(key)?),
+            "screenshot-dir" => options.screenshot_dir = PathBuf::from(value.parse::<String>(key)?),
+            "ant-glyphs" => options.ant_glyphs = ::parse_glyphs(&value.parse::<String>(key)?)?,
+            "theme" | "theme.name" => options.theme = value.parse::<String>(key)?.parse()?,
+            "steps" => options.steps = Some(value.parse(key)?),
+            "duration" => options.duration = Some(::parse_duration(&value.parse::<String>(key)?)?),
+            "on-finish" => options.on_finish = value.parse(key)?,
+            "screensaver" => options.screensaver = value.as_bool(key)?,
+            "screensaver-rules" => {
+                let specs = match *value {
+                    Value::Array(ref values) => values,
+                    _ => return Err(format!("{} should be an array of rules", key)),
+                };
+                options.screensaver_rules = specs
+                    .iter()
+                    .map(|spec| rule::parse(&spec.parse::<String>(key)?))
+                    .collect::<Result<_, String>>()?;
+            }
+            "frame-every" => options.frame_every = value.parse(key)?,
+            "scale" => options.scale = value.parse(key)?,
+            "frame-delay" => options.frame_delay = value.parse(key)?,
+            "size" => {
+                let text: String = value.parse(key)?;
+                options.size = Some(
+                    ::parse_size(&text)
+                        .ok_or_else(|| format!("Invalid value for size: {}", value))?,
+                );
+            }
+            "split" => {
+                let text: String = value.parse(key)?;
+                options.split = Some(
+                    ::parse_size(&text)
+                        .filter(|&(columns, rows)| columns > 0 && rows > 0)
+                        .ok_or_else(|| format!("Invalid value for split: {}", value))?,
+                );
+            }
+            "pane-rules" => {
+                let specs = match *value {
+                    Value::Array(ref values) => values,
+                    _ => return Err(format!("{} should be an array of rules", key)),
+                };
+                options.pane_rules = specs
+                    .iter()
+                    .map(|spec| rule::parse(&spec.parse::<String>(key)?))
+                    .collect::<Result<_, String>>()?;
+            }
+            _ if key.starts_with("theme.") => options.theme.change(&key["theme.".len()..], value)?,
+            _ if key.starts_with("keys.") => {
+                let action = &key["keys.".len()..];
+                let key: String = value.parse(key)?;
+                options.keys.bind_spec(&format!("{}={}", action, key))?;
+            }
+            _ => return Err(format!("Unknown setting {}", key)),
+        }
+    }
+    Ok(())
+}
+
+/// The effective settings in config file form.
+pub fn dump(options: &Options) -> String {
+    let mut out = String::new();
+    {
+        let mut line = |key: &str, value: Value| out.push_str(&format!("{} = {}\n", key, value));
+        // First, so that the settings below still override it when read back.
+        if let Some(preset) = options.preset {
+            line("preset", Value::String(preset.name.to_string()));
+        }
+        line("backend", Value::String(options.backend.clone()));
+        line("pixels", Value::Boolean(options.pixels));
+        line("braille", Value::Boolean(options.braille));
+        if let Some(ascii) = options.ascii {
+            line("ascii", Value::Boolean(ascii));
+        }
+        let cells: String = options.ascii_cells.iter().collect();
+        line("ascii-cells", Value::String(cells));
+        line("zoom", Value::Integer(i64::from(options.zoom)));
+        line("zoom-blend", Value::String(options.zoom_blend.name().to_string()));
+        line("autofit", Value::Boolean(options.autofit));
+        line("edit", Value::Boolean(options.edit));
+        line("speed", Value::Float(options.speed));
+        line("path", Value::Boolean(options.show_path));
+        line("counter", Value::Boolean(options.show_counter));
+        line("stats-json", Value::Boolean(options.stats_json));
+        if let Some(density) = options.random_fill {
+            line("random-fill", Value::Float(density));
+        }
+        match options.seed {
+            // TOML integers are signed, larger seeds are written as strings.
+            Some(seed) if seed <= i64::MAX as u64 => line("seed", Value::Integer(seed as i64)),
+            Some(seed) => line("seed", Value::String(seed.to_string())),
+            None => {}
+        }
+        match options.start {
+            Some(Start::At(x, y)) => line("start", Value::String(format!("{},{}", x, y))),
+            Some(Start::Random) => line("start", Value::String("random".to_string())),
+            None => {}
+        }
+        line("heading", Value::String(options.heading.name().to_string()));
+        line("rule", Value::String(options.rule.to_string()));
+        if let Some(ref schedule) = options.schedule {
+            line("schedule", Value::String(schedule.to_string()));
+        }
+        if !options.ants.is_empty() {
+            let specs = options.ants.iter().map(|spec| Value::String(spec.to_string()));
+            line("ants", Value::Array(specs.collect()));
+        }
+        line("symmetry", Value::Integer(i64::from(options.symmetry.order())));
+        line("on-wall", Value::String(options.on_wall.name().to_string()));
+        line("boundary", Value::String(options.boundary.name().to_string()));
+        line("background", Value::String(options.background.name().to_string()));
+        line("collision", Value::String(options.collision.name().to_string()));
+        line("life-every", Value::Integer(options.life_every as i64));
+        line("noise", Value::Float(options.noise));
+        line("fade", Value::Integer(options.fade as i64));
+        let events = options.slowmo_on.iter();
+        let names = events.map(|event| Value::String(event.name().to_string()));
+        line("slowmo-on", Value::Array(names.collect()));
+        line("detect-cycles", Value::Boolean(options.detect_cycles));
+        line("checkpoint-every", Value::Integer(options.checkpoint_every as i64));
+        let dir = options.checkpoint_dir.display().to_string();
+        line("checkpoint-dir", Value::String(dir));
+        let dir = options.screenshot_dir.display().to_string();
+        line("screenshot-dir", Value::String(dir));
+        let glyphs: String = options.ant_glyphs.iter().collect();
+        line("ant-glyphs", Value::String(glyphs));
+        if let Some(steps) = options.steps {
+            line("steps", Value::Integer(steps as i64));
+        }
+        if let Some(duration) = options.duration {
+            line("duration", Value::String(::duration_name(duration)));
+        }
+        line("on-finish", Value::String(options.on_finish.name().to_string()));
+        line("screensaver", Value::Boolean(options.screensaver));
+        let rules = options.screensaver_rules.iter();
+        let specs = rules.map(|rule| Value::String(rule.to_string()));
+        line("screensaver-rules", Value::Array(specs.collect()));
+        line("frame-every", Value::Integer(options.frame_every as i64));
+        line("scale", Value::Integer(i64::from(options.scale)));
+        line("frame-delay", Value::Integer(i64::from(options.frame_delay)));
+        if let Some((w, h)) = options.size {
+            line("size", Value::String(format!("{}x{}", w, h)));
+        }
+        if let Some((columns, rows)) = options.split {
+            line("split", Value::String(format!("{}x{}", columns, rows)));
+        }
+        if !options.pane_rules.is_empty() {
+            let specs = options.pane_rules.iter().map(|rule| Value::String(rule.to_string()));
+            line("pane-rules", Value::Array(specs.collect()));
+        }
+    }
+
+    out.push_str("\n[theme]\n");
+    let name = Value::String(options.theme.source().to_string());
+    out.push_str(&format!("name = {}\n", name));
+    for (key, value) in options.theme.changes() {
+        out.push_str(&format!("{} = {}\n", key, value));
+    }
+
+    out.push_str("\n[keys]\n");
+    for &action in Action::ALL {
+        if let Some(key) = options.keys.key(action) {
+            out.push_str(&format!(
+                "{} = {}\n",
+                action.name(),
+                Value::String(keys::key_name(key))
+            ));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A dump with every setting changed from its default.
+    const EVERY_SETTING: &str = r##"preset = "fibonacci-spiral"
+backend = "ansi"
+pixels = true
+braille = true
+ascii = true
+ascii-cells = " #"
+zoom = 2
+zoom-blend = "max"
+autofit = true
+edit = true
+speed = 20.0
+path = true
+counter = false
+stats-json = true
+random-fill = 0.25
+seed = 42
+start = "3,4"
+heading = "up"
+rule = "LLRR"
+schedule = "0:LLRR,500:RLR"
+ants = ["3,4,up,LLRR,#e02020"]
+symmetry = 2
+on-wall = "turn"
+boundary = "grow"
+background = "life"
+collision = "swap"
+life-every = 50
+noise = 0.5
+fade = 10
+slowmo-on = ["highway", "edge"]
+detect-cycles = true
+checkpoint-every = 1000
+checkpoint-dir = "cps"
+screenshot-dir = "shots"
+ant-glyphs = "^v<>"
+steps = 5000
+duration = "30s"
+on-finish = "loop"
+screensaver = true
+screensaver-rules = ["RL", "LR"]
+frame-every = 10
+scale = 2
+frame-delay = 8
+size = "50x40"
+split = "2x1"
+pane-rules = ["LR", "LLRR"]
+
+[theme]
+name = "solarized"
+cell.1.glyph = "#"
+
+[keys]
+quit = "q"
+pause = "space"
+step = "."
+step-back = ","
+rewind = "u"
+reverse = "R"
+faster = "+"
+slower = "-"
+pan-up = "up"
+pan-down = "down"
+pan-left = "left"
+pan-right = "right"
+zoom-in = "z"
+zoom-out = "Z"
+autofit = "b"
+export = "e"
+edit = "E"
+graph = "g"
+histogram = "h"
+save-1 = "f1"
+save-2 = "f2"
+save-3 = "f3"
+save-4 = "f4"
+load-1 = "f5"
+load-2 = "f6"
+load-3 = "f7"
+load-4 = "f8"
+screenshot = "p"
+"##;
+
+    #[test]
+    fn dump_reads_back() {
+        let mut options = Options::default();
+        apply(&mut options, &parse(EVERY_SETTING).unwrap()).unwrap();
+        assert_eq!(dump(&options), EVERY_SETTING);
+
+        // A setting left at its default wouldn't show that it is read back.
+        let defaults = parse(&dump(&Options::default())).unwrap();
+        for entry in parse(EVERY_SETTING).unwrap() {
+            // Without curses the only backend is the default one.
+            if entry.0 == "backend" && cfg!(not(feature = "curses")) {
+                continue;
+            }
+            if !entry.0.starts_with("keys.") || entry.0 == "keys.export" || entry.0 == "keys.edit" {
+                assert!(!defaults.contains(&entry), "{} is the default", entry.0);
+            }
+        }
+    }
+}
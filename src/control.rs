@@ -0,0 +1,278 @@
+//! The `--control-socket` server, letting scripts drive a running simulation.
+//!
+//! Clients send one command per line and get one line back for each, starting with `ok` or
+//! `error`:
+//!
+//! ```text
+//! pause              hold the run
+//! resume             carry on after pause
+//! step N             take N steps right away, 1 if not given and at most a million
+//! set-speed N        steps per second, 0 for as fast as possible
+//! query-state        the step count, speed and ants as JSON
+//! screenshot [FILE]  export the grid, to a numbered file next to --export-on-exit by default
+//! quit               end the run
+//! ```
+
+#[cfg(unix)]
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Most steps one `step` may ask for. They are all taken before the next frame, so the display
+/// and every other client wait for them.
+pub const MAX_STEPS: u64 = 1_000_000;
+
+/// A request from a client.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Pause,
+    Resume,
+    Step(u64),
+    SetSpeed(f64),
+    QueryState,
+    Screenshot(Option<PathBuf>),
+    Quit,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Command, String> {
+        let mut words = s.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let arg = words.next();
+        if words.next().is_some() {
+            return Err(format!("Too many arguments to {}", name));
+        }
+        let command = match name {
+            "pause" => Command::Pause,
+            "resume" => Command::Resume,
+            "step" if arg.is_none() => Command::Step(1),
+            "step" => {
+                let steps = parse_arg(name, arg)?;
+                if steps > MAX_STEPS {
+                    return Err(format!("Can't take more than {} steps at once", MAX_STEPS));
+                }
+                Command::Step(steps)
+            }
+            "set-speed" => {
+                let speed: f64 = parse_arg(name, arg)?;
+                if speed.is_nan() || speed < 0.0 {
+                    return Err(format!("Invalid speed {}", speed));
+                }
+                Command::SetSpeed(speed)
+            }
+            "query-state" => Command::QueryState,
+            "screenshot" => Command::Screenshot(arg.map(PathBuf::from)),
+            "quit" => Command::Quit,
+            _ => return Err(format!("Unknown command {:?}", name)),
+        };
+        match command {
+            Command::Step(_) | Command::SetSpeed(_) | Command::Screenshot(_) => {}
+            _ if arg.is_some() => return Err(format!("{} takes no arguments", name)),
+            _ => {}
+        }
+        Ok(command)
+    }
+}
+
+/// The argument of command `name`, which needs one.
+fn parse_arg<T: FromStr>(name: &str, arg: Option<&str>) -> Result<T, String> {
+    let arg = arg.ok_or_else(|| format!("{} needs an argument", name))?;
+    arg.parse()
+        .map_err(|_| format!("Invalid value for {}: {}", name, arg))
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    /// Also the path, to remove the socket file again.
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+trait Stream: Read + Write {}
+impl<T: Read + Write> Stream for T {}
+
+struct Client {
+    stream: Box<dyn Stream>,
+    /// Bytes read that don't make up a whole line yet.
+    pending: Vec<u8>,
+}
+
+/// Listens for clients without ever blocking the main loop.
+pub struct Server {
+    listener: Listener,
+    clients: Vec<Client>,
+}
+
+impl Server {
+    /// Listen on `address`: a TCP port on localhost, a `host:port` pair, or else the path of a
+    /// Unix socket to create.
+    pub fn bind(address: &str) -> Result<Server, String> {
+        let error = |e: io::Error| format!("Could not listen on {}: {}", address, e);
+        let listener = if let Ok(port) = address.parse::<u16>() {
+            Listener::Tcp(TcpListener::bind(("127.0.0.1", port)).map_err(error)?)
+        } else if address.contains(':') {
+            Listener::Tcp(TcpListener::bind(address).map_err(error)?)
+        } else {
+            bind_unix(address)?
+        };
+        match listener {
+            Listener::Tcp(ref listener) => listener.set_nonblocking(true),
+            #[cfg(unix)]
+            Listener::Unix(ref listener, _) => listener.set_nonblocking(true),
+        }
+        .map_err(error)?;
+        Ok(Server {
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    /// Take in new clients and run every command they sent through `handle`, sending back
+    /// what it returns.
+    pub fn poll<F: FnMut(Command) -> Result<String, String>>(&mut self, mut handle: F) {
+        while let Some(stream) = self.accept() {
+            self.clients.push(Client {
+                stream,
+                pending: Vec::new(),
+            });
+        }
+
+        self.clients.retain_mut(|client| {
+            let mut buf = [0; 1024];
+            let open = loop {
+                match client.stream.read(&mut buf) {
+                    Ok(0) => break false,
+                    Ok(n) => client.pending.extend_from_slice(&buf[..n]),
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break true,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(_) => break false,
+                }
+            };
+
+            while let Some(end) = client.pending.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = client.pending.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let reply = match line.parse().and_then(&mut handle) {
+                    Ok(ref text) if text.is_empty() => "ok\n".to_string(),
+                    Ok(text) => format!("ok {}\n", text),
+                    Err(e) => format!("error {}\n", e),
+                };
+                // Replies are short, a client not reading them is dropped.
+                if client.stream.write_all(reply.as_bytes()).is_err() {
+                    return false;
+                }
+            }
+            open
+        });
+    }
+
+    fn accept(&self) -> Option<Box<dyn Stream>> {
+        match self.listener {
+            Listener::Tcp(ref listener) => {
+                let (stream, _) = listener.accept().ok()?;
+                setup_stream(stream, TcpStream::set_nonblocking)
+            }
+            #[cfg(unix)]
+            Listener::Unix(ref listener, _) => {
+                let (stream, _) = listener.accept().ok()?;
+                setup_stream(stream, UnixStream::set_nonblocking)
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Listener::Unix(_, ref path) = self.listener {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Box up a new connection, which has to be non-blocking to be read from the main loop.
+fn setup_stream<S: Stream + 'static>(
+    stream: S,
+    set_nonblocking: fn(&S, bool) -> io::Result<()>,
+) -> Option<Box<dyn Stream>> {
+    set_nonblocking(&stream, true).ok()?;
+    Some(Box::new(stream))
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &str) -> Result<Listener, String> {
+    // A socket left behind by an earlier run would make binding fail. Anything else at the
+    // path is left alone.
+    if let Ok(meta) = fs::symlink_metadata(path) {
+        use std::os::unix::fs::FileTypeExt;
+        if meta.file_type().is_socket() && UnixStream::connect(path).is_err() {
+            let _ = fs::remove_file(path);
+        }
+    }
+    let listener = UnixListener::bind(path)
+        .map_err(|e| format!("Could not listen on {}: {}", path, e))?;
+    Ok(Listener::Unix(listener, PathBuf::from(path)))
+}
+
+#[cfg(not(unix))]
+fn bind_unix(path: &str) -> Result<Listener, String> {
+    Err(format!(
+        "Unix sockets aren't available here, give --control-socket a port instead of {}",
+        path
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        let commands = [
+            ("pause", Command::Pause),
+            ("resume", Command::Resume),
+            ("step", Command::Step(1)),
+            ("  step   25 ", Command::Step(25)),
+            ("step 1000000", Command::Step(MAX_STEPS)),
+            ("set-speed 0", Command::SetSpeed(0.0)),
+            ("set-speed 12.5", Command::SetSpeed(12.5)),
+            ("query-state", Command::QueryState),
+            ("screenshot", Command::Screenshot(None)),
+            ("screenshot out.png", Command::Screenshot(Some(PathBuf::from("out.png")))),
+            ("quit", Command::Quit),
+        ];
+        for &(line, ref command) in &commands {
+            assert_eq!(line.parse::<Command>().as_ref(), Ok(command), "{}", line);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_commands() {
+        let errors = [
+            ("", "Unknown command"),
+            ("jump", "Unknown command"),
+            ("step -1", "Invalid value for step"),
+            ("step two", "Invalid value for step"),
+            ("step 1 2", "Too many arguments"),
+            ("step 1000001", "more than 1000000 steps"),
+            ("set-speed", "needs an argument"),
+            ("set-speed -5", "Invalid speed"),
+            ("set-speed NaN", "Invalid speed"),
+            ("pause now", "takes no arguments"),
+            ("quit 1", "takes no arguments"),
+        ];
+        for &(line, error) in &errors {
+            let got = line.parse::<Command>().unwrap_err();
+            assert!(got.contains(error), "{}: {}", line, got);
+        }
+    }
+}
@@ -0,0 +1,22 @@
+//! The cells a step changed, gathered while the run is observed so only they are drawn again.
+
+use langton_ant::simulation::{Observer, State};
+
+/// Column and row of every cell changed since the last `take`.
+#[derive(Default)]
+pub struct Damage {
+    cells: Vec<(usize, usize)>,
+}
+
+impl Damage {
+    /// The cells changed since the last call, in the order they changed.
+    pub fn take(&mut self) -> Vec<(usize, usize)> {
+        std::mem::take(&mut self.cells)
+    }
+}
+
+impl Observer for Damage {
+    fn on_cell_changed(&mut self, x: usize, y: usize, _from: State, _to: State) {
+        self.cells.push((x, y));
+    }
+}
@@ -0,0 +1,121 @@
+//! The `--emit-steps` stream: one JSON object per ant and step, for other programs to follow a
+//! run as it happens.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use langton_ant::simulation::{Ant, Direction, Observer, State};
+
+/// Writes step events to a file or to stdout.
+pub struct Emitter {
+    out: BufWriter<Box<dyn Write>>,
+}
+
+impl Emitter {
+    /// Write to the file at `path`, or to stdout for `-`.
+    ///
+    /// The grid needs the terminal, so with stdout the grid is drawn on `/dev/tty` instead and
+    /// stdout must be redirected to a file or pipe.
+    pub fn open(path: &Path) -> Result<Emitter, String> {
+        let out: Box<dyn Write> = if path == Path::new("-") {
+            Box::new(take_stdout()?)
+        } else {
+            Box::new(
+                File::create(path)
+                    .map_err(|e| format!("Could not create {}: {}", path.display(), e))?,
+            )
+        };
+        Ok(Emitter {
+            out: BufWriter::new(out),
+        })
+    }
+
+    /// Record that `ant` is at `x`, `y` heading `heading` after step `step`, and its cell went
+    /// from `old` to `new`. An ant turned away by a wall stays put and keeps its color.
+    #[allow(clippy::too_many_arguments)]
+    pub fn step(
+        &mut self,
+        step: u64,
+        ant: usize,
+        x: i64,
+        y: i64,
+        heading: Direction,
+        old: State,
+        new: State,
+    ) -> io::Result<()> {
+        writeln!(
+            self.out,
+            r#"{{"step":{},"ant":{},"x":{},"y":{},"heading":"{}","old":{},"new":{}}}"#,
+            step,
+            ant,
+            x,
+            y,
+            heading.name(),
+            old.0,
+            new.0
+        )
+    }
+
+    /// Hand the lines written so far to the reader.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// What each ant did in the steps since the last `take`, gathered while the run is observed:
+/// its number when the step began, where it ended up, and the color its cell went from and to.
+#[derive(Default)]
+pub struct Moves {
+    moves: Vec<(usize, Ant, State, State)>,
+}
+
+impl Moves {
+    /// The moves since the last call, in the order the ants made them.
+    pub fn take(&mut self) -> Vec<(usize, Ant, State, State)> {
+        std::mem::take(&mut self.moves)
+    }
+}
+
+impl Observer for Moves {
+    fn on_ant_moved(&mut self, index: usize, ant: &Ant, from: State, to: State) {
+        self.moves.push((index, *ant, from, to));
+    }
+}
+
+/// A handle on stdout for the events, pointing stdout itself at the terminal for the backends.
+#[cfg(unix)]
+fn take_stdout() -> Result<File, String> {
+    use std::os::unix::io::FromRawFd;
+
+    unsafe {
+        if libc::isatty(libc::STDOUT_FILENO) == 1 {
+            return Err(
+                "--emit-steps - needs stdout redirected to a file or pipe, the terminal shows the grid"
+                    .to_string(),
+            );
+        }
+        let tty = libc::open(b"/dev/tty\0".as_ptr() as *const libc::c_char, libc::O_RDWR);
+        if tty < 0 {
+            return Err(format!(
+                "Could not open /dev/tty to draw on: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        let events = libc::dup(libc::STDOUT_FILENO);
+        if events < 0 || libc::dup2(tty, libc::STDOUT_FILENO) < 0 {
+            return Err(format!(
+                "Could not move the grid off stdout: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        libc::close(tty);
+        Ok(File::from_raw_fd(events))
+    }
+}
+
+/// The Windows console is drawn on through its own handle, so stdout is free to use as is.
+#[cfg(not(unix))]
+fn take_stdout() -> Result<io::Stdout, String> {
+    Ok(io::stdout())
+}
@@ -0,0 +1,231 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "image")]
+use palette;
+#[cfg(feature = "image")]
+use png;
+use simulation::{Bounds, Cell, Simulation};
+
+/// File formats a grid snapshot can be written in.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    /// Plain text, `#` for painted cells, `.` for black ones and `X` for walls. Loadable with
+    /// `--pattern`.
+    Text,
+    /// Netpbm bitmap, painted cells and walls being set.
+    Pbm,
+    /// Indexed PNG, keeping every cell color.
+    #[cfg(feature = "image")]
+    Png,
+}
+
+impl Format {
+    /// Pick the format from the extension of `path`.
+    pub fn from_path(path: &Path) -> Result<Format, String> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("txt") => Ok(Format::Text),
+            Some("pbm") => Ok(Format::Pbm),
+            #[cfg(feature = "image")]
+            Some("png") => Ok(Format::Png),
+            _ => Err(format!(
+                "Unsupported export format for {}, expected .txt, .pbm or .png",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// Write the grid to `path` and the ant's position to a `.json` file next to it.
+pub fn export(path: &Path, sim: &Simulation) -> Result<(), String> {
+    let format = Format::from_path(path)?;
+    write_grid(path, format, sim)
+        .and_then(|_| write_metadata(&metadata_path(path), sim))
+        .map_err(|e| format!("Could not export to {}: {}", path.display(), e))
+}
+
+/// The sidecar file holding the ant position for the snapshot at `path`.
+fn metadata_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".json");
+    PathBuf::from(name)
+}
+
+/// `path` with the step count worked into the file name, e.g. `out.png` becomes `out-120.png`.
+pub fn numbered_path(path: &Path, steps: u64) -> PathBuf {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("grid");
+    let name = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => format!("{}-{}.{}", stem, steps, ext),
+        None => format!("{}-{}", stem, steps),
+    };
+    path.with_file_name(name)
+}
+
+/// The part of the grid on screen at one moment, as the renderer drew it.
+#[derive(Clone, Debug)]
+pub struct Viewport {
+    /// The cells in view.
+    pub cells: Bounds,
+    /// The characters shown for them, a line per terminal row.
+    pub lines: Vec<String>,
+}
+
+/// Write `viewport` of `sim` into `dir` as text, and as a PNG of the cells in view when PNGs
+/// are supported, returning the paths written.
+///
+/// Files are named by the time and step count, like `screenshot-20240131-174502-1200.txt`.
+pub fn screenshot(
+    dir: &Path,
+    sim: &Simulation,
+    viewport: &Viewport,
+) -> Result<Vec<PathBuf>, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+    let name = format!("screenshot-{}-{}", timestamp(SystemTime::now()), sim.steps);
+    let error = |path: &Path, e: io::Error| format!("Could not write {}: {}", path.display(), e);
+
+    let mut paths = Vec::new();
+    let text = dir.join(format!("{}.txt", name));
+    fs::write(&text, viewport.lines.join("\n") + "\n").map_err(|e| error(&text, e))?;
+    paths.push(text);
+    #[cfg(feature = "image")]
+    {
+        let png = dir.join(format!("{}.png", name));
+        write_cells_png(&png, sim, viewport.cells).map_err(|e| error(&png, e))?;
+        paths.push(png);
+    }
+    Ok(paths)
+}
+
+/// Write the visited part of the grid to `path` as a PNG, the ants on top.
+#[cfg(feature = "image")]
+pub fn thumbnail(path: &Path, sim: &Simulation) -> Result<(), String> {
+    let cells = sim.visited.unwrap_or_else(|| sim.arena());
+    write_cells_png(path, sim, cells)
+        .map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+/// The cells in `cells`, with the ants on top, a pixel each.
+#[cfg(feature = "image")]
+fn write_cells_png(path: &Path, sim: &Simulation, cells: Bounds) -> io::Result<()> {
+    let indices = palette::indices(sim);
+    let (left, right) = (cells.min_x as usize, cells.max_x as usize);
+    let pixels: Vec<u8> = (cells.min_y as usize..=cells.max_y as usize)
+        .flat_map(|y| indices[y * sim.width() + left..=y * sim.width() + right].iter().cloned())
+        .collect();
+    let mut out = BufWriter::new(File::create(path)?);
+    let (width, height) = (cells.width() as u32, cells.height() as u32);
+    png::write(&mut out, width, height, &palette::indexed(sim), &pixels)?;
+    out.flush()
+}
+
+/// `time` as `YYYYMMDD-HHMMSS`, in UTC.
+fn timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // Days since 1970 to a date, counting in 400 year eras from March 1st of the year 0, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Whether `cell` is anything but black. The text and bitmap formats only have two colors.
+fn is_white(cell: &Cell) -> bool {
+    cell.wall || !cell.state.is_blank()
+}
+
+fn write_grid(path: &Path, format: Format, sim: &Simulation) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let (width, height) = (sim.width(), sim.height());
+
+    match format {
+        Format::Text => {
+            for row in sim.rows() {
+                let line: String = row
+                    .iter()
+                    .map(|cell| {
+                        if cell.wall {
+                            'X'
+                        } else if is_white(cell) {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                writeln!(out, "{}", line)?;
+            }
+        }
+        Format::Pbm => {
+            // White cells are drawn dark on screen, so they are the set bits.
+            writeln!(out, "P1\n{} {}", width, height)?;
+            for row in sim.rows() {
+                let bits: Vec<&str> = row
+                    .iter()
+                    .map(|cell| if is_white(cell) { "1" } else { "0" })
+                    .collect();
+                writeln!(out, "{}", bits.join(" "))?;
+            }
+        }
+        #[cfg(feature = "image")]
+        Format::Png => {
+            let pixels: Vec<u8> = sim
+                .grid
+                .iter()
+                .map(|cell| palette::cell_index(sim, cell))
+                .collect();
+            let mut colors = palette::cells(sim);
+            colors.push(palette::WALL);
+            png::write(&mut out, width as u32, height as u32, &colors, &pixels)?;
+        }
+    }
+
+    out.flush()
+}
+
+fn write_metadata(path: &Path, sim: &Simulation) -> io::Result<()> {
+    let mut out = File::create(path)?;
+    let ants: Vec<String> = sim
+        .ants
+        .iter()
+        .map(|ant| {
+            format!(
+                r#"{{"x":{},"y":{},"heading":"{}","rule":"{}"}}"#,
+                ant.x,
+                ant.y,
+                ant.heading.name(),
+                sim.rule_for(ant.rule)
+            )
+        })
+        .collect();
+    // `ant` is the first ant, kept for readers written before there could be several.
+    writeln!(
+        out,
+        r#"{{"width":{},"height":{},"ant":{},"ants":[{}],"steps":{}}}"#,
+        sim.width(),
+        sim.height(),
+        ants[0],
+        ants.join(","),
+        sim.steps
+    )
+}
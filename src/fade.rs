@@ -0,0 +1,90 @@
+//! Shading recently changed cells, so they fade back to their own look over a number of steps.
+
+use std::collections::VecDeque;
+
+/// Number of shades a cell goes through while fading.
+pub const LEVELS: u64 = 3;
+
+/// When each cell last changed, counted in steps taken in either direction.
+pub struct Fade {
+    /// Steps it takes a cell to fade back.
+    length: u64,
+    /// Steps taken so far. Starts at 1 so that 0 can mean never.
+    clock: u64,
+    /// The clock at the last call to `aged`.
+    last_pass: u64,
+    /// Clock reading when each cell last changed, row by row.
+    changed_at: Vec<u64>,
+    /// Cells changed within the last `length` steps, oldest first. A cell changing again gets a
+    /// second entry, the old one is skipped once `changed_at` no longer matches it.
+    recent: VecDeque<(u64, usize)>,
+}
+
+impl Fade {
+    /// Fade over `length` steps on a grid of `cells` cells.
+    pub fn new(length: u64, cells: usize) -> Fade {
+        Fade {
+            length: length.max(1),
+            clock: 1,
+            last_pass: 1,
+            changed_at: vec![0; cells],
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Forget every change, as if the grid was new, fitting a grid of `cells` cells from now on.
+    pub fn resize(&mut self, cells: usize) {
+        *self = Fade::new(self.length, cells);
+    }
+
+    /// Count one step.
+    pub fn tick(&mut self) {
+        self.clock += 1;
+    }
+
+    /// Mark the cell at `index` as just changed.
+    pub fn touch(&mut self, index: usize) {
+        self.changed_at[index] = self.clock;
+        self.recent.push_back((self.clock, index));
+    }
+
+    /// How far the cell at `index` has faded, from 0 right after a change to `LEVELS - 1`, or
+    /// `None` once it is back to normal.
+    pub fn level(&self, index: usize) -> Option<u64> {
+        match self.changed_at[index] {
+            0 => None,
+            changed => self.level_after(self.clock - changed),
+        }
+    }
+
+    fn level_after(&self, age: u64) -> Option<u64> {
+        if age < self.length {
+            Some(age * LEVELS / self.length)
+        } else {
+            None
+        }
+    }
+
+    /// The cells whose shade changed since the last call, to be drawn again.
+    pub fn aged(&mut self) -> Vec<usize> {
+        let mut cells = Vec::new();
+        for &(changed, index) in &self.recent {
+            if self.changed_at[index] != changed {
+                continue;
+            }
+            // Cells changed since the last pass were drawn fresh when they changed.
+            let before = self.level_after(self.last_pass.saturating_sub(changed));
+            if self.level_after(self.clock - changed) != before {
+                cells.push(index);
+            }
+        }
+        while let Some(&(changed, _)) = self.recent.front() {
+            if self.clock - changed < self.length {
+                break;
+            }
+            self.recent.pop_front();
+        }
+        self.last_pass = self.clock;
+        cells
+    }
+}
@@ -0,0 +1,249 @@
+//! A minimal animated GIF encoder.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Writes the frames of a looping animation with a fixed palette.
+pub struct Encoder<W: Write> {
+    out: W,
+    width: u16,
+    height: u16,
+    /// Bits per palette index, at least 2 as required by the LZW coding.
+    depth: u8,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Write the header and the global palette, which is padded to a power of two.
+    pub fn new(mut out: W, width: u16, height: u16, palette: &[[u8; 3]]) -> io::Result<Encoder<W>> {
+        let mut depth = 2;
+        while (1 << depth) < palette.len() && depth < 8 {
+            depth += 1;
+        }
+
+        out.write_all(b"GIF89a")?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        // Global color table present, 8 bit color resolution, table size 2^depth.
+        out.write_all(&[0xF0 | (depth - 1), 0, 0])?;
+        for i in 0..(1 << depth) {
+            out.write_all(palette.get(i).unwrap_or(&[0, 0, 0]))?;
+        }
+        // Loop forever.
+        out.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+
+        Ok(Encoder {
+            out,
+            width,
+            height,
+            depth,
+        })
+    }
+
+    /// Add a frame shown for `delay` hundredths of a second.
+    ///
+    /// `pixels` holds one palette index per pixel, row by row.
+    pub fn frame(&mut self, pixels: &[u8], delay: u16) -> io::Result<()> {
+        assert_eq!(pixels.len(), self.width as usize * self.height as usize);
+
+        // Graphic control extension carrying the delay.
+        self.out.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        self.out.write_all(&delay.to_le_bytes())?;
+        self.out.write_all(&[0x00, 0x00])?;
+
+        // Image descriptor covering the whole canvas, using the global palette.
+        self.out.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.out.write_all(&self.width.to_le_bytes())?;
+        self.out.write_all(&self.height.to_le_bytes())?;
+        self.out.write_all(&[0x00, self.depth])?;
+
+        let data = lzw(pixels, self.depth);
+        for block in data.chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0x00])
+    }
+
+    /// Write the trailer and hand back the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0x3B])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Packs variable width codes least significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u8) {
+        self.buffer |= u32::from(code) << self.bits;
+        self.bits += width;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// GIF flavoured LZW compression of `pixels` with the given minimum code size.
+fn lzw(pixels: &[u8], depth: u8) -> Vec<u8> {
+    let clear = 1u16 << depth;
+    let end = clear + 1;
+    let mut out = BitWriter {
+        bytes: Vec::new(),
+        buffer: 0,
+        bits: 0,
+    };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut width = depth + 1;
+
+    out.write(clear, width);
+    let mut pixels = pixels.iter();
+    let mut prefix = match pixels.next() {
+        Some(&first) => u16::from(first),
+        None => {
+            out.write(end, width);
+            return out.finish();
+        }
+    };
+
+    for &pixel in pixels {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+
+        out.write(prefix, width);
+        if next == 4096 {
+            // The table is full, start over.
+            out.write(clear, width);
+            table.clear();
+            next = end + 1;
+            width = depth + 1;
+        } else {
+            table.insert((prefix, pixel), next);
+            if next == 1 << width && width < 12 {
+                width += 1;
+            }
+            next += 1;
+        }
+        prefix = u16::from(pixel);
+    }
+
+    out.write(prefix, width);
+    out.write(end, width);
+    out.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rng::Rng;
+
+    /// Undo `lzw`, reading codes lowest bit first and growing them as the table does.
+    fn unlzw(data: &[u8], depth: u8) -> Vec<u8> {
+        let clear = 1usize << depth;
+        let end = clear + 1;
+        let reset = || -> Vec<Vec<u8>> {
+            let mut table: Vec<Vec<u8>> = (0..clear).map(|i| vec![i as u8]).collect();
+            table.extend(vec![Vec::new(), Vec::new()]);
+            table
+        };
+        let mut table = reset();
+        let mut width = depth + 1;
+        let mut previous: Option<usize> = None;
+        let mut out = Vec::new();
+        let bit = |at: usize| usize::from(data[at / 8] >> (at % 8) & 1);
+        let mut at = 0;
+        loop {
+            let code: usize = (0..usize::from(width)).map(|i| bit(at + i) << i).sum();
+            at += usize::from(width);
+            if code == clear {
+                table = reset();
+                width = depth + 1;
+                previous = None;
+                continue;
+            } else if code == end {
+                return out;
+            }
+            let entry = match table.get(code) {
+                Some(entry) => entry.clone(),
+                // The code the encoder added right before writing it.
+                None => {
+                    let mut entry = table[previous.unwrap()].clone();
+                    entry.push(entry[0]);
+                    entry
+                }
+            };
+            out.extend_from_slice(&entry);
+            if let Some(previous) = previous {
+                if table.len() < 4096 {
+                    let mut added = table[previous].clone();
+                    added.push(entry[0]);
+                    table.push(added);
+                }
+            }
+            previous = Some(code);
+            if table.len() == 1 << width && width < 12 {
+                width += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let (width, height) = (120u16, 90u16);
+        let palette: Vec<[u8; 3]> = (0..12).map(|i| [i * 20, 0, 255 - i * 20]).collect();
+        let mut rng = Rng::new(3);
+        // Noise fills the code table so it starts over, a single color makes long runs.
+        let pixels = width as usize * height as usize;
+        let noise: Vec<u8> = (0..pixels).map(|_| (rng.next_u64() % 12) as u8).collect();
+        let still = vec![5; noise.len()];
+        let mut encoder = Encoder::new(Vec::new(), width, height, &palette).unwrap();
+        encoder.frame(&noise, 4).unwrap();
+        encoder.frame(&still, 9).unwrap();
+        let out = encoder.finish().unwrap();
+
+        assert_eq!(out[..6], *b"GIF89a");
+        assert_eq!(out[6..10], [120, 0, 90, 0]);
+        // 12 colors are padded to 16.
+        assert_eq!(out[10], 0xF3);
+        let colors = &out[13..13 + 16 * 3];
+        let rgb: Vec<u8> = palette.iter().flat_map(|rgb| rgb.iter().cloned()).collect();
+        assert_eq!(colors[..12 * 3], rgb[..]);
+        assert!(colors[12 * 3..].iter().all(|&c| c == 0));
+
+        let mut rest = &out[13 + 16 * 3 + 19..];
+        for &(pixels, delay) in &[(&noise, 4u16), (&still, 9)] {
+            assert_eq!(rest[..4], [0x21, 0xF9, 0x04, 0x00]);
+            assert_eq!(rest[4..6], delay.to_le_bytes());
+            assert_eq!(rest[8], 0x2C);
+            let depth = rest[18];
+            assert_eq!(depth, 4);
+            rest = &rest[19..];
+            let mut data = Vec::new();
+            while rest[0] != 0 {
+                let len = usize::from(rest[0]);
+                data.extend_from_slice(&rest[1..1 + len]);
+                rest = &rest[1 + len..];
+            }
+            rest = &rest[1..];
+            assert_eq!(&unlzw(&data, depth), pixels);
+        }
+        assert_eq!(rest, [0x3B]);
+    }
+}
@@ -0,0 +1,159 @@
+//! Inline bitmap rendering for terminals that speak sixel or the kitty graphics protocol.
+
+use std::env;
+
+use langton_ant::palette;
+use langton_ant::simulation::Simulation;
+
+/// An image protocol understood by the terminal.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Protocol {
+    Sixel,
+    Kitty,
+}
+
+impl Protocol {
+    /// Guess the protocol from the environment the terminal sets up.
+    ///
+    /// Asking the terminal itself would mean parsing a device attributes reply out of the input
+    /// stream, which every backend would have to cooperate with; the environment is good enough
+    /// for the terminals that support either protocol.
+    pub fn detect() -> Option<Protocol> {
+        let var = |name| env::var(name).unwrap_or_default();
+        let term = var("TERM");
+        let program = var("TERM_PROGRAM");
+
+        if env::var_os("KITTY_WINDOW_ID").is_some()
+            || term.contains("kitty")
+            || term.contains("ghostty")
+            || program == "WezTerm"
+            || program == "ghostty"
+        {
+            Some(Protocol::Kitty)
+        } else if term.contains("sixel")
+            || term.starts_with("mlterm")
+            || term.starts_with("foot")
+            || term.starts_with("contour")
+            || program == "iTerm.app"
+        {
+            Some(Protocol::Sixel)
+        } else {
+            None
+        }
+    }
+}
+
+/// The escape sequence drawing the whole grid at the cursor, `scale` pixels per cell.
+pub fn encode(protocol: Protocol, sim: &Simulation, scale: u16) -> String {
+    let scale = scale.max(1) as usize;
+    match protocol {
+        Protocol::Sixel => sixel(sim, scale),
+        Protocol::Kitty => kitty(sim, scale),
+    }
+}
+
+fn sixel(sim: &Simulation, scale: usize) -> String {
+    let (width, height) = (sim.width(), sim.height());
+    let cells = palette::indices(sim);
+    let colors = palette::indexed(sim);
+    let (pw, ph) = (width * scale, height * scale);
+
+    let mut out = format!("\x1bPq\"1;1;{};{}", pw, ph);
+    for (i, rgb) in colors.iter().enumerate() {
+        // Sixel colors are percentages.
+        let pct = |c: u8| u32::from(c) * 100 / 255;
+        out.push_str(&format!("#{};2;{};{};{}", i, pct(rgb[0]), pct(rgb[1]), pct(rgb[2])));
+    }
+
+    let mut line = vec![0u8; pw];
+    for band in 0..ph.div_ceil(6) {
+        for color in 0..colors.len() {
+            for (px, bits) in line.iter_mut().enumerate() {
+                *bits = 0;
+                for bit in 0..6 {
+                    let py = band * 6 + bit;
+                    if py < ph && cells[py / scale * width + px / scale] as usize == color {
+                        *bits |= 1 << bit;
+                    }
+                }
+            }
+            if line.iter().all(|&bits| bits == 0) {
+                continue;
+            }
+
+            out.push_str(&format!("#{}", color));
+            let mut run = line.iter().peekable();
+            while let Some(&bits) = run.next() {
+                let mut count = 1;
+                while run.peek() == Some(&&bits) {
+                    run.next();
+                    count += 1;
+                }
+                let c = (63 + bits) as char;
+                if count > 3 {
+                    out.push_str(&format!("!{}{}", count, c));
+                } else {
+                    out.extend(std::iter::repeat_n(c, count));
+                }
+            }
+            // Back to the start of the band for the next color.
+            out.push('$');
+        }
+        out.push('-');
+    }
+
+    out.push_str("\x1b\\");
+    out
+}
+
+fn kitty(sim: &Simulation, scale: usize) -> String {
+    let (width, height) = (sim.width(), sim.height());
+    let cells = palette::indices(sim);
+    let colors = palette::indexed(sim);
+    let (pw, ph) = (width * scale, height * scale);
+
+    let mut rgb = Vec::with_capacity(pw * ph * 3);
+    for py in 0..ph {
+        for px in 0..pw {
+            rgb.extend_from_slice(&colors[cells[py / scale * width + px / scale] as usize]);
+        }
+    }
+    let data = base64(&rgb);
+
+    // Replace the previous frame, then send the new one in chunks of at most 4096 bytes.
+    let mut out = String::from("\x1b_Ga=d,d=i,i=1,q=2\x1b\\");
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        if i == 0 {
+            out.push_str(&format!(
+                "\x1b_Ga=T,f=24,s={},v={},i=1,C=1,q=2,m={};",
+                pw, ph, more
+            ));
+        } else {
+            out.push_str(&format!("\x1b_Gm={};", more));
+        }
+        out.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+        out.push_str("\x1b\\");
+    }
+    out
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,540 @@
+//! A small gzip writer and reader, for checkpoints of large grids and for trace files that
+//! would otherwise take gigabytes.
+//!
+//! Input is buffered and compressed a chunk at a time into deflate blocks with the fixed
+//! Huffman codes, finding repeats with a hash chain. That is a fraction of what a real deflate
+//! gets out of tables of numbers, but the encoder stays dependency free. The reader takes any
+//! deflate stream, so checkpoints recompressed by other tools still load.
+
+use std::io::{self, Write};
+
+/// Bytes compressed together. Repeats are only found within a chunk.
+const CHUNK: usize = 1 << 18;
+
+/// How far back a repeat can start.
+const WINDOW: usize = 32768;
+
+/// Shortest and longest repeat deflate can encode.
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+/// Earlier positions tried for each repeat, more compresses better and slower.
+const CHAIN: usize = 16;
+
+const HASH_BITS: u32 = 15;
+
+/// Smallest length of each length code and the number of extra bits after it.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// Smallest distance of each distance code and the number of extra bits after it.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Compresses everything written to it into a gzip stream on `out`.
+///
+/// `finish` has to be called to write the end of the stream.
+pub struct GzipWriter<W: Write> {
+    out: W,
+    /// Input not compressed yet.
+    pending: Vec<u8>,
+    /// Compressed bytes not handed to `out` yet.
+    compressed: Vec<u8>,
+    /// Bits not making up a whole byte yet, oldest in the lowest bits.
+    bits: u64,
+    bit_count: u32,
+    crc: Crc32,
+    /// Bytes written, modulo 2^32 as gzip records it.
+    size: u32,
+}
+
+impl<W: Write> GzipWriter<W> {
+    pub fn new(mut out: W) -> io::Result<GzipWriter<W>> {
+        // Deflate, no file name or time, unknown operating system.
+        out.write_all(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff])?;
+        Ok(GzipWriter {
+            out,
+            pending: Vec::with_capacity(CHUNK),
+            compressed: Vec::new(),
+            bits: 0,
+            bit_count: 0,
+            crc: Crc32::new(),
+            size: 0,
+        })
+    }
+
+    /// Compress what is left and end the stream, returning the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let pending = std::mem::take(&mut self.pending);
+        self.block(&pending);
+        // An empty final block.
+        self.push(0b011, 3);
+        self.symbol(256);
+        if self.bit_count > 0 {
+            self.push(0, 8 - self.bit_count);
+        }
+        self.compressed.extend_from_slice(&self.crc.finish().to_le_bytes());
+        self.compressed.extend_from_slice(&self.size.to_le_bytes());
+        self.out.write_all(&self.compressed)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    /// Add `count` bits of `value` to the stream.
+    fn push(&mut self, value: u32, count: u32) {
+        self.bits |= u64::from(value) << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.compressed.push(self.bits as u8);
+            self.bits >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Add a Huffman code, which is stored starting from its highest bit.
+    fn code(&mut self, code: u32, length: u32) {
+        self.push(code.reverse_bits() >> (32 - length), length);
+    }
+
+    /// Add a literal byte, a length or the end of block with its fixed code.
+    fn symbol(&mut self, symbol: u16) {
+        let symbol = u32::from(symbol);
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    /// Add a repeat of `length` bytes starting `distance` bytes back.
+    fn repeat(&mut self, length: usize, distance: usize) {
+        let code = LENGTH_BASE.iter().rposition(|&base| usize::from(base) <= length);
+        let code = code.unwrap_or(0);
+        self.symbol(257 + code as u16);
+        let extra = length - usize::from(LENGTH_BASE[code]);
+        self.push(extra as u32, u32::from(LENGTH_EXTRA[code]));
+
+        let code = DISTANCE_BASE.iter().rposition(|&base| usize::from(base) <= distance);
+        let code = code.unwrap_or(0);
+        self.code(code as u32, 5);
+        let extra = distance - usize::from(DISTANCE_BASE[code]);
+        self.push(extra as u32, u32::from(DISTANCE_EXTRA[code]));
+    }
+
+    /// Compress `data` into one block that isn't the last.
+    fn block(&mut self, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        // Not final, fixed Huffman codes.
+        self.push(0b010, 3);
+
+        let hash = |at: usize| {
+            let key = u32::from(data[at]) << 16 | u32::from(data[at + 1]) << 8
+                | u32::from(data[at + 2]);
+            (key.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+        };
+        // The latest position with each hash, and before that the previous one with the same.
+        let mut head = vec![usize::MAX; 1 << HASH_BITS];
+        let mut previous = vec![usize::MAX; data.len()];
+
+        let mut at = 0;
+        while at < data.len() {
+            let longest = (data.len() - at).min(MAX_MATCH);
+            let (mut length, mut distance) = (0, 0);
+            if longest >= MIN_MATCH {
+                let mut candidate = head[hash(at)];
+                for _ in 0..CHAIN {
+                    if candidate == usize::MAX || at - candidate > WINDOW {
+                        break;
+                    }
+                    let same = data[candidate..]
+                        .iter()
+                        .zip(&data[at..at + longest])
+                        .take_while(|&(a, b)| a == b)
+                        .count();
+                    if same > length {
+                        length = same;
+                        distance = at - candidate;
+                        if same == longest {
+                            break;
+                        }
+                    }
+                    candidate = previous[candidate];
+                }
+            }
+
+            let taken = if length >= MIN_MATCH {
+                self.repeat(length, distance);
+                length
+            } else {
+                self.symbol(u16::from(data[at]));
+                1
+            };
+            // The last two positions have no three bytes to hash.
+            let end = (at + taken).min(data.len().saturating_sub(MIN_MATCH - 1));
+            for (position, previous) in previous.iter_mut().enumerate().take(end).skip(at) {
+                let h = hash(position);
+                *previous = head[h];
+                head[h] = position;
+            }
+            at += taken;
+        }
+        self.symbol(256);
+    }
+}
+
+impl<W: Write> Write for GzipWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.crc.update(buf);
+        self.size = self.size.wrapping_add(buf.len() as u32);
+        self.pending.extend_from_slice(buf);
+        if self.pending.len() >= CHUNK {
+            let pending = std::mem::replace(&mut self.pending, Vec::with_capacity(CHUNK));
+            self.block(&pending);
+            self.out.write_all(&self.compressed)?;
+            self.compressed.clear();
+        }
+        Ok(buf.len())
+    }
+
+    /// Hands over the whole bytes compressed so far. Buffered input stays buffered, so it can
+    /// still be compressed together.
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.write_all(&self.compressed)?;
+        self.compressed.clear();
+        self.out.flush()
+    }
+}
+
+/// The data in the gzip stream `bytes`, checked against the CRC and size at its end.
+pub fn gunzip(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    if bytes.len() < 18 || bytes[..3] != [0x1f, 0x8b, 8] {
+        return Err("Not a gzip stream".to_string());
+    }
+    let flags = bytes[3];
+    let mut at = 10;
+    // Extra fields, a file name and a comment, none of which matter here.
+    if flags & 0x04 != 0 {
+        let extra = bytes.get(at..at + 2).ok_or_else(truncated)?;
+        at += 2 + usize::from(u16::from_le_bytes([extra[0], extra[1]]));
+    }
+    for &flag in &[0x08, 0x10] {
+        if flags & flag != 0 {
+            let end = bytes.get(at..).and_then(|rest| rest.iter().position(|&b| b == 0));
+            at += end.ok_or_else(truncated)? + 1;
+        }
+    }
+    if flags & 0x02 != 0 {
+        at += 2;
+    }
+
+    let mut bits = Bits { bytes, at: at * 8 };
+    let data = inflate(&mut bits)?;
+    let trailer = bytes.get(bits.at.div_ceil(8)..).ok_or_else(truncated)?;
+    if trailer.len() < 8 {
+        return Err(truncated());
+    }
+    let mut crc = Crc32::new();
+    crc.update(&data);
+    let size = (data.len() as u32).to_le_bytes();
+    if trailer[..4] != crc.finish().to_le_bytes() || trailer[4..8] != size {
+        return Err(corrupt());
+    }
+    Ok(data)
+}
+
+fn truncated() -> String {
+    "The gzip stream ends too early".to_string()
+}
+
+fn corrupt() -> String {
+    "The gzip stream is corrupt".to_string()
+}
+
+/// Order the code lengths of a block's own codes are stored in.
+const LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// A deflate stream read a bit at a time, lowest bit of each byte first.
+struct Bits<'a> {
+    bytes: &'a [u8],
+    /// Bits read so far.
+    at: usize,
+}
+
+impl<'a> Bits<'a> {
+    fn bit(&mut self) -> Result<usize, String> {
+        let byte = self.bytes.get(self.at / 8).ok_or_else(truncated)?;
+        self.at += 1;
+        Ok(usize::from(byte >> ((self.at - 1) % 8) & 1))
+    }
+
+    /// A number of `count` bits, lowest first.
+    fn number(&mut self, count: u8) -> Result<usize, String> {
+        let mut number = 0;
+        for i in 0..count {
+            number |= self.bit()? << i;
+        }
+        Ok(number)
+    }
+}
+
+/// Huffman codes given by the length of each symbol's code, as deflate stores them.
+struct Codes {
+    /// Number of codes of each length.
+    counts: [usize; 16],
+    /// Symbols with a code, shortest codes first.
+    symbols: Vec<usize>,
+}
+
+impl Codes {
+    fn new(lengths: &[u8]) -> Result<Codes, String> {
+        let mut counts = [0; 16];
+        for &length in lengths {
+            counts[usize::from(length)] += 1;
+        }
+        counts[0] = 0;
+        // Codes that would need more than the lengths allow only turn up in broken streams.
+        let mut left = 1usize;
+        for &count in &counts[1..] {
+            left = (left << 1).checked_sub(count).ok_or_else(corrupt)?;
+        }
+        let mut symbols: Vec<usize> = (0..lengths.len()).filter(|&s| lengths[s] > 0).collect();
+        symbols.sort_by_key(|&s| lengths[s]);
+        Ok(Codes { counts, symbols })
+    }
+
+    /// The codes every block with type 1 uses, for symbols and for distances.
+    fn fixed() -> Result<(Codes, Codes), String> {
+        let mut lengths = [8; 288];
+        lengths[144..256].iter_mut().for_each(|length| *length = 9);
+        lengths[256..280].iter_mut().for_each(|length| *length = 7);
+        Ok((Codes::new(&lengths)?, Codes::new(&[5; 30])?))
+    }
+
+    /// The codes of a block with type 2, stored at its start.
+    fn read(bits: &mut Bits) -> Result<(Codes, Codes), String> {
+        let symbols = bits.number(5)? + 257;
+        let distances = bits.number(5)? + 1;
+        let mut lengths = [0; 19];
+        for &symbol in &LENGTH_ORDER[..bits.number(4)? + 4] {
+            lengths[symbol] = bits.number(3)? as u8;
+        }
+        let lengths_codes = Codes::new(&lengths)?;
+
+        let mut lengths = Vec::with_capacity(symbols + distances);
+        while lengths.len() < symbols + distances {
+            let (length, times) = match lengths_codes.decode(bits)? {
+                length @ 0..=15 => (length as u8, 1),
+                16 => (*lengths.last().ok_or_else(corrupt)?, 3 + bits.number(2)?),
+                17 => (0, 3 + bits.number(3)?),
+                _ => (0, 11 + bits.number(7)?),
+            };
+            lengths.extend(std::iter::repeat_n(length, times));
+        }
+        if lengths.len() > symbols + distances {
+            return Err(corrupt());
+        }
+        let (symbol_lengths, distance_lengths) = lengths.split_at(symbols);
+        Ok((Codes::new(symbol_lengths)?, Codes::new(distance_lengths)?))
+    }
+
+    /// The next symbol in `bits`. Codes are stored starting from their highest bit, and the
+    /// codes of each length follow on from the shorter ones.
+    fn decode(&self, bits: &mut Bits) -> Result<usize, String> {
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &self.counts[1..] {
+            code |= bits.bit()?;
+            if code < first + count {
+                return Ok(self.symbols[index + code - first]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(corrupt())
+    }
+}
+
+/// Undo the deflate blocks read from `bits`, up to and including the last one.
+fn inflate(bits: &mut Bits) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    loop {
+        let last = bits.bit()? == 1;
+        let (symbols, distances) = match bits.number(2)? {
+            // Stored, from the next whole byte on.
+            0 => {
+                let at = bits.at.div_ceil(8);
+                let header = bits.bytes.get(at..at + 4).ok_or_else(truncated)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                if u16::from_le_bytes([header[2], header[3]]) != !len {
+                    return Err(corrupt());
+                }
+                let start = at + 4;
+                let block = bits.bytes.get(start..start + usize::from(len));
+                out.extend_from_slice(block.ok_or_else(truncated)?);
+                bits.at = (start + usize::from(len)) * 8;
+                if last {
+                    return Ok(out);
+                }
+                continue;
+            }
+            1 => Codes::fixed()?,
+            2 => Codes::read(bits)?,
+            _ => return Err(corrupt()),
+        };
+        loop {
+            let symbol = symbols.decode(bits)?;
+            if symbol < 256 {
+                out.push(symbol as u8);
+                continue;
+            } else if symbol == 256 {
+                break;
+            }
+            // Lengths and distances past the tables only turn up in broken streams.
+            let code = symbol - 257;
+            let base = LENGTH_BASE.get(code).ok_or_else(corrupt)?;
+            let length = usize::from(*base) + bits.number(LENGTH_EXTRA[code])?;
+            let code = distances.decode(bits)?;
+            let base = DISTANCE_BASE.get(code).ok_or_else(corrupt)?;
+            let distance = usize::from(*base) + bits.number(DISTANCE_EXTRA[code])?;
+            if distance > out.len() {
+                return Err(corrupt());
+            }
+            for _ in 0..length {
+                let byte = out[out.len() - distance];
+                out.push(byte);
+            }
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// CRC-32 as used by PNG and gzip.
+pub struct Crc32 {
+    value: u32,
+    table: [u32; 256],
+}
+
+impl Default for Crc32 {
+    fn default() -> Crc32 {
+        Crc32::new()
+    }
+}
+
+impl Crc32 {
+    pub fn new() -> Crc32 {
+        let mut table = [0u32; 256];
+        for (n, entry) in table.iter_mut().enumerate() {
+            let mut c = n as u32;
+            for _ in 0..8 {
+                c = if c & 1 == 1 {
+                    0xEDB8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+            }
+            *entry = c;
+        }
+        Crc32 {
+            value: 0xFFFF_FFFF,
+            table,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.value = self.table[((self.value ^ u32::from(byte)) & 0xFF) as usize]
+                ^ (self.value >> 8);
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        self.value ^ 0xFFFF_FFFF
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rng::Rng;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut writer = GzipWriter::new(Vec::new()).unwrap();
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn round_trips() {
+        // Lines with repeats, then noise, past a chunk so there are several blocks.
+        let mut data = Vec::new();
+        for step in 0..20_000 {
+            let line = format!("{},0,{},{},right,1\n", step, step % 7, step % 5);
+            data.extend_from_slice(line.as_bytes());
+        }
+        let mut rng = Rng::new(7);
+        data.extend((0..CHUNK).map(|_| rng.next_u64() as u8));
+        assert!(data.len() > CHUNK);
+
+        let compressed = gzip(&data);
+        assert!(compressed.len() < data.len());
+        assert_eq!(gunzip(&compressed).unwrap(), data);
+        assert_eq!(gunzip(&gzip(&[])).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn reads_streams_from_zlib() {
+        // Python's gzip.compress(..., mtime=0), with the fixed codes, a stored block and codes
+        // of its own.
+        let fixed = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x4b, 0xcc, 0x2b, 0x29,
+            0x56, 0x48, 0x84, 0x13, 0x25, 0xa5, 0x45, 0x79, 0x0a, 0x45, 0x99, 0xe9, 0x19, 0x25,
+            0x3a, 0x48, 0x02, 0x39, 0xa9, 0x69, 0x25, 0x5c, 0x00, 0xd8, 0x2a, 0x44, 0x04, 0x2a,
+            0x00, 0x00, 0x00,
+        ];
+        let text = &b"ants ants ants turn right, ants turn left\n"[..];
+        assert_eq!(gunzip(&fixed).unwrap(), text);
+        let stored = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03, 0x01, 0x06, 0x00, 0xf9,
+            0xff, 0x73, 0x74, 0x6f, 0x72, 0x65, 0x64, 0x0b, 0xf9, 0x43, 0x56, 0x06, 0x00, 0x00,
+            0x00,
+        ];
+        assert_eq!(gunzip(&stored).unwrap(), b"stored");
+
+        let dynamic = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x15, 0x8b, 0x31, 0x02,
+            0x00, 0x20, 0x08, 0x02, 0x77, 0xde, 0xe2, 0x20, 0xa5, 0x56, 0xff, 0xff, 0x58, 0x30,
+            0x88, 0xc8, 0x49, 0x46, 0x82, 0x41, 0xac, 0x28, 0xec, 0x58, 0x28, 0x4d, 0xcb, 0x8f,
+            0xb2, 0x23, 0x76, 0xb5, 0x9f, 0x6e, 0xa6, 0x00, 0x69, 0xf1, 0x2b, 0xb7, 0x72, 0x96,
+            0xcb, 0x6d, 0x37, 0xce, 0x8e, 0xe9, 0xb5, 0xb8, 0xf1, 0x01, 0x39, 0xb5, 0x9f, 0xc5,
+            0x5a, 0x00, 0x00, 0x00,
+        ];
+        let numbers: String = (0..20).map(|i| format!("{},{}\n", i, i * i % 7)).collect();
+        assert_eq!(gunzip(&dynamic).unwrap(), numbers.as_bytes());
+    }
+
+    #[test]
+    fn rejects_broken_streams() {
+        let compressed = gzip(b"the ant walks on and on and on");
+        assert!(gunzip(b"LANTSNAP").is_err());
+        assert!(gunzip(&compressed[..compressed.len() - 3]).is_err());
+        let mut flipped = compressed.clone();
+        flipped[12] ^= 0x10;
+        assert!(gunzip(&flipped).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! Spotting a highway: the first ant repeating the same moves over and over while getting
+//! somewhere, like Langton's ant does every 104 steps once its chaos is over.
+//!
+//! It is a guess from the ant's path alone, the cells it walks into are never compared, so a
+//! long enough lucky stretch counts as well. Three repeats in a row, and no fewer than
+//! `MIN_STEPS` steps of them, are asked for to keep those rare.
+
+use std::collections::VecDeque;
+
+use simulation::Simulation;
+
+/// Longest period looked for, in steps.
+pub const MAX_PERIOD: usize = 1024;
+
+/// Number of times the moves have to repeat.
+const REPEATS: usize = 3;
+
+/// Steps the repeats have to take at least, short zigzags come up all the time in chaos.
+const MIN_STEPS: usize = 512;
+
+/// Steps between two looks at the moves, a look costs more than a step.
+const CHECK_EVERY: u64 = 64;
+
+/// A highway being built.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Found {
+    /// Steps it takes to build a stretch of it.
+    pub period: usize,
+    /// Columns and rows the ant gets over in a period.
+    pub shift: (i64, i64),
+    /// The step it was seen at, a few periods after it started.
+    pub step: u64,
+}
+
+/// Follows the moves of the first ant, one call to `update` after every step.
+#[derive(Clone, Debug, Default)]
+pub struct Highway {
+    /// The columns and rows moved on each of the last steps, oldest first.
+    moves: VecDeque<(i8, i8)>,
+    /// Where the ant was, from the grid's first top left corner, and at which step.
+    last: Option<((i64, i64), u64)>,
+    found: Option<Found>,
+}
+
+impl Highway {
+    pub fn new() -> Highway {
+        Highway::default()
+    }
+
+    /// Take in the step `sim` just took. Returns the highway if this step is the one it was
+    /// found at. Anything but the next step, such as going back or a restart, starts over.
+    pub fn update(&mut self, sim: &Simulation) -> Option<Found> {
+        let ant = sim.ants.first()?;
+        let at = (ant.x - sim.origin().0, ant.y - sim.origin().1);
+        let (dx, dy) = match self.last {
+            Some((last, step)) if step + 1 == sim.steps => (at.0 - last.0, at.1 - last.1),
+            _ => (i64::MAX, 0),
+        };
+        self.last = Some((at, sim.steps));
+        if dx.abs() > 1 || dy.abs() > 1 {
+            // The run jumped, or the first ant is another one now.
+            self.moves.clear();
+            self.found = None;
+            return None;
+        }
+        if self.moves.len() == MAX_PERIOD * REPEATS {
+            self.moves.pop_front();
+        }
+        self.moves.push_back((dx as i8, dy as i8));
+
+        match self.found {
+            // Still on it.
+            Some(found) if self.repeats(found.period) => return None,
+            Some(_) => self.found = None,
+            None => {}
+        }
+        if !sim.steps.is_multiple_of(CHECK_EVERY) {
+            return None;
+        }
+        let period = (1..=self.moves.len() / REPEATS).find(|&period| self.repeats(period))?;
+        self.found = Some(Found {
+            period,
+            shift: self.shift(period),
+            step: sim.steps,
+        });
+        self.found
+    }
+
+    /// The highway the ant is on, if it is on one.
+    pub fn found(&self) -> Option<Found> {
+        self.found
+    }
+
+    /// Whether the last moves are the same `period` moves again and again, getting the ant
+    /// somewhere.
+    fn repeats(&self, period: usize) -> bool {
+        let len = self.moves.len();
+        let span = (period * REPEATS).max(MIN_STEPS);
+        if len < span {
+            return false;
+        }
+        (1..=span - period)
+            .all(|back| self.moves[len - back] == self.moves[len - back - period])
+            && self.shift(period) != (0, 0)
+    }
+
+    /// Columns and rows moved over the last `period` steps.
+    fn shift(&self, period: usize) -> (i64, i64) {
+        self.moves
+            .iter()
+            .rev()
+            .take(period)
+            .fold((0, 0), |(x, y), &(dx, dy)| (x + i64::from(dx), y + i64::from(dy)))
+    }
+}
@@ -0,0 +1,233 @@
+//! The table mapping key presses to runtime controls.
+
+use backend::Key;
+
+/// Something the user can do while the ant runs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Pause,
+    /// Take a single step, meant for use while paused.
+    Step,
+    StepBack,
+    /// Start or stop running backwards.
+    Rewind,
+    /// Flip the direction of time and keep running, also from the end of a run.
+    Reverse,
+    Faster,
+    Slower,
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    /// Show fewer cells per character.
+    ZoomIn,
+    /// Show more cells per character, until the grid fits.
+    ZoomOut,
+    /// Start or stop keeping every visited cell in view.
+    Autofit,
+    Export,
+    /// Enter or leave edit mode, meant for use while paused.
+    Edit,
+    /// Show or hide the sparklines of recent speed and frame times.
+    Graph,
+    /// Show or hide the panel counting cells and flips of each color.
+    Histogram,
+    /// Save the run to one of the quick save slots, numbered from 1.
+    QuickSave(u8),
+    /// Go back to the run in one of the quick save slots.
+    QuickLoad(u8),
+    /// Write what is on screen to the screenshots directory.
+    Screenshot,
+}
+
+impl Action {
+    /// Every action, in the order they are listed in help and config dumps.
+    pub const ALL: &'static [Action] = &[
+        Action::Quit,
+        Action::Pause,
+        Action::Step,
+        Action::StepBack,
+        Action::Rewind,
+        Action::Reverse,
+        Action::Faster,
+        Action::Slower,
+        Action::PanUp,
+        Action::PanDown,
+        Action::PanLeft,
+        Action::PanRight,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::Autofit,
+        Action::Export,
+        Action::Edit,
+        Action::Graph,
+        Action::Histogram,
+        Action::QuickSave(1),
+        Action::QuickSave(2),
+        Action::QuickSave(3),
+        Action::QuickSave(4),
+        Action::QuickLoad(1),
+        Action::QuickLoad(2),
+        Action::QuickLoad(3),
+        Action::QuickLoad(4),
+        Action::Screenshot,
+    ];
+
+    /// The name used by `--bind` and the `[keys]` config table.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Pause => "pause",
+            Action::Step => "step",
+            Action::StepBack => "step-back",
+            Action::Rewind => "rewind",
+            Action::Reverse => "reverse",
+            Action::Faster => "faster",
+            Action::Slower => "slower",
+            Action::PanUp => "pan-up",
+            Action::PanDown => "pan-down",
+            Action::PanLeft => "pan-left",
+            Action::PanRight => "pan-right",
+            Action::ZoomIn => "zoom-in",
+            Action::ZoomOut => "zoom-out",
+            Action::Autofit => "autofit",
+            Action::Export => "export",
+            Action::Edit => "edit",
+            Action::Graph => "graph",
+            Action::Histogram => "histogram",
+            Action::QuickSave(1) => "save-1",
+            Action::QuickSave(2) => "save-2",
+            Action::QuickSave(3) => "save-3",
+            Action::QuickSave(_) => "save-4",
+            Action::QuickLoad(1) => "load-1",
+            Action::QuickLoad(2) => "load-2",
+            Action::QuickLoad(3) => "load-3",
+            Action::QuickLoad(_) => "load-4",
+            Action::Screenshot => "screenshot",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().cloned().find(|action| action.name() == name)
+    }
+}
+
+/// Parse a key as written in bindings: a single character, `space`, an arrow key name, or a
+/// function key from `f1` to `f12`.
+pub fn parse_key(name: &str) -> Result<Key, String> {
+    let function = name
+        .strip_prefix('f')
+        .and_then(|number| number.parse::<u8>().ok());
+    if let Some(number @ 1..=12) = function {
+        return Ok(Key::F(number));
+    }
+    match name {
+        "space" => Ok(Key::Char(' ')),
+        "up" => Ok(Key::Up),
+        "down" => Ok(Key::Down),
+        "left" => Ok(Key::Left),
+        "right" => Ok(Key::Right),
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(Key::Char(c)),
+                _ => Err(format!("Unknown key {:?}", name)),
+            }
+        }
+    }
+}
+
+/// The name `parse_key` reads back as `key`.
+pub fn key_name(key: Key) -> String {
+    match key {
+        Key::Char(' ') => "space".to_string(),
+        Key::Char(c) => c.to_string(),
+        Key::Up => "up".to_string(),
+        Key::Down => "down".to_string(),
+        Key::Left => "left".to_string(),
+        Key::Right => "right".to_string(),
+        Key::F(number) => format!("f{}", number),
+    }
+}
+
+/// Which key triggers which action. Every action has at most one key.
+#[derive(Clone, Debug)]
+pub struct KeyMap {
+    bindings: Vec<(Action, Key)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> KeyMap {
+        KeyMap {
+            bindings: vec![
+                (Action::Quit, Key::Char('q')),
+                (Action::Pause, Key::Char(' ')),
+                (Action::Step, Key::Char('.')),
+                (Action::StepBack, Key::Char(',')),
+                (Action::Rewind, Key::Char('u')),
+                (Action::Reverse, Key::Char('R')),
+                (Action::Faster, Key::Char('+')),
+                (Action::Slower, Key::Char('-')),
+                (Action::PanUp, Key::Up),
+                (Action::PanDown, Key::Down),
+                (Action::PanLeft, Key::Left),
+                (Action::PanRight, Key::Right),
+                (Action::ZoomIn, Key::Char('z')),
+                (Action::ZoomOut, Key::Char('Z')),
+                (Action::Autofit, Key::Char('b')),
+                (Action::Export, Key::Char('x')),
+                (Action::Edit, Key::Char('e')),
+                (Action::Graph, Key::Char('g')),
+                (Action::Histogram, Key::Char('h')),
+                (Action::QuickSave(1), Key::F(1)),
+                (Action::QuickSave(2), Key::F(2)),
+                (Action::QuickSave(3), Key::F(3)),
+                (Action::QuickSave(4), Key::F(4)),
+                (Action::QuickLoad(1), Key::F(5)),
+                (Action::QuickLoad(2), Key::F(6)),
+                (Action::QuickLoad(3), Key::F(7)),
+                (Action::QuickLoad(4), Key::F(8)),
+                (Action::Screenshot, Key::Char('p')),
+            ],
+        }
+    }
+}
+
+impl KeyMap {
+    /// Bind `key` to `action`, taking it away from whatever action had it before.
+    pub fn bind(&mut self, action: Action, key: Key) {
+        self.bindings
+            .retain(|&(other, bound)| other != action && bound != key);
+        self.bindings.push((action, key));
+    }
+
+    /// Apply a binding written as `action=key`.
+    pub fn bind_spec(&mut self, spec: &str) -> Result<(), String> {
+        let mut parts = spec.splitn(2, '=');
+        let name = parts.next().unwrap_or("").trim();
+        let key = parts
+            .next()
+            .ok_or_else(|| format!("Expected a binding like quit=x, got {}", spec))?;
+        let action =
+            Action::from_name(name).ok_or_else(|| format!("Unknown action {:?}", name))?;
+        self.bind(action, parse_key(key.trim())?);
+        Ok(())
+    }
+
+    /// The action bound to `key`, if any.
+    pub fn action(&self, key: Key) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|&&(_, bound)| bound == key)
+            .map(|&(action, _)| action)
+    }
+
+    /// The key bound to `action`, if any.
+    pub fn key(&self, action: Action) -> Option<Key> {
+        self.bindings
+            .iter()
+            .find(|&&(bound, _)| bound == action)
+            .map(|&(_, key)| key)
+    }
+}
@@ -0,0 +1,29 @@
+//! The simulation core of langtons_ant, free of any terminal code so it also builds for
+//! `wasm32-unknown-unknown`.
+
+pub mod checkpoint;
+pub mod export;
+#[cfg(feature = "image")]
+pub mod gif;
+pub mod gzip;
+pub mod highway;
+pub mod life;
+pub mod palette;
+pub mod pattern;
+pub mod presets;
+#[cfg(feature = "image")]
+pub mod png;
+#[cfg(feature = "image")]
+pub mod render;
+pub mod rng;
+pub mod rule;
+#[cfg(feature = "script")]
+pub mod script;
+pub mod simulation;
+pub mod stats;
+pub mod svg;
+pub mod symmetry;
+pub mod trace;
+#[cfg(any(target_arch = "wasm32", test))]
+pub mod web;
+pub mod zobrist;
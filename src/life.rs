@@ -0,0 +1,57 @@
+//! Conway's Game of Life running on the grid under the ants, for `--background life`.
+//!
+//! Painted cells count as alive and black ones as dead. A generation turns dead cells with
+//! three live neighbours white and blackens live cells without two or three; survivors keep
+//! their color. Walls never change and count as dead, and so does everything past the edge.
+//! What the ants paint seeds the automaton, and what it leaves behind steers the ants.
+
+use simulation::{Cell, State};
+
+/// Move the cells of `grid`, rows of `width` cells, on by one generation. Returns the index
+/// and former color of every cell that changed.
+pub fn generation(grid: &mut [Cell], width: usize) -> Vec<(usize, State)> {
+    let mut changed = Vec::new();
+    if width == 0 {
+        return changed;
+    }
+    let height = grid.len() / width;
+
+    // Framed by a row and column of dead cells on every side, so neighbours need no checks.
+    let stride = width + 2;
+    let mut alive = vec![0u8; stride * (height + 2)];
+    for (i, cell) in grid.iter().enumerate() {
+        if !cell.wall && !cell.state.is_blank() {
+            alive[(i / width + 1) * stride + i % width + 1] = 1;
+        }
+    }
+
+    for (i, cell) in grid.iter_mut().enumerate() {
+        if cell.wall {
+            continue;
+        }
+        let center = (i / width + 1) * stride + i % width + 1;
+        let neighbours: u8 = [
+            center - stride - 1,
+            center - stride,
+            center - stride + 1,
+            center - 1,
+            center + 1,
+            center + stride - 1,
+            center + stride,
+            center + stride + 1,
+        ]
+        .iter()
+        .map(|&at| alive[at])
+        .sum();
+        let before = cell.state;
+        match (alive[center], neighbours) {
+            (0, 3) => cell.state = State::WHITE,
+            (1, 2) | (1, 3) | (0, _) => {}
+            _ => cell.state = State::BLACK,
+        }
+        if cell.state != before {
+            changed.push((i, before));
+        }
+    }
+    changed
+}
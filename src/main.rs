@@ -1,238 +1,3147 @@
+extern crate langton_ant;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "curses")]
 extern crate pancurses;
 
+mod backend;
+mod braille;
+mod config;
+mod control;
+mod damage;
+mod events;
+mod fade;
+mod graphics;
+mod keys;
+mod metrics;
+mod screensaver;
+mod session;
+mod slowmo;
+mod split;
+#[cfg(feature = "image")]
+mod sweep;
+mod theme;
+#[cfg(feature = "gui")]
+mod window;
+#[cfg(feature = "gui")]
+mod x11;
+mod zoom;
+
 use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use pancurses::{curs_set, endwin, initscr, noecho, Input, Window};
-use pancurses as pc;
+use backend::cast::Cast;
+use backend::{Backend, Input, Key, Style};
+use control::{Command as Control, Server};
+use events::{Emitter, Moves};
+use fade::Fade;
+use graphics::Protocol;
+use damage::Damage;
+use keys::{Action, KeyMap};
+use metrics::Metrics;
+use screensaver::Screensaver;
+use session::Session;
+use slowmo::{Event, Slowmo};
+use theme::Theme;
+use zoom::Blend;
+use langton_ant::checkpoint::{self, Checkpoints};
+use langton_ant::palette;
+use langton_ant::pattern::Pattern;
+use langton_ant::presets::{self, Preset};
+#[cfg(feature = "image")]
+use langton_ant::render;
+use langton_ant::rng::Rng;
+use langton_ant::rule::{self, Rule, Schedule};
+#[cfg(feature = "script")]
+use langton_ant::script::Script;
+#[cfg(feature = "script")]
+use langton_ant::simulation::Hooks;
+use langton_ant::simulation::{
+    Background, Boundary, Bounds, Cell, Collision, Direction, LeftArena, OnWall, Simulation,
+    State,
+};
+use langton_ant::stats::{Histogram, Stats};
+use langton_ant::symmetry::Symmetry;
+use langton_ant::trace::Trace;
+use langton_ant::zobrist::{Cycle, Cycles};
+use langton_ant::export::{self, Viewport};
+use langton_ant::svg;
 
-#[derive(Copy, Clone, Debug)]
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
+struct Main {
+    /// The terminal
+    window: Box<dyn Backend>,
+    /// The grid and the ant.
+    sim: Simulation,
+    /// Steps per second to aim for, 0 for as fast as possible.
+    speed: f64,
+    /// Steps per second actually reached, measured every `RATE_INTERVAL`.
+    rate: f64,
+    /// Start of the current rate measurement.
+    rate_since: Instant,
+    /// Steps taken since `rate_since`.
+    rate_steps: u64,
+    /// The last measurements of the speed, and how long frames took.
+    metrics: Metrics,
+    /// Whether the sparklines of `metrics` are shown below the status line.
+    graph: bool,
+    /// Whether the panel of `counts` is shown at the right edge.
+    histogram: bool,
+    /// The colors of the run, counted from the first time the panel was shown.
+    counts: Option<Arc<Mutex<Histogram>>>,
+    /// The cells the last step changed, to draw again.
+    damage: Arc<Mutex<Damage>>,
+    /// What the ants did in the step just taken, for `--emit-steps`.
+    moves: Arc<Mutex<Moves>>,
+    /// Whether or not to show path
+    path: bool,
+    /// Whether or not to show step counter
+    show_counter: bool,
+    /// Template for the file names written by the export key.
+    export_path: PathBuf,
+    /// Where the screenshot key writes.
+    screenshot_dir: PathBuf,
+    /// How cells are put on screen.
+    renderer: Renderer,
+    /// Pixels per cell when drawing an image.
+    scale: u16,
+    /// When the last image was drawn.
+    last_frame: Instant,
+    /// Whether the run is currently going backwards.
+    rewinding: bool,
+    /// Whether stepping is on hold.
+    paused: bool,
+    /// Grid column and row of the cursor while in edit mode.
+    editing: Option<(usize, usize)>,
+    /// Grid column and row shown in the top left corner.
+    view: (usize, usize),
+    /// Columns and rows of cells behind each character with the default renderer.
+    zoom: usize,
+    /// How a zoomed out character picks its color.
+    blend: Blend,
+    /// Whether the view follows the visited cells, zooming out when they no longer fit.
+    autofit: bool,
+    /// Which keys do what.
+    keys: KeyMap,
+    /// Heading of ants dropped with the mouse.
+    heading: Direction,
+    /// Characters for ants heading up, down, left and right.
+    ant_glyphs: [char; 4],
+    /// How cells, walls and ants look.
+    theme: Theme,
+    /// Shading of recently changed cells, with `--fade`.
+    fade: Option<Fade>,
+    /// What slows the run down, with `--slowmo-on`.
+    slowmo: Option<Slowmo>,
+    /// Whether every run is watched for a cycle, with `--detect-cycles`.
+    detect_cycles: bool,
+    /// What watches the current run for a cycle.
+    cycles: Option<Arc<Mutex<Cycles>>>,
+    /// Where steps are reported with `--emit-steps`. Dropped if writing fails, usually because
+    /// the reader went away.
+    events: Option<Emitter>,
+    /// Commands coming in with `--control-socket`.
+    control: Option<Server>,
+    /// Where the run is saved every so often, with `--checkpoint-every`.
+    checkpoints: Option<Checkpoints>,
+    /// Where the quick save slots are, `None` without a config directory.
+    session: Option<Session>,
+    /// Number of steps after which the run is over.
+    limit: Option<u64>,
+    /// Time after which the run is over.
+    duration: Option<Duration>,
+    /// When the run is over, `duration` after it started.
+    deadline: Option<Instant>,
+    /// What to do when the run is over.
+    on_finish: Finish,
+    /// Where the next run comes from with `--screensaver`, which also ends on any key.
+    screensaver: Option<Screensaver>,
+    /// Why the run stopped, if an ant walked off the grid.
+    left_arena: Option<LeftArena>,
+    /// Shown before the step counter, the rule of a pane with `--split`.
+    title: String,
+    /// The grid as it was before the first step, kept for `Finish::Loop`.
+    initial: Option<Simulation>,
 }
 
-impl Direction {
-    fn rotate_left(self) -> Direction {
-        use Direction::*;
-        match self {
-            Up => Left,
-            Left => Down,
-            Down => Right,
-            Right => Up,
-        }
-    }
-    fn rotate_right(self) -> Direction {
-        use Direction::*;
+/// Ways of drawing the grid.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Renderer {
+    /// One character per cell.
+    Cells,
+    /// One braille character per 2×4 cells.
+    Braille,
+    /// The whole grid as an inline image.
+    Pixels(Protocol),
+}
+
+/// Shortest time between two images in `--pixels` mode.
+const FRAME_INTERVAL: Duration = Duration::from_millis(40);
+
+/// How often keys are checked while paused.
+const PAUSED_POLL: Duration = Duration::from_millis(10);
+
+/// How far the run may fall behind its speed before the missed steps are skipped.
+const MAX_LAG: Duration = Duration::from_millis(250);
+
+/// How often the speed shown in the status line is measured.
+const RATE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Shown by the quick save keys when there is nowhere to keep the slots.
+const NO_SESSION: &str = "No config directory for quick saves, set HOME or XDG_CONFIG_HOME";
+
+/// Slowest speed `slower` goes down to, in steps per second.
+const MIN_SPEED: f64 = 0.1;
+
+/// What to do, picked by the first argument.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Command {
+    /// Watch the ant in the terminal.
+    Run,
+    /// Render an animation without a terminal.
+    Render,
+    /// Measure how fast the simulation runs without drawing anything.
+    Bench,
+    /// Run without a terminal and compare the end result to a known hash.
+    Verify,
+    /// Run every rule of a file without a terminal and write an image and stats of each.
+    Sweep,
+    /// Watch the ant in an X11 window.
+    Window,
+}
+
+/// What happens once the run reaches `--steps` or an ant walks off the grid.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Finish {
+    /// Pause and keep the display up until the user quits.
+    Freeze,
+    /// Exit.
+    Quit,
+    /// Start over from the initial grid.
+    Loop,
+}
+
+impl Finish {
+    /// The name accepted by `--on-finish`.
+    fn name(self) -> &'static str {
         match self {
-            Up => Right,
-            Right => Down,
-            Down => Left,
-            Left => Up,
-        }
-    }
-    fn offset(&self) -> (i8, i8) {
-        use Direction::*;
-        match *self {
-            Up => (0, 1),
-            Down => (0, -1),
-            Left => (1, 0),
-            Right => (-1, 0),
+            Finish::Freeze => "freeze",
+            Finish::Quit => "quit",
+            Finish::Loop => "loop",
         }
     }
 }
 
-#[derive(Copy, Clone, Debug, Hash)]
-enum State {
-    White,
-    Black,
-}
+impl FromStr for Finish {
+    type Err = String;
 
-impl State {
-    fn toggle(self) -> State {
-        match self {
-            State::White => State::Black,
-            State::Black => State::White,
+    fn from_str(s: &str) -> Result<Finish, String> {
+        match s {
+            "freeze" => Ok(Finish::Freeze),
+            "quit" => Ok(Finish::Quit),
+            "loop" => Ok(Finish::Loop),
+            _ => Err(format!(
+                "Unknown finish {}, expected freeze, quit or loop",
+                s
+            )),
         }
     }
 }
 
-/// A cell in the grid.
-#[derive(Copy, Clone, Debug, Hash)]
-struct Cell {
-    state: State,
+/// Where the ant starts out.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Start {
+    /// A column and row.
+    At(u16, u16),
+    /// A random cell, picked with the shared seed.
+    Random,
 }
 
-struct Main {
-    /// The terminal window
-    window: Window,
-    /// The grid.
-    ///
-    /// The cells are enumerated like you would read a book. Left to right, until you reach the
-    /// line ending.
-    grid: Box<[Box<[Cell]>]>,
-    /// The x coordinate.
+/// An ant given with `--ant X,Y,HEADING[,RULE[,COLOR]]`.
+#[derive(Clone, Debug)]
+struct AntSpec {
     x: u16,
-    /// The y coordinate.
     y: u16,
-    /// Current heading of the ant
     heading: Direction,
-    /// Delay between steps
-    delay: u64,
+    /// The rule to follow instead of `--rule`.
+    rule: Option<Arc<dyn Rule>>,
+    /// RGB color in images, picked by index when not given.
+    color: Option<[u8; 3]>,
+}
+
+impl FromStr for AntSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<AntSpec, String> {
+        let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+        if parts.len() < 3 || parts.len() > 5 {
+            return Err(format!(
+                "Expected an ant like 10,10,up or 10,10,up,LLRR,#e02020, got {}",
+                s
+            ));
+        }
+        let (x, y) = parse_point(&format!("{},{}", parts[0], parts[1]))
+            .ok_or_else(|| format!("Expected a position like 10,5 in {}", s))?;
+        Ok(AntSpec {
+            x,
+            y,
+            heading: parts[2].parse()?,
+            rule: parts.get(3).map(|spec| rule::parse(spec)).transpose()?,
+            color: parts.get(4).map(|color| palette::parse(color)).transpose()?,
+        })
+    }
+}
+
+impl fmt::Display for AntSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{},{}", self.x, self.y, self.heading.name())?;
+        match (&self.rule, self.color) {
+            (Some(rule), Some(color)) => write!(f, ",{},{}", rule, palette::hex(color)),
+            (Some(rule), None) => write!(f, ",{}", rule),
+            // The rule can't be left out in front of a color.
+            (None, Some(color)) => write!(f, ",{},{}", rule::classic(), palette::hex(color)),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Settings collected from the config file and the command line.
+#[derive(Clone)]
+struct Options {
+    command: Command,
+    /// Name of the terminal backend.
+    backend: String,
+    /// Whether to draw the grid as an inline image when the terminal supports it.
+    pixels: bool,
+    /// Whether to draw 2×4 cells per character with braille patterns.
+    braille: bool,
+    /// Columns and rows of cells behind each character, 1 for one cell per character.
+    zoom: u16,
+    /// How a zoomed out character picks its color.
+    zoom_blend: Blend,
+    /// Keep the visited cells in view.
+    autofit: bool,
+    /// Whether to start in edit mode.
+    edit: bool,
+    /// Steps per second, 0 for as fast as possible.
+    speed: f64,
     /// Whether or not to show path
-    path: bool,
+    show_path: bool,
     /// Whether or not to show step counter
     show_counter: bool,
+    /// Whether to print the exit summary as JSON.
+    stats_json: bool,
+    /// Fraction of cells to start out white.
+    random_fill: Option<f64>,
+    /// Seed for everything random, picked from the clock when not given.
+    seed: Option<u64>,
+    /// The preset the rule, pattern, size and speed were taken from.
+    preset: Option<&'static Preset>,
+    /// Pattern to stamp onto the grid before the run starts.
+    pattern: Option<Pattern>,
+    /// Column and row of the pattern's top left corner, centered when not given.
+    pattern_at: Option<(u16, u16)>,
+    /// Hooks deciding turns and colors instead of the rules.
+    #[cfg(feature = "script")]
+    script: Option<Arc<Script>>,
+    /// Where the ant starts, the middle of the grid when not given.
+    start: Option<Start>,
+    /// Initial heading of the ant.
+    heading: Direction,
+    /// Where to write the grid once the run ends.
+    export_on_exit: Option<PathBuf>,
+    /// Where to write the ant's trajectory as SVG once the run ends.
+    svg_path: Option<PathBuf>,
+    /// Where to write a line for every step, `-` for stdout.
+    emit_steps: Option<PathBuf>,
+    /// Where to write a CSV line for every ant and step when running without a terminal.
+    trace: Option<PathBuf>,
+    /// Where to record the screen for asciinema, with `--asciicast`.
+    asciicast: Option<PathBuf>,
+    /// Port or Unix socket path to take commands on.
+    control_socket: Option<String>,
+    /// Where `render` writes its animation.
+    gif: Option<PathBuf>,
+    /// File of rules `sweep` runs.
+    rules_file: Option<PathBuf>,
+    /// Directory `sweep` writes into.
+    out: Option<PathBuf>,
+    /// Number of rules `sweep` runs at once, defaults to the number of cores.
+    threads: Option<usize>,
+    /// Number of steps to run for.
+    steps: Option<u64>,
+    /// Time to run for, whatever the number of steps.
+    duration: Option<Duration>,
+    /// What to do when the run is over.
+    on_finish: Finish,
+    /// Whether to start over with a random rule whenever the run is over, until a key is
+    /// pressed.
+    screensaver: bool,
+    /// The rules `screensaver` picks from.
+    screensaver_rules: Vec<Arc<dyn Rule>>,
+    /// Steps between two frames of an animation.
+    frame_every: u64,
+    /// Pixels per cell in rendered images.
+    scale: u16,
+    /// Time each animation frame is shown, in hundredths of a second.
+    frame_delay: u16,
+    /// Columns and rows of the grid when there is no terminal to size it.
+    size: Option<(u16, u16)>,
+    /// Columns and rows of panes to tile the terminal into, each running its own grid.
+    split: Option<(u16, u16)>,
+    /// Every rule given with `--rule`, in order, one for each pane with `split`.
+    pane_rules: Vec<Arc<dyn Rule>>,
+    /// How the ant turns on each cell color.
+    rule: Arc<dyn Rule>,
+    /// Rules taking over from `rule` at given steps, with `--schedule`.
+    schedule: Option<Schedule>,
+    /// Ants to start with instead of the one placed by `start` and `heading`.
+    ants: Vec<AntSpec>,
+    /// Copies of the starting ants to place around the center.
+    symmetry: Symmetry,
+    /// What ants do at walls.
+    on_wall: OnWall,
+    /// What happens when an ant reaches the edge of the grid.
+    boundary: Boundary,
+    /// What the grid does by itself.
+    background: Background,
+    /// Steps between two generations of `--background life`.
+    life_every: u64,
+    /// What ants ending a step on the same cell do.
+    collision: Collision,
+    /// Chance of an ant turning the wrong way on a step.
+    noise: f64,
+    /// Characters for ants heading up, down, left and right.
+    ant_glyphs: [char; 4],
+    /// How cells, walls and ants look in the terminal.
+    theme: Theme,
+    /// Whether to draw in ASCII only, or None to go by the locale and the terminal.
+    ascii: Option<bool>,
+    /// Characters for cells from black on when drawing in ASCII.
+    ascii_cells: Vec<char>,
+    /// Steps over which changed cells fade back, 0 for no fading.
+    fade: u64,
+    /// What slows the run down for a while with `--slowmo-on`.
+    slowmo_on: Vec<Event>,
+    /// Whether to watch for the run coming back to a state it was in.
+    detect_cycles: bool,
+    /// Steps between two checkpoints, 0 for none.
+    checkpoint_every: u64,
+    /// Where checkpoints are written.
+    checkpoint_dir: PathBuf,
+    /// Where screenshots are written.
+    screenshot_dir: PathBuf,
+    /// Checkpoint to carry on from, or a directory to take the newest one from.
+    load: Option<PathBuf>,
+    /// Session to carry on and save on the way out, with its own quick save slots.
+    session: Option<String>,
+    /// Fingerprint `verify` expects the run to end with.
+    expect_hash: Option<u64>,
+    /// Which keys do what.
+    keys: KeyMap,
 }
 
-fn init(w: u16, h: u16, window: Window, delay: u64, path: bool, counter: bool) {
-    let mut main = Main {
-        x: h / 2,
-        y: w / 2,
-        window,
-        grid: vec![
-            vec![
-                Cell {
-                    state: State::Black,
-                };
-                w as usize
-            ].into_boxed_slice();
-            h as usize
-        ].into_boxed_slice(),
-        heading: Direction::Right,
-        delay,
-        path,
-        show_counter: counter,
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            command: Command::Run,
+            backend: backend::NAMES.first().unwrap_or(&"").to_string(),
+            pixels: false,
+            braille: false,
+            zoom: 1,
+            zoom_blend: Blend::Majority,
+            autofit: false,
+            edit: false,
+            speed: 50.0,
+            show_path: false,
+            show_counter: true,
+            stats_json: false,
+            random_fill: None,
+            seed: None,
+            preset: None,
+            pattern: None,
+            #[cfg(feature = "script")]
+            script: None,
+            pattern_at: None,
+            start: None,
+            heading: Direction::Right,
+            export_on_exit: None,
+            svg_path: None,
+            emit_steps: None,
+            trace: None,
+            asciicast: None,
+            control_socket: None,
+            gif: None,
+            rules_file: None,
+            out: None,
+            threads: None,
+            steps: None,
+            duration: None,
+            on_finish: Finish::Quit,
+            screensaver: false,
+            screensaver_rules: screensaver::default_pool(),
+            frame_every: 100,
+            scale: 4,
+            frame_delay: 4,
+            size: None,
+            split: None,
+            pane_rules: Vec::new(),
+            rule: rule::classic(),
+            schedule: None,
+            ants: Vec::new(),
+            symmetry: Symmetry::Single,
+            on_wall: OnWall::Bounce,
+            boundary: Boundary::End,
+            background: Background::Still,
+            collision: Collision::Sequential,
+            life_every: 100,
+            noise: 0.0,
+            ant_glyphs: theme::UNICODE.ants,
+            theme: Theme::classic(),
+            ascii: None,
+            ascii_cells: theme::ASCII_CELLS.chars().collect(),
+            fade: 0,
+            slowmo_on: Vec::new(),
+            detect_cycles: false,
+            checkpoint_every: 0,
+            checkpoint_dir: PathBuf::from("checkpoints"),
+            screenshot_dir: PathBuf::from("screenshots"),
+            load: None,
+            session: None,
+            expect_hash: None,
+            keys: KeyMap::default(),
+        }
+    }
+}
+
+/// Build the starting grid described by `options`.
+///
+/// Fails when the rules given can't share a grid, or the symmetric copies of an ant don't fit.
+fn setup(w: u16, h: u16, options: &Options) -> Result<Simulation, String> {
+    setup_with_rule(w, h, options, &options.rule)
+}
+
+/// Build the starting grid described by `options`, with `rule` as the default rule.
+fn setup_with_rule(
+    w: u16,
+    h: u16,
+    options: &Options,
+    rule: &Arc<dyn Rule>,
+) -> Result<Simulation, String> {
+    let mut sim = match options.load {
+        // The checkpoint has its own grid size and rules.
+        Some(ref path) => checkpoint::load(path)?,
+        None => new_grid(w, h, options, rule)?,
     };
+    if options.svg_path.is_some() {
+        let ant = sim.ants[0];
+        sim.trail = Some(vec![(ant.x, ant.y)]);
+    }
+    #[cfg(feature = "script")]
+    {
+        sim.hooks = options.script.clone().map(|script| script as Arc<dyn Hooks>);
+    }
+    Ok(sim)
+}
+
+/// A fresh `w` by `h` grid set up as `options` say, with `rule` as the default rule.
+fn new_grid(w: u16, h: u16, options: &Options, rule: &Arc<dyn Rule>) -> Result<Simulation, String> {
+    let mut sim = Simulation::new(w, h);
+    let mut rng = Rng::new(options.seed.unwrap_or_else(Rng::time_seed));
+    if let Some(density) = options.random_fill {
+        sim.fill_random(density, &mut rng);
+    }
+    if let Some(ref pattern) = options.pattern {
+        sim.stamp(pattern, options.pattern_at);
+    }
+
+    // Positions past the edge are pulled back onto the grid, there is no way to know the size
+    // of the terminal while parsing arguments.
+    let (w, h) = (w.max(1), h.max(1));
+    let ant = &mut sim.ants[0];
+    match options.start {
+        Some(Start::At(x, y)) => {
+            ant.x = i64::from(x.min(w - 1));
+            ant.y = i64::from(y.min(h - 1));
+        }
+        Some(Start::Random) => {
+            // The row is drawn first, so seeds keep picking the same cell as they used to.
+            ant.y = rng.below(u64::from(h)) as i64;
+            ant.x = rng.below(u64::from(w)) as i64;
+        }
+        None => {}
+    }
+    ant.heading = options.heading;
+    sim.set_rule(rule.clone())?;
+    if let Some(ref schedule) = options.schedule {
+        if options.symmetry != Symmetry::Single {
+            return Err("--schedule can't change the rules of copies from --symmetry".to_string());
+        }
+        sim.set_schedule(schedule)?;
+    }
+    sim.on_wall = options.on_wall;
+    sim.boundary = options.boundary;
+    sim.background = options.background;
+    sim.background_every = options.life_every;
+    sim.collision = options.collision;
+    sim.noise = options.noise;
+
+    if !options.ants.is_empty() {
+        sim.ants.clear();
+    }
+    for spec in &options.ants {
+        let rule = match spec.rule {
+            Some(ref rule) => sim.add_rule(rule.clone())?,
+            None => 0,
+        };
+        let ant = sim.add_ant(
+            i64::from(spec.x.min(w - 1)),
+            i64::from(spec.y.min(h - 1)),
+            spec.heading,
+        );
+        ant.rule = rule;
+        ant.color = spec.color;
+    }
+    options.symmetry.apply(&mut sim)?;
+
+    // Noise continues the sequence the setup drew from, so a seed reproduces both.
+    sim.rng = rng;
+    Ok(sim)
+}
+
+/// Columns and rows of the grid that fills `window` when drawn with `renderer`.
+fn grid_size(window: &dyn Backend, renderer: Renderer, options: &Options) -> (u16, u16) {
+    let (mut w, mut h) = window.size();
+    match renderer {
+        Renderer::Cells => {
+            w = w.saturating_mul(options.zoom);
+            h = h.saturating_mul(options.zoom);
+        }
+        Renderer::Braille => {
+            w *= braille::CELL_WIDTH as u16;
+            h *= braille::CELL_HEIGHT as u16;
+        }
+        Renderer::Pixels(_) => {
+            // The image starts below the counter line.
+            let (pw, ph) = window.pixel_size().unwrap_or((w * 10, h * 20));
+            let scale = options.scale.max(1);
+            w = pw / scale;
+            h = (ph - ph / h.max(1)) / scale;
+        }
+    }
+    (w, h)
+}
+
+fn init(
+    window: Box<dyn Backend>,
+    renderer: Renderer,
+    events: Option<Emitter>,
+    options: &Options,
+) -> Result<Stats, String> {
+    let (w, h) = grid_size(&*window, renderer, options);
+    // Returning drops the window, so the error lands on the normal screen.
+    let sim = setup(w, h, options)?;
+    let mut main = Main::new(window, renderer, sim, options);
+    main.events = events;
+    if let Some(ref address) = options.control_socket {
+        main.control = Some(Server::bind(address)?);
+    }
+    main.checkpoints = checkpoints(options)?;
+    main.session = Session::open(options.session.as_deref()).ok();
+    if options.screensaver {
+        main.screensaver = Some(Screensaver::new(options, w, h));
+        main.title = options.rule.to_string();
+    }
+    main.draw_grid();
+    if options.edit {
+        main.toggle_edit();
+    }
 
     // Start the loop.
-    main.start();
+    let stats = main.start();
+    main.sim.finish();
+    let mut errors = write_outputs(&main.sim, options);
+    if let Some(ref session) = main.session {
+        errors.extend(session.autosave(&main.sim).err());
+    }
+    let left_arena = main.left_arena;
+    errors.extend(main.window.close().err());
+
+    // Close the window before reporting anything.
+    drop(main);
+    if let Some(left) = left_arena {
+        eprintln!("{}", left);
+    }
+    for e in errors {
+        eprintln!("{}", e);
+    }
+    Ok(stats)
+}
+
+/// Write the files requested for the end of a run, returning what went wrong.
+fn write_outputs(sim: &Simulation, options: &Options) -> Vec<String> {
+    let mut errors = Vec::new();
+    if let Some(ref path) = options.export_on_exit {
+        errors.extend(export::export(path, sim).err());
+    }
+    if let Some(ref path) = options.svg_path {
+        errors.extend(svg::write(path, sim).err());
+    }
+    errors
+}
+
+/// Where to write checkpoints, if `options` ask for them.
+fn checkpoints(options: &Options) -> Result<Option<Checkpoints>, String> {
+    if options.checkpoint_every == 0 {
+        return Ok(None);
+    }
+    Checkpoints::new(&options.checkpoint_dir, options.checkpoint_every).map(Some)
 }
 
-impl Drop for Main {
-    fn drop(&mut self) {
-        // When done, restore the defaults to avoid messing with the terminal.
-        endwin();
+/// Whether another step fits in the current frame, moving `next_step` on if so.
+fn step_due(
+    speed: f64,
+    paused: bool,
+    taken: u64,
+    step_once: bool,
+    frame_start: Instant,
+    next_step: &mut Instant,
+) -> bool {
+    if paused {
+        return step_once && taken == 0;
+    }
+    if speed <= 0.0 {
+        // As fast as possible, but still showing a frame now and then.
+        return !taken.is_multiple_of(256) || frame_start.elapsed() < FRAME_INTERVAL;
+    }
+    if Instant::now() < *next_step {
+        return false;
     }
+    *next_step += Duration::from_secs_f64(1.0 / speed);
+    true
 }
 
 impl Main {
-    fn start(&mut self) {
-        let mut index = 0;
+    /// A run of `sim` in `window`, not drawn yet.
+    fn new(
+        window: Box<dyn Backend>,
+        renderer: Renderer,
+        sim: Simulation,
+        options: &Options,
+    ) -> Main {
+        let fade = if options.fade > 0 {
+            Some(Fade::new(options.fade, sim.grid.len()))
+        } else {
+            None
+        };
+        let mut main = Main {
+            window,
+            initial: if options.on_finish == Finish::Loop {
+                Some(sim.clone())
+            } else {
+                None
+            },
+            sim,
+            speed: options.speed,
+            rate: 0.0,
+            rate_since: Instant::now(),
+            rate_steps: 0,
+            metrics: Metrics::new(),
+            graph: false,
+            histogram: false,
+            counts: None,
+            damage: Arc::new(Mutex::new(Damage::default())),
+            moves: Arc::new(Mutex::new(Moves::default())),
+            path: options.show_path,
+            show_counter: options.show_counter,
+            export_path: options
+                .export_on_exit
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("langtons_ant.txt")),
+            screenshot_dir: options.screenshot_dir.clone(),
+            renderer,
+            scale: options.scale,
+            last_frame: Instant::now(),
+            rewinding: false,
+            paused: options.edit,
+            editing: None,
+            view: (0, 0),
+            zoom: usize::from(options.zoom),
+            blend: options.zoom_blend,
+            autofit: options.autofit,
+            keys: options.keys.clone(),
+            heading: options.heading,
+            ant_glyphs: options.ant_glyphs,
+            theme: options.theme.clone(),
+            fade,
+            slowmo: if options.slowmo_on.is_empty() {
+                None
+            } else {
+                Some(Slowmo::new(options.slowmo_on.clone()))
+            },
+            detect_cycles: options.detect_cycles,
+            cycles: None,
+            events: None,
+            control: None,
+            checkpoints: None,
+            session: None,
+            limit: options.steps,
+            duration: options.duration,
+            deadline: None,
+            left_arena: None,
+            title: String::new(),
+            on_finish: options.on_finish,
+            screensaver: None,
+        };
+        main.observe_run();
+        main
+    }
+
+    /// Follow the run in `sim` from here on, after it was started or replaced. Its colors are
+    /// counted from scratch, and cycles looked for from its current step.
+    fn observe_run(&mut self) {
+        self.counts = None;
+        self.damage.lock().unwrap().take();
+        self.sim.observe(self.damage.clone());
+        self.moves.lock().unwrap().take();
+        self.sim.observe(self.moves.clone());
+        // Whether the run can be watched was checked before the terminal was taken over.
+        self.cycles = if self.detect_cycles {
+            Cycles::watch(&mut self.sim).ok()
+        } else {
+            None
+        };
+    }
+
+    fn start(&mut self) -> Stats {
+        let started = Instant::now();
+        self.deadline = self.duration.map(|duration| started + duration);
+        let mut next_step = Instant::now();
         loop {
-            index += 1;
-            if self.show_counter {
-            self.window.mvprintw(0, 0, &index.to_string());
+            let status = self.draw_status();
+
+            if self.editing.is_some() {
+                if !self.edit() {
+                    break;
+                }
+                self.window.refresh();
+                std::thread::sleep(PAUSED_POLL);
+                continue;
+            }
+
+            let mut step_once = false;
+            while let Some(input) = self.window.poll() {
+                let key = match input {
+                    Input::Key(_) if self.screensaver.is_some() => {
+                        return self.sim.stats(started.elapsed())
+                    }
+                    Input::Key(key) => key,
+                    Input::Click { row, column, shift } => {
+                        self.click(row, column, shift);
+                        continue;
+                    }
+                };
+                match self.keys.action(key) {
+                    Some(Action::Quit) => return self.sim.stats(started.elapsed()),
+                    Some(Action::Pause) => self.paused = !self.paused,
+                    Some(Action::Step) => step_once = true,
+                    Some(Action::StepBack) => {
+                        self.step_backward();
+                    }
+                    Some(Action::Rewind) => self.rewinding = !self.rewinding,
+                    Some(Action::Reverse) => self.reverse(),
+                    // Unlimited stays unlimited.
+                    Some(Action::Faster) => self.speed *= 2.0,
+                    Some(Action::Slower) if self.speed == 0.0 => {
+                        self.speed = (self.rate / 2.0).max(1.0)
+                    }
+                    Some(Action::Slower) => self.speed = (self.speed / 2.0).max(MIN_SPEED),
+                    Some(Action::PanUp) => self.pan(0, -1),
+                    Some(Action::PanDown) => self.pan(0, 1),
+                    Some(Action::PanLeft) => self.pan(-1, 0),
+                    Some(Action::PanRight) => self.pan(1, 0),
+                    Some(Action::ZoomIn) => self.zoom_by(-1),
+                    Some(Action::ZoomOut) => self.zoom_by(1),
+                    Some(Action::Autofit) => self.toggle_autofit(),
+                    // The cursor is a single cell, so only without zoom.
+                    Some(Action::Edit)
+                        if self.paused && self.renderer == Renderer::Cells && self.zoom == 1 =>
+                    {
+                        self.toggle_edit()
+                    }
+                    Some(Action::Export) => {
+                        let path = export::numbered_path(&self.export_path, self.sim.steps);
+                        self.export_to(&path, &status);
+                    }
+                    Some(Action::Graph) => self.toggle_graph(),
+                    Some(Action::Histogram) => self.toggle_histogram(),
+                    Some(Action::QuickSave(slot)) => self.quick_save(slot, &status),
+                    Some(Action::QuickLoad(slot)) => self.quick_load(slot, &status),
+                    Some(Action::Screenshot) => self.screenshot(&status),
+                    Some(Action::Edit) | None => {}
+                }
+            }
+
+            if !self.poll_control(&status) {
+                return self.sim.stats(started.elapsed());
+            }
+
+            if self.paused && !step_once {
+                self.draw_graph();
+                self.draw_histogram();
+                self.window.refresh();
+                std::thread::sleep(PAUSED_POLL);
+                next_step = Instant::now();
+                continue;
+            }
+
+            // Steps are scheduled against the clock rather than slept between, so the time spent
+            // drawing doesn't slow the run down. A backlog too big to catch up on is dropped.
+            let frame_start = Instant::now();
+            if frame_start > next_step + MAX_LAG {
+                next_step = frame_start;
+            }
+            let speed = self.pace(self.speed);
+            let mut taken = 0;
+            // A change of pace waits for the next frame.
+            while self.pace(self.speed) == speed
+                && step_due(speed, self.paused, taken, step_once, frame_start, &mut next_step)
+            {
+                if !self.advance(&status) {
+                    return self.sim.stats(started.elapsed());
+                }
+                taken += 1;
+            }
+
+            self.fit();
+            self.draw_fading();
+            self.draw_graph();
+            self.draw_histogram();
+            self.window.refresh();
+            self.flush_events();
+            self.metrics.frame(frame_start.elapsed());
+            let now = Instant::now();
+            if speed > 0.0 && next_step > now {
+                // Wake up for keys even when the next step is a long way off.
+                std::thread::sleep((next_step - now).min(PAUSED_POLL));
+            }
+        }
+
+        self.sim.stats(started.elapsed())
+    }
+
+    /// Export the grid to `path`, saying how it went after the `status` line.
+    fn export_to(&mut self, path: &Path, status: &str) {
+        let message = match export::export(path, &self.sim) {
+            Ok(()) => format!(" exported {}", path.display()),
+            Err(e) => format!(" {}", e),
+        };
+        self.window.put(0, status.len() as u16, &message);
+    }
+
+    /// Write what is on screen to `screenshot_dir`, saying so after the `status` line.
+    fn screenshot(&mut self, status: &str) {
+        let viewport = self.viewport();
+        let message = match export::screenshot(&self.screenshot_dir, &self.sim, &viewport) {
+            Ok(paths) => {
+                let names: Vec<String> =
+                    paths.iter().map(|path| path.display().to_string()).collect();
+                format!(" wrote {}", names.join(" and "))
+            }
+            Err(e) => format!(" {}", e),
+        };
+        self.window.put(0, status.len() as u16, &message);
+    }
 
+    /// The cells in view and the characters drawn for them, without the status line and
+    /// overlays on top.
+    fn viewport(&self) -> Viewport {
+        let (columns, rows) = self.screen_cells();
+        let (left, top) = self.view;
+        let right = (left + columns).min(self.sim.width());
+        let bottom = (top + rows).min(self.sim.height());
+        let lines = match self.renderer {
+            Renderer::Cells if self.zoom > 1 => {
+                let zoom = self.zoom;
+                (top / zoom..bottom.div_ceil(zoom))
+                    .map(|row| {
+                        (left / zoom..right.div_ceil(zoom))
+                            .map(|column| self.block_look(row, column).0)
+                            .collect()
+                    })
+                    .collect()
             }
-            if let Some(Input::Character('q')) = self.window.getch() {
-                break;
+            Renderer::Braille => {
+                let first = left / braille::CELL_WIDTH;
+                let last = right.div_ceil(braille::CELL_WIDTH);
+                (top / braille::CELL_HEIGHT..bottom.div_ceil(braille::CELL_HEIGHT))
+                    .map(|row| {
+                        (first..last)
+                            .map(|column| braille::glyph(&self.sim, row, column))
+                            .collect()
+                    })
+                    .collect()
             }
+            // The image has no characters, so it is written out as cells would be.
+            Renderer::Cells | Renderer::Pixels(_) => (top..bottom)
+                .map(|y| (left..right).map(|x| self.look_at(x, y).0).collect())
+                .collect(),
+        };
+        Viewport {
+            cells: Bounds {
+                min_x: left as i64,
+                min_y: top as i64,
+                max_x: right as i64 - 1,
+                max_y: bottom as i64 - 1,
+            },
+            lines,
+        }
+    }
 
-            // Offsets
-            let (oy, ox) = self.heading.offset();
-            self.x = (self.x as isize + ox as isize) as u16;
-            self.y = (self.y as isize + oy as isize) as u16;
+    /// Save the run to quick save `slot`, saying so after the `status` line.
+    fn quick_save(&mut self, slot: u8, status: &str) {
+        let message = match self.session {
+            Some(ref session) => match session.save(slot, &self.sim) {
+                Ok(path) => format!(" saved {}", path.display()),
+                Err(e) => format!(" {}", e),
+            },
+            None => format!(" {}", NO_SESSION),
+        };
+        self.window.put(0, status.len() as u16, &message);
+    }
 
-            let x = self.x as usize;
-            let y = self.y as usize;
-            if x >= self.grid.len() || y >= self.grid[x].len() {
-                return;
+    /// Carry on from the run in quick save `slot`, saying after the `status` line if there is
+    /// none.
+    fn quick_load(&mut self, slot: u8, status: &str) {
+        let loaded = match self.session {
+            Some(ref session) => session.load(slot),
+            None => Err(NO_SESSION.to_string()),
+        };
+        match loaded {
+            Ok(mut sim) => {
+                // Hooks aren't saved, the run keeps its own.
+                sim.hooks = self.sim.hooks.clone();
+                self.switch_to(sim);
+                self.window.put(0, status.len() as u16, &format!(" loaded slot {}", slot));
             }
+            Err(e) => self.window.put(0, status.len() as u16, &format!(" {}", e)),
+        }
+    }
 
-            let current = self.grid[x][y];
+    /// Carry out the commands sent to the control socket.
+    ///
+    /// Returns `false` when the program should exit.
+    fn poll_control(&mut self, status: &str) -> bool {
+        let mut server = match self.control.take() {
+            Some(server) => server,
+            None => return true,
+        };
+        let mut running = true;
+        server.poll(|command| {
+            if !running {
+                return Err("The run is over".to_string());
+            }
+            self.control(command, status, &mut running)
+        });
+        self.control = Some(server);
+        running
+    }
 
-            let new_char = match current.state {
-                State::White => {
-                    self.grid[x][y].state = State::Black;
-                    self.heading = self.heading.rotate_left();
-                    if self.path {
-                        "░"
-                    } else {
-                        " "
+    /// Carry out one control command, returning the reply. Clears `running` when the program
+    /// should exit.
+    fn control(
+        &mut self,
+        command: Control,
+        status: &str,
+        running: &mut bool,
+    ) -> Result<String, String> {
+        match command {
+            Control::Pause => self.paused = true,
+            Control::Resume if self.editing.is_some() => {
+                return Err("Can't resume while editing".to_string())
+            }
+            Control::Resume => self.paused = false,
+            Control::Step(steps) => {
+                for _ in 0..steps {
+                    if !self.advance(status) {
+                        *running = false;
+                        break;
                     }
                 }
-                State::Black => {
-                    self.grid[x][y].state = State::White;
-                    self.heading = self.heading.rotate_right();
-                    "█"
+                return Ok(self.sim.steps.to_string());
+            }
+            Control::SetSpeed(speed) => self.speed = speed,
+            Control::QueryState => return Ok(self.state_json()),
+            Control::Screenshot(path) => {
+                let path =
+                    path.unwrap_or_else(|| export::numbered_path(&self.export_path, self.sim.steps));
+                export::export(&path, &self.sim)?;
+                return Ok(path.display().to_string());
+            }
+            Control::Quit => *running = false,
+        }
+        Ok(String::new())
+    }
+
+    /// The run as a control socket client sees it.
+    fn state_json(&self) -> String {
+        let ants: Vec<String> = self
+            .sim
+            .ants
+            .iter()
+            .map(|ant| {
+                format!(
+                    r#"{{"x":{},"y":{},"heading":"{}"}}"#,
+                    ant.x,
+                    ant.y,
+                    ant.heading.name()
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"steps":{},"paused":{},"speed":{:?},"rate":{:.1},"ants":[{}]}}"#,
+            self.sim.steps,
+            self.paused,
+            self.speed,
+            self.rate,
+            ants.join(",")
+        )
+    }
+
+    /// Take one step in the current direction and deal with the run being over.
+    ///
+    /// Returns `false` when the program should exit.
+    fn advance(&mut self, status: &str) -> bool {
+        if self.rewinding {
+            if !self.step_backward() {
+                // Back at the start, which is an end of the run as well.
+                self.rewinding = false;
+                if self.on_finish == Finish::Freeze {
+                    self.paused = true;
                 }
-            };
-            self.window.mvaddstr(self.x as i32, self.y as i32, new_char);
+            }
+        } else if self.limit.is_some_and(|limit| self.sim.steps >= limit)
+            || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            || !self.step_forward()
+        {
+            if self.screensaver.is_some() {
+                self.next_run();
+                return true;
+            }
+            match self.on_finish {
+                Finish::Freeze => {
+                    self.paused = true;
+                    if let Some(left) = self.left_arena {
+                        self.window.put(0, status.len() as u16, &format!(" {}", left));
+                    }
+                }
+                Finish::Quit => return false,
+                Finish::Loop => self.restart(),
+            }
+        } else {
+            self.checkpoint(status);
+        }
+        self.rate_steps += 1;
+        true
+    }
+
+    /// Write a checkpoint if one is due, saying after the `status` line when that fails.
+    fn checkpoint(&mut self, status: &str) {
+        let checkpoints = match self.checkpoints {
+            Some(ref checkpoints) if checkpoints.due(self.sim.steps) => checkpoints,
+            _ => return,
+        };
+        if let Err(e) = checkpoints.write(&self.sim) {
+            self.window.put(0, status.len() as u16, &format!(" {}", e));
+        }
+    }
 
-            // Toggle current cells state
-            self.grid[x][y].state = current.state.toggle();
+    /// Show the step counter and the measured speed in the top left corner, if enabled.
+    ///
+    /// Returns the text drawn, so messages can go after it.
+    fn draw_status(&mut self) -> String {
+        let elapsed = self.rate_since.elapsed();
+        if elapsed >= RATE_INTERVAL {
+            self.rate = self.rate_steps as f64 / elapsed.as_secs_f64();
+            self.rate_since = Instant::now();
+            self.rate_steps = 0;
+            self.metrics.sample(self.rate);
+        }
+        if !self.show_counter {
+            return String::new();
+        }
 
-            self.window.refresh();
-            std::thread::sleep(std::time::Duration::from_millis(self.delay));
+        let rate = if self.rate < 10.0 {
+            format!("{:.1}", self.rate)
+        } else {
+            format!("{:.0}", self.rate)
+        };
+        let mut status = format!("{} {} steps/s", self.sim.steps + 1, rate);
+        if !self.sim.schedule().is_empty() {
+            status = format!("{} {}", self.sim.rule_for(0), status);
+        }
+        if let Some(event) = self.slowmo.as_ref().and_then(Slowmo::cause) {
+            status = format!("{} slowed for {}", status, event.name());
+        }
+        if let Some(cycle) = found_cycle(&self.cycles) {
+            status = format!("{} {}", status, describe_cycle(cycle));
         }
+        if !self.title.is_empty() {
+            status = format!("{} {}", self.title, status);
+        }
+        // The trailing spaces clear what was left over from a longer status.
+        self.window.put(0, 0, &format!("{}  ", status));
+        status
     }
-}
 
-fn main() {
-    let mut show_path = false;
-    let mut delay = 20;
-    let mut show_counter = true;
+    /// Show or hide the graph overlay, drawing over it again when it goes.
+    fn toggle_graph(&mut self) {
+        self.graph = !self.graph;
+        if !self.graph {
+            self.window.clear();
+            self.draw_grid();
+        }
+    }
 
-    let mut args = env::args().skip(1);
-    while let Some(arg) = args.next() {
-        match arg.as_str() {
-            "-h" | "--help" => {
-                println!("{}", HELP);
-                std::process::exit(0)
+    /// Draw the sparklines of the speed and the frame times below the status line, if shown.
+    fn draw_graph(&mut self) {
+        if !self.graph {
+            return;
+        }
+        let rates = &self.metrics.rates;
+        let frames = &self.metrics.frame_ms;
+        let lines = [
+            format!(
+                "steps/s  {:<width$} {:.0} max {:.0}",
+                rates.sparkline(&self.theme.glyphs.sparks),
+                rates.last(),
+                rates.max(),
+                width = metrics::SAMPLES
+            ),
+            format!(
+                "frame ms {:<width$} {:.2} max {:.2}",
+                frames.sparkline(&self.theme.glyphs.sparks),
+                frames.last(),
+                frames.max(),
+                width = metrics::SAMPLES
+            ),
+        ];
+        for (row, line) in lines.iter().enumerate() {
+            // The trailing spaces clear what was left over from longer numbers.
+            self.window.put(row as u16 + 1, 0, &format!("{}  ", line));
+        }
+    }
+
+    /// Show or hide the histogram panel, drawing over it again when it goes. The counts go on
+    /// being kept once started, so the flips cover the whole run.
+    fn toggle_histogram(&mut self) {
+        self.histogram = !self.histogram;
+        if !self.histogram {
+            self.window.clear();
+            self.draw_grid();
+        }
+    }
+
+    /// Draw a line for each color at the right edge, if shown: its look, a bar of its share of
+    /// the cells, the number of cells and how often a cell was painted it.
+    fn draw_histogram(&mut self) {
+        if !self.histogram {
+            return;
+        }
+        let counts = match self.counts {
+            Some(ref counts) => counts.clone(),
+            None => {
+                let counts = Arc::new(Mutex::new(Histogram::of(&self.sim.grid)));
+                self.sim.observe(counts.clone());
+                self.counts = Some(counts.clone());
+                counts
             }
-            "-p" | "--path" => {
-                show_path = true;
+        };
+        let histogram = counts.lock().unwrap();
+        // Colors past the rules' own only show up when something else painted them.
+        let colors = (0..histogram.cells.len())
+            .rev()
+            .find(|&color| histogram.cells[color] > 0 || histogram.flips[color] > 0)
+            .map_or(0, |color| color + 1)
+            .max(usize::from(self.sim.colors()));
+        let total: u64 = histogram.cells.iter().sum();
+        let header = format!("{:>3}   {:<10} {:>10} {:>12}", "", "", "cells", "flips");
+        let mut lines = vec![(None, header)];
+        for color in 0..colors {
+            let cells = histogram.cells[color];
+            let bar = match total {
+                0 => 0,
+                total => ((cells as f64 / total as f64) * 10.0).round() as usize,
+            };
+            let line = format!(
+                "{:>3}   {:<10} {:>10} {:>12}",
+                color,
+                self.theme.glyphs.bar.repeat(bar),
+                cells,
+                histogram.flips[color]
+            );
+            lines.push((Some(State(color as u8)), line));
+        }
+
+        let (columns, _) = self.window.size();
+        // Every line is as wide as the first.
+        let width = lines[0].1.chars().count() as u16;
+        let column = columns.saturating_sub(width);
+        for (row, (state, line)) in lines.iter().enumerate() {
+            let row = row as u16 + 1;
+            self.window.put(row, column, line);
+            if let Some(state) = *state {
+                let look = self.theme.cell(state);
+                self.window.put_styled(row, column + 4, &look.glyph, look.style);
             }
-            "-d" | "--delay" => {
-                delay = args.next()
-                    .unwrap_or_else(|| {
-                        eprintln!("No delay given.");
-                        std::process::exit(1)
-                    })
-                    .parse()
-                    .unwrap_or_else(|_| {
-                        eprintln!("Invalid integer given");
-                        std::process::exit(1)
-                    });
+        }
+    }
+
+    /// Start the next run of `--screensaver` on a grid the size the first one started at, from
+    /// the start if there is no next one.
+    fn next_run(&mut self) {
+        let next = match self.screensaver {
+            Some(ref mut screensaver) => screensaver.next(),
+            None => return,
+        };
+        match next {
+            Ok((sim, rule)) => {
+                let size = self.sim.grid.len();
+                self.sim = sim;
+                self.observe_run();
+                self.title = rule.to_string();
+                self.left_arena = None;
+                self.deadline = self.duration.map(|duration| Instant::now() + duration);
+                if let Some(ref mut fade) = self.fade {
+                    fade.resize(self.sim.grid.len());
+                }
+                if self.sim.grid.len() != size {
+                    self.view = (0, 0);
+                }
+                self.window.clear();
+                self.draw_grid();
             }
-            "-c" | "--no-counter" => {
-                show_counter = false;
+            Err(_) => self.restart(),
+        }
+    }
+
+    /// Carry on from `sim` instead of the current run.
+    fn switch_to(&mut self, sim: Simulation) {
+        let size = self.sim.grid.len();
+        self.sim = sim;
+        self.observe_run();
+        self.left_arena = None;
+        if let Some(ref mut fade) = self.fade {
+            fade.resize(self.sim.grid.len());
+        }
+        if self.sim.grid.len() != size {
+            self.view = (0, 0);
+            self.editing = self.editing.map(|_| (0, 0));
+        }
+        self.window.clear();
+        self.draw_grid();
+    }
+
+    /// Go back to the grid the run started with.
+    fn restart(&mut self) {
+        let size = self.sim.grid.len();
+        if let Some(ref initial) = self.initial {
+            self.sim = initial.clone();
+            self.observe_run();
+        }
+        self.left_arena = None;
+        self.deadline = self.duration.map(|duration| Instant::now() + duration);
+        if let Some(ref mut fade) = self.fade {
+            fade.resize(self.sim.grid.len());
+        }
+        if self.sim.grid.len() != size {
+            // The grid grew during the run, keep the view on it.
+            self.view = (0, 0);
+            self.editing = self.editing.map(|_| (0, 0));
+        }
+        self.window.clear();
+        self.draw_grid();
+    }
+
+    /// Take one step and draw the cells the ants left and landed on. Returns `false` once an
+    /// ant left the grid.
+    fn step_forward(&mut self) -> bool {
+        let left = self.ant_cells();
+        let (origin, size) = (self.sim.origin(), self.sim.grid.len());
+        if let Err(left) = self.sim.step() {
+            self.left_arena = Some(left);
+            return false;
+        }
+        self.emit_step();
+        if let Some(ref mut slowmo) = self.slowmo {
+            slowmo.update(&self.sim);
+        }
+        let painted = self.ant_cells();
+        let changed = self.damage.lock().unwrap().take();
+        if self.sim.grid.len() != size {
+            self.grew(origin);
+            self.touch(&painted);
+            return true;
+        }
+        self.touch(&painted);
+        // Besides what the ants painted, a background may have changed cells anywhere.
+        for (x, y) in changed {
+            self.draw_cell(x, y);
+        }
+        self.draw_ant_moves(left);
+        true
+    }
+
+    /// Follow the grid growing with `--boundary grow`, from `origin` before the step: keep the
+    /// same cells in view and draw everything again.
+    fn grew(&mut self, origin: (i64, i64)) {
+        let (dx, dy) = (self.sim.origin().0 - origin.0, self.sim.origin().1 - origin.1);
+        self.view = (self.view.0 + dx as usize, self.view.1 + dy as usize);
+        if let Some((x, y)) = self.editing {
+            self.editing = Some((x + dx as usize, y + dy as usize));
+        }
+        if let Some(ref mut fade) = self.fade {
+            fade.resize(self.sim.grid.len());
+        }
+        // Pulls the view back onto whole steps and redraws.
+        self.pan(0, 0);
+    }
+
+    /// The steps per second to go at instead of `speed` while `--slowmo-on` slows the run down.
+    fn pace(&self, speed: f64) -> f64 {
+        self.slowmo.as_ref().map_or(speed, |slowmo| slowmo.pace(speed))
+    }
+
+    /// Run the other way in time from here on, unfreezing a run that is over.
+    fn reverse(&mut self) {
+        self.rewinding = !self.rewinding;
+        self.paused = false;
+    }
+
+    /// Undo one step and draw the cells painted back. Returns `false` at the start.
+    fn step_backward(&mut self) -> bool {
+        let left = self.ant_cells();
+        if !self.sim.step_back() {
+            return false;
+        }
+        // The ant is back on the grid.
+        self.left_arena = None;
+        let restored = self.damage.lock().unwrap().take();
+        for (x, y) in restored {
+            self.draw_cell(x, y);
+        }
+        self.touch(&left);
+        self.draw_ant_moves(left);
+        true
+    }
+
+    /// Report the step just taken, ant by ant as they moved.
+    fn emit_step(&mut self) {
+        let moves = self.moves.lock().unwrap().take();
+        let events = match self.events {
+            Some(ref mut events) => events,
+            None => return,
+        };
+        let step = self.sim.steps;
+        let result = moves.iter().try_for_each(|&(i, ant, old, new)| {
+            events.step(step, i, ant.x, ant.y, ant.heading, old, new)
+        });
+        if result.is_err() {
+            self.events = None;
+        }
+    }
+
+    fn flush_events(&mut self) {
+        let failed = match self.events {
+            Some(ref mut events) => events.flush().is_err(),
+            None => false,
+        };
+        if failed {
+            self.events = None;
+        }
+    }
+
+    /// Start fading the cells at `cells`, which changed in the step just taken.
+    fn touch(&mut self, cells: &[(usize, usize)]) {
+        let width = self.sim.width();
+        if let Some(ref mut fade) = self.fade {
+            fade.tick();
+            for &(x, y) in cells {
+                fade.touch(y * width + x);
             }
-            _ => {}
         }
     }
 
-    let window = initscr();
-    noecho();
-    curs_set(0);
+    /// Draw the cells that got further along fading since the last frame.
+    fn draw_fading(&mut self) {
+        let width = self.sim.width();
+        let cells = match self.fade {
+            Some(ref mut fade) => fade.aged(),
+            None => return,
+        };
+        for index in cells {
+            self.draw_cell(index % width, index / width);
+        }
+    }
+
+    /// Column and row of every ant.
+    fn ant_cells(&self) -> Vec<(usize, usize)> {
+        self.sim
+            .ants
+            .iter()
+            .map(|ant| (ant.x as usize, ant.y as usize))
+            .collect()
+    }
+
+    /// Draw the cells `left` behind after the ants moved, then the ants where they are now.
+    fn draw_ant_moves(&mut self, left: Vec<(usize, usize)>) {
+        for (x, y) in left {
+            self.draw_cell(x, y);
+        }
+        for (x, y) in self.ant_cells() {
+            self.draw_cell(x, y);
+        }
+    }
+
+    /// Toggle the clicked cell, or drop a new ant on it with `shift`.
+    fn click(&mut self, row: u16, column: u16, shift: bool) {
+        let (x, y) = match self.renderer {
+            // A character covers several cells, take the top left one.
+            Renderer::Cells => (
+                self.view.0 + column as usize * self.zoom,
+                self.view.1 + row as usize * self.zoom,
+            ),
+            Renderer::Braille => (
+                self.view.0 + column as usize * braille::CELL_WIDTH,
+                self.view.1 + row as usize * braille::CELL_HEIGHT,
+            ),
+            // The image isn't aligned to character cells.
+            Renderer::Pixels(_) => return,
+        };
+        if x >= self.sim.width() || y >= self.sim.height() {
+            return;
+        }
+
+        if shift {
+            self.sim.add_ant(x as i64, y as i64, self.heading);
+        } else {
+            let cell = self.sim.cell_mut(x, y);
+            cell.state = cell.state.toggle();
+        }
+        self.edited();
+        if self.editing.is_some() {
+            self.draw_edit_cell(x, y);
+        } else {
+            self.draw_cell(x, y);
+        }
+        self.window.refresh();
+    }
 
-    window.nodelay(true);
-    let (rows, columns) = window.get_max_yx();
+    /// Count the colors again after a cell was changed by hand, if they are being counted, and
+    /// look for cycles from here.
+    fn recount(&mut self) {
+        if let Some(ref counts) = self.counts {
+            counts.lock().unwrap().recount(&self.sim.grid);
+        }
+        if let Some(ref cycles) = self.cycles {
+            cycles.lock().unwrap().restart(&self.sim);
+        }
+    }
 
-    if pc::has_colors() {
-        pc::start_color();
+    /// Recount after a change by hand, and stop stepping back at it since no rule can undo it.
+    fn edited(&mut self) {
+        self.sim.forget_history();
+        self.recount();
     }
 
-    pc::init_pair(1, pc::COLOR_BLACK, pc::COLOR_WHITE);
-    window.bkgd(pc::COLOR_PAIR(1));
+    /// Handle the pending input in edit mode. Returns `false` when the user quits.
+    ///
+    /// Only quitting and leaving edit mode go through the key map, the editing keys are fixed.
+    fn edit(&mut self) -> bool {
+        while let Some(input) = self.window.poll() {
+            let key = match input {
+                Input::Key(key) => key,
+                Input::Click { row, column, shift } => {
+                    self.click(row, column, shift);
+                    continue;
+                }
+            };
+            let (x, y) = match self.editing {
+                Some(cursor) => cursor,
+                None => return true,
+            };
+            match self.keys.action(key) {
+                Some(Action::Quit) => return false,
+                Some(Action::Edit) => {
+                    self.toggle_edit();
+                    // Leaving edit mode resumes the run.
+                    self.paused = false;
+                    return true;
+                }
+                _ => {}
+            }
+
+            match key {
+                Key::Up => self.move_cursor(x, y.wrapping_sub(1)),
+                Key::Down => self.move_cursor(x, y + 1),
+                Key::Left => self.move_cursor(x.wrapping_sub(1), y),
+                Key::Right => self.move_cursor(x + 1, y),
+                Key::Char(' ') => {
+                    let cell = self.sim.cell_mut(x, y);
+                    cell.state = cell.state.toggle();
+                    self.edited();
+                }
+                Key::Char('w') => {
+                    let cell = self.sim.cell_mut(x, y);
+                    cell.wall = !cell.wall;
+                    self.edited();
+                }
+                // Add an ant, or turn the one already under the cursor.
+                Key::Char('a') => {
+                    match self.ant_at(x, y) {
+                        Some(i) => {
+                            self.sim.ants[i].heading = self.sim.ants[i].heading.rotate_right()
+                        }
+                        None => {
+                            self.sim.add_ant(x as i64, y as i64, self.heading);
+                        }
+                    }
+                    self.edited();
+                }
+                // There has to be an ant left.
+                Key::Char('d') => match self.ant_at(x, y) {
+                    Some(i) if self.sim.ants.len() > 1 => {
+                        self.sim.ants.remove(i);
+                        self.edited();
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+            if let Some((x, y)) = self.editing {
+                self.draw_edit_cell(x, y);
+            }
+        }
+        true
+    }
+
+    /// Enter edit mode with the cursor on the first ant, or leave it.
+    fn toggle_edit(&mut self) {
+        match self.editing.take() {
+            Some((x, y)) => {
+                self.draw_cell(x, y);
+                for i in 0..self.sim.ants.len() {
+                    let ant = self.sim.ants[i];
+                    self.draw_cell(ant.x as usize, ant.y as usize);
+                }
+            }
+            None => {
+                let ant = self.sim.ants[0];
+                self.editing = Some((ant.x as usize, ant.y as usize));
+                self.draw_edit_marks();
+            }
+        }
+        self.window.refresh();
+    }
+
+    /// Move the edit cursor to column `x` and row `y` if that is on the grid, panning to it.
+    fn move_cursor(&mut self, x: usize, y: usize) {
+        if x >= self.sim.width() || y >= self.sim.height() {
+            return;
+        }
+        if let Some((old_x, old_y)) = self.editing.replace((x, y)) {
+            self.draw_edit_cell(old_x, old_y);
+        }
+
+        let (columns, rows) = self.screen_cells();
+        let (left, top) = self.view;
+        let pan_columns = if x < left {
+            -1
+        } else {
+            (x >= left + columns) as isize
+        };
+        let pan_rows = if y < top {
+            -1
+        } else {
+            (y >= top + rows) as isize
+        };
+        if pan_columns != 0 || pan_rows != 0 {
+            self.pan(pan_columns, pan_rows);
+        }
+    }
+
+    /// Index of an ant standing on column `x`, row `y`.
+    fn ant_at(&self, x: usize, y: usize) -> Option<usize> {
+        self.sim
+            .ants
+            .iter()
+            .position(|ant| (ant.x as usize, ant.y as usize) == (x, y))
+    }
+
+    /// Draw the ants and the cursor on top of the grid.
+    fn draw_edit_marks(&mut self) {
+        for i in 0..self.sim.ants.len() {
+            let ant = self.sim.ants[i];
+            self.draw_edit_cell(ant.x as usize, ant.y as usize);
+        }
+        if let Some((x, y)) = self.editing {
+            self.draw_edit_cell(x, y);
+        }
+    }
+
+    /// Draw a cell in edit mode, where the cursor is a shaded block unless an ant is on it.
+    fn draw_edit_cell(&mut self, x: usize, y: usize) {
+        if self.editing == Some((x, y)) && self.ant_at(x, y).is_none() {
+            // Darker over painted cells, so the cell's state stays visible.
+            let shades = self.theme.glyphs.shades;
+            let cursor = if self.sim.cell(x, y).state.is_blank() {
+                shades[1]
+            } else {
+                shades[2]
+            };
+            self.put_in_view(x, y, cursor, Style::default());
+        } else {
+            self.draw_cell(x, y);
+        }
+    }
+
+    /// Move the view by `columns` and `rows` pan steps and redraw.
+    fn pan(&mut self, columns: isize, rows: isize) {
+        let (step_columns, step_rows) = match self.renderer {
+            Renderer::Cells => (8 * self.zoom, 4 * self.zoom),
+            Renderer::Braille => (8 * braille::CELL_WIDTH, 4 * braille::CELL_HEIGHT),
+            // The image always shows the whole grid.
+            Renderer::Pixels(_) => return,
+        };
+        let (screen_columns, screen_rows) = self.screen_cells();
+        let max_left = self.sim.width().saturating_sub(screen_columns);
+        let max_top = self.sim.height().saturating_sub(screen_rows);
+
+        let shift = |at: usize, by: isize, step: usize, max: usize| {
+            let moved = (at as isize + by * step as isize).max(0) as usize;
+            // Stay aligned to whole steps so braille and zoomed out characters keep covering the
+            // same cells.
+            (moved.min(max) / step) * step
+        };
+        self.view = (
+            shift(
+                self.view.0,
+                columns,
+                step_columns,
+                max_left.div_ceil(step_columns) * step_columns,
+            ),
+            shift(self.view.1, rows, step_rows, max_top.div_ceil(step_rows) * step_rows),
+        );
+
+        self.window.clear();
+        self.draw_grid();
+        if self.editing.is_some() {
+            self.draw_edit_marks();
+            self.window.refresh();
+        }
+    }
+
+    /// Change the zoom by `by` cells per character, keeping the middle of the view in place.
+    fn zoom_by(&mut self, by: isize) {
+        if self.renderer != Renderer::Cells || self.editing.is_some() {
+            return;
+        }
+        let (columns, rows) = self.screen_cells();
+        let middle = (self.view.0 + columns / 2, self.view.1 + rows / 2);
+        // Zooming out stops once the whole grid fits.
+        let (width, height) = self.window.size();
+        let fits = self
+            .sim
+            .width()
+            .div_ceil(usize::from(width.max(1)))
+            .max(self.sim.height().div_ceil(usize::from(height.max(1))));
+        let zoom = (self.zoom as isize + by).max(1) as usize;
+        if zoom == self.zoom || (by > 0 && zoom > fits.max(self.zoom)) {
+            return;
+        }
+        self.zoom = zoom;
+
+        let (columns, rows) = self.screen_cells();
+        self.view = (
+            middle.0.saturating_sub(columns / 2),
+            middle.1.saturating_sub(rows / 2),
+        );
+        // Lines the view up with the new zoom and redraws.
+        self.pan(0, 0);
+    }
+
+    /// Start or stop following the visited cells, moving the view to them right away.
+    fn toggle_autofit(&mut self) {
+        self.autofit = !self.autofit;
+        self.fit();
+    }
+
+    /// With autofit, center the view on the visited cells, at the closest zoom that shows them
+    /// all. The view stays aligned to pan steps like `pan` keeps it, and is only drawn again
+    /// when it changed.
+    fn fit(&mut self) {
+        let bounds = match self.sim.visited {
+            Some(bounds) if self.autofit && self.editing.is_none() => bounds,
+            _ => return,
+        };
+        let (min_x, min_y) = (bounds.min_x.max(0) as usize, bounds.min_y.max(0) as usize);
+        let (max_x, max_y) = (bounds.max_x.max(0) as usize, bounds.max_y.max(0) as usize);
+
+        let (width, height) = self.window.size();
+        let (width, height) = (usize::from(width.max(1)), usize::from(height.max(1)));
+        let middle = ((min_x + max_x) / 2, (min_y + max_y) / 2);
+        let align = |middle: usize, screen: usize, step: usize, size: usize| {
+            let max = size.saturating_sub(screen).div_ceil(step) * step;
+            (middle.saturating_sub(screen / 2).min(max) / step) * step
+        };
+        // Only the default renderer zooms.
+        let mut zoom = match self.renderer {
+            Renderer::Cells => 1,
+            _ => self.zoom,
+        };
+        let view = loop {
+            let (columns, rows, steps) = match self.renderer {
+                Renderer::Cells => (width * zoom, height * zoom, (8 * zoom, 4 * zoom)),
+                Renderer::Braille => {
+                    let (columns, rows) = self.screen_cells();
+                    (columns, rows, (8 * braille::CELL_WIDTH, 4 * braille::CELL_HEIGHT))
+                }
+                // The image always shows the whole grid.
+                Renderer::Pixels(_) => return,
+            };
+            let left = align(middle.0, columns, steps.0, self.sim.width());
+            let top = align(middle.1, rows, steps.1, self.sim.height());
+            let fits =
+                min_x >= left && min_y >= top && max_x < left + columns && max_y < top + rows;
+            let whole = columns >= self.sim.width() && rows >= self.sim.height();
+            if fits || whole || self.renderer != Renderer::Cells {
+                break (left, top);
+            }
+            zoom += 1;
+        };
+        if (zoom, view) != (self.zoom, self.view) {
+            self.zoom = zoom;
+            self.view = view;
+            self.pan(0, 0);
+        }
+    }
+
+    /// Columns and rows of grid cells that fit on screen.
+    fn screen_cells(&self) -> (usize, usize) {
+        let (columns, rows) = self.window.size();
+        match self.renderer {
+            Renderer::Cells => (columns as usize * self.zoom, rows as usize * self.zoom),
+            Renderer::Braille => (
+                columns as usize * braille::CELL_WIDTH,
+                rows as usize * braille::CELL_HEIGHT,
+            ),
+            _ => (columns as usize, rows as usize),
+        }
+    }
+
+    /// Draw `text` at grid column `x` and row `y`, if that is in view.
+    ///
+    /// For braille and zoomed out cells the position is in characters rather than cells.
+    fn put_in_view(&mut self, x: usize, y: usize, text: &str, style: Style) {
+        let (left, top) = match self.renderer {
+            Renderer::Cells => (self.view.0 / self.zoom, self.view.1 / self.zoom),
+            Renderer::Braille => (
+                self.view.0 / braille::CELL_WIDTH,
+                self.view.1 / braille::CELL_HEIGHT,
+            ),
+            Renderer::Pixels(_) => self.view,
+        };
+        let (columns, rows) = self.window.size();
+        if x >= left && y >= top && x - left < columns as usize && y - top < rows as usize {
+            self.window
+                .put_styled((y - top) as u16, (x - left) as u16, text, style);
+        }
+    }
+
+    /// Show the cell at column `x` and row `y` after it changed.
+    ///
+    /// Ants are drawn over their cell, or the block of cells they are in when zoomed out, by
+    /// heading. Braille characters are too small for that and the image draws them itself.
+    fn draw_cell(&mut self, x: usize, y: usize) {
+        match self.renderer {
+            Renderer::Cells if self.zoom > 1 => {
+                let (column, row) = (x / self.zoom, y / self.zoom);
+                self.draw_block(row, column);
+            }
+            Renderer::Cells => {
+                let (glyph, style) = self.look_at(x, y);
+                self.put_in_view(x, y, &glyph, style);
+            }
+            Renderer::Braille => {
+                let (column, row) = (x / braille::CELL_WIDTH, y / braille::CELL_HEIGHT);
+                let glyph = braille::glyph(&self.sim, row, column).to_string();
+                self.put_in_view(column, row, &glyph, Style::default());
+            }
+            Renderer::Pixels(_) => {
+                if self.last_frame.elapsed() >= FRAME_INTERVAL {
+                    self.draw_image();
+                }
+            }
+        }
+    }
+
+    /// The character for an ant by its heading.
+    fn ant_glyph(&self, ant: usize) -> String {
+        let index = match self.sim.ants[ant].heading {
+            Direction::Up => 0,
+            Direction::Down => 1,
+            Direction::Left => 2,
+            Direction::Right => 3,
+        };
+        self.ant_glyphs[index].to_string()
+    }
+
+    /// The colors of an ant: its own color from `--ant` if it has one, the theme's otherwise.
+    fn ant_style(&self, ant: usize) -> Style {
+        Style {
+            fg: self.sim.ants[ant].color.or(self.theme.ant.fg),
+            bg: self.theme.ant.bg,
+        }
+    }
+
+    /// The character for `cell` and its colors in the theme, `fading` at that level if set.
+    fn cell_look(&self, cell: Cell, fading: Option<u64>) -> (String, Style) {
+        if cell.wall {
+            return (self.theme.wall.glyph.clone(), self.theme.wall.style);
+        }
+        let look = self.theme.cell(cell.state);
+        let shades = self.theme.glyphs.shades;
+        let glyph = match (cell.state, fading) {
+            // Changed cells start out looking like the other color, from dark after turning
+            // black and from light after being painted.
+            (State::BLACK, Some(level)) => shades[shades.len() - 1 - level as usize],
+            (_, Some(level)) => shades[level as usize],
+            (State::BLACK, None) if self.path => shades[0],
+            (_, None) => &look.glyph,
+        };
+        (glyph.to_string(), look.style)
+    }
+
+    /// Draw the zoomed out character at terminal `row` and `column`, showing the first ant in
+    /// its block if there is one.
+    fn draw_block(&mut self, row: usize, column: usize) {
+        let (text, style) = self.block_look(row, column);
+        self.put_in_view(column, row, &text, style);
+    }
+
+    /// The character and colors `draw_block` shows.
+    fn block_look(&self, row: usize, column: usize) -> (String, Style) {
+        let zoom = self.zoom;
+        let ant = self.sim.ants.iter().position(|ant| {
+            (ant.x as usize / zoom, ant.y as usize / zoom) == (column, row)
+        });
+        match ant {
+            Some(i) => (self.ant_glyph(i), self.ant_style(i)),
+            None => {
+                let cell = zoom::block(&self.sim, row, column, zoom, self.blend);
+                self.cell_look(cell, None)
+            }
+        }
+    }
+
+    /// The character and colors of the cell at column `x` and row `y` without zoom, or of the
+    /// ant on it.
+    fn look_at(&self, x: usize, y: usize) -> (String, Style) {
+        if let Some(i) = self.ant_at(x, y) {
+            return (self.ant_glyph(i), self.ant_style(i));
+        }
+        let cell = self.sim.cell(x, y);
+        let fading = self
+            .fade
+            .as_ref()
+            .and_then(|fade| fade.level(y * self.sim.width() + x));
+        self.cell_look(cell, fading)
+    }
+
+    /// Draw every painted cell and wall in view, for grids that didn't start out blank.
+    fn draw_grid(&mut self) {
+        let (columns, rows) = self.screen_cells();
+        let (left, top) = self.view;
+        let right = (left + columns).min(self.sim.width());
+        let bottom = (top + rows).min(self.sim.height());
+        match self.renderer {
+            Renderer::Cells if self.zoom > 1 => {
+                let zoom = self.zoom;
+                for row in top / zoom..bottom.div_ceil(zoom) {
+                    for column in left / zoom..right.div_ceil(zoom) {
+                        self.draw_block(row, column);
+                    }
+                }
+            }
+            Renderer::Cells => {
+                // Black cells look like the cleared screen, unless the theme draws them.
+                let black = self.theme.cell(State::BLACK);
+                let cleared = black.glyph == " " && black.style == Style::default();
+                for y in top..bottom {
+                    for x in left..right {
+                        let cell = self.sim.cell(x, y);
+                        if cell.wall || !cell.state.is_blank() || !cleared {
+                            self.draw_cell(x, y);
+                        }
+                    }
+                }
+            }
+            Renderer::Braille => {
+                let first = left / braille::CELL_WIDTH;
+                let last = right.div_ceil(braille::CELL_WIDTH);
+                for row in top / braille::CELL_HEIGHT..bottom.div_ceil(braille::CELL_HEIGHT) {
+                    let line: String = (first..last)
+                        .map(|column| braille::glyph(&self.sim, row, column))
+                        .collect();
+                    self.put_in_view(first, row, &line, Style::default());
+                }
+            }
+            Renderer::Pixels(_) => self.draw_image(),
+        }
+        self.window.refresh();
+    }
+
+    /// Draw the whole grid as one image below the counter line.
+    fn draw_image(&mut self) {
+        if let Renderer::Pixels(protocol) = self.renderer {
+            let image = graphics::encode(protocol, &self.sim, self.scale);
+            self.window.put(1, 0, &image);
+            self.last_frame = Instant::now();
+        }
+    }
+}
+
+/// Parse the value following `flag`, exiting with a message if it is missing or malformed.
+fn parse_value<T: FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> T {
+    let value = args.next().unwrap_or_else(|| {
+        eprintln!("No value given for {}.", flag);
+        std::process::exit(1)
+    });
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid value for {}: {}", flag, value);
+        std::process::exit(1)
+    })
+}
+
+/// The speed matching a delay of `ms` milliseconds between steps.
+fn speed_from_delay(ms: u64) -> f64 {
+    if ms == 0 {
+        0.0
+    } else {
+        1000.0 / ms as f64
+    }
+}
+
+/// Parse the characters for ants heading up, down, left and right, in that order.
+fn parse_glyphs(value: &str) -> Result<[char; 4], String> {
+    let chars: Vec<char> = value.chars().collect();
+    match chars[..] {
+        [up, down, left, right] => Ok([up, down, left, right]),
+        _ => Err(format!(
+            "Expected four characters for up, down, left and right like ^v<>, got {}",
+            value
+        )),
+    }
+}
+
+/// Parse a time like `30s`, `500ms`, `5m` or `1h`. A bare number is in seconds.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let error = || format!("Expected a time like 30s, 500ms, 5m or 1h, got {}", value);
+    let at = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(at);
+    let number: f64 = number.parse().map_err(|_| error())?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(error()),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| error())
+}
+
+/// The time `parse_duration` reads back as `duration`.
+fn duration_name(duration: Duration) -> String {
+    if duration.subsec_millis() == 0 {
+        format!("{}s", duration.as_secs())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+/// Parse a comma separated list of rules.
+fn parse_rules(value: &str) -> Result<Vec<Arc<dyn Rule>>, String> {
+    value.split(',').map(|spec| rule::parse(spec.trim())).collect()
+}
+
+/// Parse an `X,Y` pair.
+fn parse_point<T: FromStr>(value: &str) -> Option<(T, T)> {
+    let mut parts = value.splitn(2, ',');
+    let x = parts.next()?.trim().parse().ok()?;
+    let y = parts.next()?.trim().parse().ok()?;
+    Some((x, y))
+}
+
+/// Parse a `WxH` size.
+fn parse_size(value: &str) -> Option<(u16, u16)> {
+    let mut parts = value.splitn(2, 'x');
+    let w = parts.next()?.trim().parse().ok()?;
+    let h = parts.next()?.trim().parse().ok()?;
+    Some((w, h))
+}
+
+/// Run the `render` command.
+#[cfg(feature = "image")]
+fn render(options: &Options) {
+    let path = options.gif.as_ref().unwrap_or_else(|| {
+        eprintln!("render needs an output file, pass --gif FILE");
+        std::process::exit(1)
+    });
+    if options.boundary == Boundary::Grow {
+        eprintln!("render can't use --boundary grow, every frame of a GIF has the same size");
+        std::process::exit(1)
+    }
+    let (w, h) = options.size.unwrap_or((100, 100));
+    let mut sim = setup(w, h, options).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    });
+    let gif_options = render::GifOptions {
+        steps: options.steps.unwrap_or(11_000),
+        frame_every: options.frame_every,
+        scale: options.scale,
+        frame_delay: options.frame_delay,
+    };
+
+    let cycles = watch_cycles(options, &mut sim);
+    let trace = open_trace(options, &mut sim);
+
+    let started = Instant::now();
+    match render::gif(&mut sim, path, &gif_options) {
+        Ok(Some(left)) => eprintln!("{}", left),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1)
+        }
+    }
+    finish_trace(trace, &sim);
+    if let Some(cycle) = found_cycle(&cycles) {
+        eprintln!("{}", describe_cycle(cycle));
+    }
+    for e in write_outputs(&sim, options) {
+        eprintln!("{}", e);
+    }
+    print_stats(&sim.stats(started.elapsed()), options);
+}
+
+#[cfg(not(feature = "image"))]
+fn render(_: &Options) {
+    eprintln!("render needs the image feature");
+    std::process::exit(1)
+}
+
+/// Run the `window` command.
+#[cfg(feature = "gui")]
+fn window(options: &Options) {
+    match window::run(options) {
+        Ok(stats) => print_stats(&stats, options),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+#[cfg(not(feature = "gui"))]
+fn window(_: &Options) {
+    eprintln!("window needs the gui feature");
+    std::process::exit(1)
+}
+
+/// Run the `bench` command: step as fast as possible and report how it went.
+fn bench(options: &Options) {
+    let (w, h) = options.size.unwrap_or((4096, 4096));
+    let mut sim = setup(w, h, options).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    });
+    let steps = options.steps.unwrap_or(100_000_000);
+    let checkpoints = checkpoints(options).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    });
+
+    let cycles = watch_cycles(options, &mut sim);
+    let trace = open_trace(options, &mut sim);
+
+    let started = Instant::now();
+    let mut left_arena = None;
+    while sim.steps < steps {
+        if let Err(left) = sim.step() {
+            left_arena = Some(left);
+            break;
+        }
+        // The clock is read only now and then, it costs more than a step.
+        if sim.steps.is_multiple_of(65536)
+            && options.duration.is_some_and(|duration| started.elapsed() >= duration)
+        {
+            break;
+        }
+        if let Some(ref checkpoints) = checkpoints {
+            if checkpoints.due(sim.steps) {
+                if let Err(e) = checkpoints.write(&sim) {
+                    eprintln!("{}", e);
+                }
+            }
+        }
+    }
+    let elapsed = started.elapsed();
+    finish_trace(trace, &sim);
+
+    let stats = sim.stats(elapsed);
+    if options.stats_json {
+        println!("{}", stats.to_json());
+        return;
+    }
+    let rules: Vec<String> = sim.rules().iter().map(|rule| rule.to_string()).collect();
+    println!("rule:         {}", rules.join(", "));
+    println!("{}", stats);
+    if let Some(left) = left_arena {
+        println!(
+            "stopped:      ant {} left the {}x{} arena at step {}",
+            left.ant + 1,
+            w,
+            h,
+            left.step
+        );
+    }
+    if cycles.is_some() {
+        match found_cycle(&cycles) {
+            Some(cycle) => println!("cycle:        {}", describe_cycle(cycle)),
+            None => println!("cycle:        none found"),
+        }
+    }
+    if let Some(kib) = peak_memory() {
+        println!("peak memory:  {:.1} MiB", kib as f64 / 1024.0);
+    }
+}
+
+/// Run the `verify` command.
+///
+/// Prints the fingerprint of the run, and exits with an error if it isn't the expected one.
+fn verify(options: &Options) {
+    let (w, h) = options.size.unwrap_or((100, 100));
+    let mut sim = setup(w, h, options).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    });
+    let steps = options.steps.unwrap_or(11_000);
+    let cycles = watch_cycles(options, &mut sim);
+    let trace = open_trace(options, &mut sim);
+    while sim.steps < steps {
+        if let Err(left) = sim.step() {
+            // Where the ant left is as much the end of the run as the last step.
+            eprintln!("{}", left);
+            break;
+        }
+    }
+    finish_trace(trace, &sim);
+    // Kept off stdout, which only has the fingerprint.
+    if let Some(cycle) = found_cycle(&cycles) {
+        eprintln!("{}", describe_cycle(cycle));
+    }
+
+    let hash = sim.fingerprint();
+    match options.expect_hash {
+        Some(expected) if expected != hash => {
+            eprintln!("mismatch: expected {:016x}, got {:016x}", expected, hash);
+            std::process::exit(1)
+        }
+        Some(_) => println!("ok {:016x}", hash),
+        None => println!("{:016x}", hash),
+    }
+}
+
+/// Run the `sweep` command.
+#[cfg(feature = "image")]
+fn sweep(options: &Options) {
+    let (rules, out) = match (&options.rules_file, &options.out) {
+        (Some(rules), Some(out)) => (rules, out),
+        _ => {
+            eprintln!("sweep needs a file of rules and a directory, pass --rules FILE --out DIR");
+            std::process::exit(1)
+        }
+    };
+    if options.load.is_some() {
+        eprintln!("sweep can't use --load, every rule starts from scratch");
+        std::process::exit(1)
+    }
+    let entries = sweep::read(rules).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    });
+    let threads = options
+        .threads
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+    let (w, h) = options.size.unwrap_or((100, 100));
+
+    let outcomes = sweep::run(&entries, w, h, threads, out, options).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    });
+    for outcome in outcomes {
+        let stats = &outcome.stats;
+        if options.stats_json {
+            println!(r#"{{"rule":"{}","stats":{}}}"#, outcome.rule, stats.to_json());
+            continue;
+        }
+        let size = match stats.bounds {
+            Some(bounds) => format!("{}x{}", bounds.width(), bounds.height()),
+            None => "0x0".to_string(),
+        };
+        let ending = match outcome.left {
+            Some(left) => format!(", left the arena at step {}", left.step),
+            None => String::new(),
+        };
+        println!(
+            "rule-{:<4} {:<16} {} steps, {} painted in {}{}",
+            outcome.line, outcome.rule, stats.steps, stats.white, size, ending
+        );
+    }
+}
+
+#[cfg(not(feature = "image"))]
+fn sweep(_: &Options) {
+    eprintln!("sweep needs the image feature");
+    std::process::exit(1)
+}
+
+/// The trace file asked for with `--trace`, following every step of `sim`. Exits if it can't
+/// be created.
+fn open_trace(options: &Options, sim: &mut Simulation) -> Option<Arc<Mutex<Trace>>> {
+    options.trace.as_ref().map(|path| {
+        let trace = Trace::create(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1)
+        });
+        let trace = Arc::new(Mutex::new(trace));
+        sim.observe(trace.clone());
+        trace
+    })
+}
+
+/// What watches `sim` for a cycle with `--detect-cycles`. Exits if it can't be watched.
+fn watch_cycles(options: &Options, sim: &mut Simulation) -> Option<Arc<Mutex<Cycles>>> {
+    if !options.detect_cycles {
+        return None;
+    }
+    let cycles = Cycles::watch(sim).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    });
+    Some(cycles)
+}
+
+/// The cycle `cycles` found, if it is watching and found one.
+fn found_cycle(cycles: &Option<Arc<Mutex<Cycles>>>) -> Option<Cycle> {
+    cycles.as_ref().and_then(|cycles| cycles.lock().unwrap().found())
+}
+
+fn describe_cycle(cycle: Cycle) -> String {
+    format!("cycle of {} steps from step {}", cycle.period, cycle.start)
+}
+
+/// Let the observers of `sim` know the run is over, exiting if writing `trace` failed along
+/// the way.
+fn finish_trace(trace: Option<Arc<Mutex<Trace>>>, sim: &Simulation) {
+    sim.finish();
+    if let Some(trace) = trace {
+        if let Some(e) = trace.lock().unwrap().error() {
+            eprintln!("{}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Largest resident set size of the process so far, in KiB.
+#[cfg(unix)]
+fn peak_memory() -> Option<u64> {
+    let usage = unsafe {
+        let mut usage: libc::rusage = std::mem::zeroed();
+        if libc::getrusage(libc::RUSAGE_SELF, &mut usage) != 0 {
+            return None;
+        }
+        usage
+    };
+    // macOS reports bytes, everything else KiB.
+    if cfg!(target_os = "macos") {
+        Some(usage.ru_maxrss as u64 / 1024)
+    } else {
+        Some(usage.ru_maxrss as u64)
+    }
+}
+
+#[cfg(not(unix))]
+fn peak_memory() -> Option<u64> {
+    None
+}
+
+fn print_stats(stats: &Stats, options: &Options) {
+    if options.stats_json {
+        println!("{}", stats.to_json());
+    } else {
+        println!("{}", stats);
+    }
+}
+
+/// Take the rule, pattern, grid size and speed from the preset called `name`.
+fn use_preset(options: &mut Options, name: &str) -> Result<(), String> {
+    let preset = Preset::find(name)?;
+    options.rule = preset.rule()?;
+    options.pattern = preset.pattern()?;
+    options.size = Some(preset.size);
+    options.speed = preset.speed;
+    options.preset = Some(preset);
+    Ok(())
+}
+
+/// Print every preset with what it sets.
+fn list_presets() {
+    for preset in presets::PRESETS {
+        println!("{:<20}{}", preset.name, preset.description);
+        let (w, h) = preset.size;
+        println!("{:<20}{}, {}x{}, {} steps/s", "", preset.spec, w, h, preset.speed);
+    }
+}
+
+/// Load the config file named by `--config`, or the default one if it exists.
+fn load_config(options: &mut Options) {
+    let mut args = env::args().skip_while(|arg| arg != "--config");
+    let (path, required) = match args.nth(1) {
+        Some(path) => (PathBuf::from(path), true),
+        None => match config::default_path() {
+            Some(path) => (path, false),
+            None => return,
+        },
+    };
+    if !required && !path.exists() {
+        return;
+    }
+
+    let result = config::load(&path).and_then(|entries| config::apply(options, &entries));
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    }
+}
+
+fn main() {
+    let mut options = Options::default();
+    load_config(&mut options);
+    let mut dump_config = false;
+    // Whether a --rule was given yet, the first one replaces the pane rules of the config file.
+    let mut rule_given = false;
+
+    let mut args = env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("render") => options.command = Command::Render,
+        Some("bench") => options.command = Command::Bench,
+        Some("verify") => options.command = Command::Verify,
+        Some("sweep") => options.command = Command::Sweep,
+        Some("window") => options.command = Command::Window,
+        Some("list-presets") => {
+            list_presets();
+            return;
+        }
+        _ => {}
+    }
+    if options.command != Command::Run {
+        args.next();
+    }
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", HELP);
+                std::process::exit(0)
+            }
+            "--config" => {
+                // Already loaded before the other flags so that they override it.
+                args.next();
+            }
+            "--dump-config" => {
+                dump_config = true;
+            }
+            "-p" | "--path" => {
+                options.show_path = true;
+            }
+            "-d" | "--delay" => {
+                options.speed = speed_from_delay(parse_value(&mut args, &arg));
+            }
+            "--speed" => {
+                options.speed = parse_value(&mut args, &arg);
+            }
+            "-c" | "--no-counter" => {
+                options.show_counter = false;
+            }
+            "--stats-json" => {
+                options.stats_json = true;
+            }
+            "--random-fill" => {
+                let density: f64 = parse_value(&mut args, &arg);
+                if !(0.0..=1.0).contains(&density) {
+                    eprintln!("Density must be between 0 and 1.");
+                    std::process::exit(1)
+                }
+                options.random_fill = Some(density);
+            }
+            "--seed" => {
+                options.seed = Some(parse_value(&mut args, &arg));
+            }
+            "--preset" => {
+                let name: String = parse_value(&mut args, &arg);
+                if let Err(e) = use_preset(&mut options, &name) {
+                    eprintln!("{}", e);
+                    std::process::exit(1)
+                }
+            }
+            "--pattern" => {
+                let path: String = parse_value(&mut args, &arg);
+                options.pattern = Some(Pattern::load(&path).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1)
+                }));
+            }
+            #[cfg(feature = "script")]
+            "--script" => {
+                let path: String = parse_value(&mut args, &arg);
+                options.script = Some(Arc::new(Script::load(&path).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1)
+                })));
+            }
+            #[cfg(not(feature = "script"))]
+            "--script" => {
+                eprintln!("--script needs a build with the script feature");
+                std::process::exit(1)
+            }
+            "--export-on-exit" => {
+                let path = PathBuf::from(parse_value::<String>(&mut args, &arg));
+                if let Err(e) = export::Format::from_path(&path) {
+                    eprintln!("{}", e);
+                    std::process::exit(1)
+                }
+                options.export_on_exit = Some(path);
+            }
+            "--start" => {
+                let value: String = parse_value(&mut args, &arg);
+                options.start = Some(if value == "random" {
+                    Start::Random
+                } else {
+                    let (x, y) = parse_point(&value).unwrap_or_else(|| {
+                        eprintln!("Expected a position like 10,5 or random, got {}", value);
+                        std::process::exit(1)
+                    });
+                    Start::At(x, y)
+                });
+            }
+            "--heading" => {
+                let value: String = parse_value(&mut args, &arg);
+                options.heading = value.parse().unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1)
+                });
+            }
+            "--pattern-at" => {
+                let value: String = parse_value(&mut args, &arg);
+                options.pattern_at = Some(parse_point(&value).unwrap_or_else(|| {
+                    eprintln!("Expected a position like 10,5, got {}", value);
+                    std::process::exit(1)
+                }));
+            }
+            "--backend" => {
+                options.backend = parse_value(&mut args, &arg);
+            }
+            "--dumb-tty" => {
+                options.backend = "dumb".to_string();
+            }
+            "--pixels" => {
+                options.pixels = true;
+            }
+            "--braille" => {
+                options.braille = true;
+            }
+            "--ascii" => {
+                options.ascii = Some(true);
+            }
+            "--no-ascii" => {
+                options.ascii = Some(false);
+            }
+            "--ascii-cells" => {
+                let value: String = parse_value(&mut args, &arg);
+                options.ascii_cells = theme::parse_ascii_cells(&value).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1)
+                });
+            }
+            "--zoom" => {
+                options.zoom = parse_value(&mut args, &arg);
+            }
+            "--zoom-blend" => {
+                options.zoom_blend = parse_value(&mut args, &arg);
+            }
+            "--autofit" => {
+                options.autofit = true;
+            }
+            "--edit" => {
+                options.edit = true;
+            }
+            "--svg-path" => {
+                options.svg_path = Some(PathBuf::from(parse_value::<String>(&mut args, &arg)));
+            }
+            "--gif" => {
+                options.gif = Some(PathBuf::from(parse_value::<String>(&mut args, &arg)));
+            }
+            "--steps" => {
+                options.steps = Some(parse_value(&mut args, &arg));
+            }
+            "--duration" => {
+                let value: String = parse_value(&mut args, &arg);
+                options.duration = Some(parse_duration(&value).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1)
+                }));
+            }
+            "--ant" => {
+                let value: String = parse_value(&mut args, &arg);
+                options.ants.push(value.parse().unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1)
+                }));
+            }
+            "--control-socket" => {
+                options.control_socket = Some(parse_value(&mut args, &arg));
+            }
+            "--emit-steps" => {
+                options.emit_steps = Some(PathBuf::from(parse_value::<String>(&mut args, &arg)));
+            }
+            "--asciicast" => {
+                options.asciicast = Some(PathBuf::from(parse_value::<String>(&mut args, &arg)));
+            }
+            "--trace" => {
+                options.trace = Some(PathBuf::from(parse_value::<String>(&mut args, &arg)));
+            }
+            "--fade" => {
+                options.fade = parse_value(&mut args, &arg);
+            }
+            "--detect-cycles" => {
+                options.detect_cycles = true;
+            }
+            "--slowmo-on" => {
+                let value: String = parse_value(&mut args, &arg);
+                options.slowmo_on = slowmo::parse_events(&value).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1)
+                });
+            }
+            "--checkpoint-every" => {
+                options.checkpoint_every = parse_value(&mut args, &arg);
+            }
+            "--screenshot-dir" => {
+                options.screenshot_dir = PathBuf::from(parse_value::<String>(&mut args, &arg));
+            }
+            "--checkpoint-dir" => {
+                options.checkpoint_dir = PathBuf::from(parse_value::<String>(&mut args, &arg));
+            }
+            "--expect-hash" => {
+                let value: String = parse_value(&mut args, &arg);
+                let digits = value.trim_start_matches("0x");
+                options.expect_hash = Some(u64::from_str_radix(digits, 16).unwrap_or_else(|_| {
+                    eprintln!("Expected a hash in hex like 3f2a9c01d4e5b678, got {}", value);
+                    std::process::exit(1)
+                }));
+            }
+            "--load" => {
+                options.load = Some(PathBuf::from(parse_value::<String>(&mut args, &arg)));
+            }
+            "--session" => options.session = Some(parse_value(&mut args, &arg)),
+            "--theme" => {
+                let value: String = parse_value(&mut args, &arg);
+                options.theme = value.parse().unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1)
+                });
+            }
+            "--ant-glyphs" => {
+                let value: String = parse_value(&mut args, &arg);
+                options.ant_glyphs = parse_glyphs(&value).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1)
+                });
+            }
+            "--noise" => {
+                let noise: f64 = parse_value(&mut args, &arg);
+                if !(0.0..=1.0).contains(&noise) {
+                    eprintln!("Noise must be between 0 and 1.");
+                    std::process::exit(1)
+                }
+                options.noise = noise;
+            }
+            "--on-wall" => {
+                options.on_wall = parse_value(&mut args, &arg);
+            }
+            "--boundary" => {
+                options.boundary = parse_value(&mut args, &arg);
+            }
+            "--background" => {
+                options.background = parse_value(&mut args, &arg);
+            }
+            "--collision" => {
+                options.collision = parse_value(&mut args, &arg);
+            }
+            "--life-every" => {
+                let every: u64 = parse_value(&mut args, &arg);
+                if every == 0 {
+                    eprintln!("Life needs at least 1 step between generations.");
+                    std::process::exit(1)
+                }
+                options.life_every = every;
+            }
+            "--symmetry" => {
+                options.symmetry = parse_value(&mut args, &arg);
+            }
+            "--rule" => {
+                let value: String = parse_value(&mut args, &arg);
+                options.rule = rule::parse(&value).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1)
+                });
+                if !rule_given {
+                    options.pane_rules.clear();
+                    rule_given = true;
+                }
+                options.pane_rules.push(options.rule.clone());
+                options.schedule = None;
+            }
+            "--schedule" => {
+                let value: String = parse_value(&mut args, &arg);
+                let schedule: Schedule = value.parse().unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1)
+                });
+                options.rule = schedule.entries()[0].1.clone();
+                options.schedule = Some(schedule);
+            }
+            "--screensaver" => {
+                options.screensaver = true;
+            }
+            "--screensaver-rules" => {
+                let value: String = parse_value(&mut args, &arg);
+                options.screensaver_rules = parse_rules(&value).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1)
+                });
+            }
+            "--on-finish" => {
+                options.on_finish = parse_value(&mut args, &arg);
+            }
+            "--rules" => {
+                options.rules_file = Some(PathBuf::from(parse_value::<String>(&mut args, &arg)));
+            }
+            "--out" => {
+                options.out = Some(PathBuf::from(parse_value::<String>(&mut args, &arg)));
+            }
+            "--threads" => options.threads = Some(parse_value(&mut args, &arg)),
+            "--frame-every" => {
+                options.frame_every = parse_value(&mut args, &arg);
+            }
+            "--scale" => {
+                options.scale = parse_value(&mut args, &arg);
+            }
+            "--frame-delay" => {
+                options.frame_delay = parse_value(&mut args, &arg);
+            }
+            "--bind" => {
+                let spec: String = parse_value(&mut args, &arg);
+                if let Err(e) = options.keys.bind_spec(&spec) {
+                    eprintln!("{}", e);
+                    std::process::exit(1)
+                }
+            }
+            "--split" => {
+                let value: String = parse_value(&mut args, &arg);
+                options.split = Some(
+                    parse_size(&value)
+                        .filter(|&(columns, rows)| columns > 0 && rows > 0)
+                        .unwrap_or_else(|| {
+                            eprintln!("Expected a layout like 2x2, got {}", value);
+                            std::process::exit(1)
+                        }),
+                );
+            }
+            "--size" => {
+                let value: String = parse_value(&mut args, &arg);
+                options.size = Some(parse_size(&value).unwrap_or_else(|| {
+                    eprintln!("Expected a size like 100x80, got {}", value);
+                    std::process::exit(1)
+                }));
+            }
+            _ => {}
+        }
+    }
+
+    if dump_config {
+        print!("{}", config::dump(&options));
+        return;
+    }
+
+    if options.session.is_some() {
+        if options.command != Command::Run || options.split.is_some() {
+            eprintln!("--session only works for a single run in the terminal");
+            std::process::exit(1)
+        }
+        let session = Session::open(options.session.as_deref()).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1)
+        });
+        // Picks up where the session quit, unless told to load something else.
+        if options.load.is_none() {
+            options.load = session.last();
+        }
+    }
+
+    if options.schedule.is_some()
+        && (options.split.is_some() || options.screensaver || options.command == Command::Sweep)
+    {
+        eprintln!("--schedule can't be used with --split, --screensaver or sweep");
+        std::process::exit(1)
+    }
+
+    if options.detect_cycles && (options.split.is_some() || options.command == Command::Sweep) {
+        eprintln!("--detect-cycles can't be used with --split or sweep");
+        std::process::exit(1)
+    }
+
+    // Rules that can't share a grid are caught before the terminal is taken over.
+    let checked = match options.split {
+        Some(layout) => split::check(layout, &options),
+        None if options.detect_cycles => {
+            setup(1, 1, &options).and_then(|mut sim| Cycles::watch(&mut sim).map(|_| ()))
+        }
+        None => setup(1, 1, &options).map(|_| ()),
+    };
+    let checked = if options.screensaver {
+        checked.and_then(|_| screensaver::check(&options))
+    } else {
+        checked
+    };
+    if let Err(e) = checked {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    }
+
+    match options.command {
+        Command::Run => {}
+        Command::Render => return render(&options),
+        Command::Bench => return bench(&options),
+        Command::Verify => return verify(&options),
+        Command::Sweep => return sweep(&options),
+        Command::Window => return window(&options),
+    }
+
+    // Nothing can be drawn on a pipe, or on a terminal without a known type. Events written to
+    // stdout move the grid to the terminal instead.
+    let piped = !io::stdout().is_terminal() && options.emit_steps != Some(PathBuf::from("-"));
+    let unknown =
+        cfg!(unix) && env::var("TERM").map_or(true, |term| term.is_empty() || term == "dumb");
+    if piped || unknown {
+        options.backend = "dumb".to_string();
+    }
+    let dumb = options.backend == "dumb";
+    if dumb {
+        if options.edit {
+            eprintln!("--edit needs keys, which aren't read without a terminal");
+            std::process::exit(1)
+        }
+        // Nobody is there to quit a frozen run.
+        if options.on_finish == Finish::Freeze {
+            options.on_finish = Finish::Quit;
+        }
+    }
+
+    // Images are written as raw escape sequences, which only the ANSI backend passes through.
+    let pixels = if options.pixels && !dumb && backend::NAMES.contains(&"ansi") {
+        Protocol::detect()
+    } else {
+        None
+    };
+    let renderer = match pixels {
+        Some(protocol) => {
+            options.backend = "ansi".to_string();
+            Renderer::Pixels(protocol)
+        }
+        None if options.braille => Renderer::Braille,
+        None => Renderer::Cells,
+    };
+    if options.ascii.unwrap_or_else(theme::needs_ascii) {
+        if renderer == Renderer::Braille {
+            eprintln!("--braille draws Unicode, pass --no-ascii if the terminal can show it");
+            std::process::exit(1)
+        }
+        options.theme.use_ascii(&options.ascii_cells);
+        if options.ant_glyphs == theme::UNICODE.ants {
+            options.ant_glyphs = theme::ASCII.ants;
+        }
+    }
+    if options.edit && renderer != Renderer::Cells {
+        eprintln!("--edit only works with the default renderer");
+        std::process::exit(1)
+    }
+    if options.trace.is_some() {
+        eprintln!("--trace only works with render, bench or verify");
+        std::process::exit(1)
+    }
+    if options.zoom == 0 {
+        eprintln!("Zoom must be at least 1.");
+        std::process::exit(1)
+    }
+    if options.zoom > 1 && renderer != Renderer::Cells {
+        eprintln!("--zoom only works with the default renderer");
+        std::process::exit(1)
+    }
+    if options.edit && options.zoom > 1 {
+        eprintln!("--edit only works without --zoom");
+        std::process::exit(1)
+    }
+    if options.fade > 0 && renderer != Renderer::Cells {
+        eprintln!("--fade only works with the default renderer");
+        std::process::exit(1)
+    }
+
+    // Opened first, stdout has to be moved off the terminal before a backend takes it.
+    let events = options.emit_steps.as_ref().map(|path| {
+        Emitter::open(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1)
+        })
+    });
+    // Created first, so a failure lands on the normal screen.
+    let cast = options.asciicast.as_ref().map(|path| {
+        let file = File::create(path).unwrap_or_else(|e| {
+            eprintln!("Could not create {}: {}", path.display(), e);
+            std::process::exit(1)
+        });
+        (path, file)
+    });
+    let mut window = backend::open(&options.backend).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    });
+    if let Some((path, file)) = cast {
+        window = Box::new(Cast::new(window, path, file));
+    }
+    window.set_colors(options.theme.screen);
+    window.clear();
+
+    if let Some(layout) = options.split {
+        let panes = split::run(window, renderer, layout, &options).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1)
+        });
+        for (i, (rule, stats)) in panes.iter().enumerate() {
+            if !options.stats_json {
+                if i > 0 {
+                    println!();
+                }
+                println!("rule:         {}", rule);
+            }
+            print_stats(stats, &options);
+        }
+        return;
+    }
 
-    init(
-        columns as u16,
-        rows as u16,
-        window,
-        delay,
-        show_path,
-        show_counter,
-    );
+    // The window is closed once `init` returns, so the summary lands on the normal screen.
+    let stats = init(window, renderer, events, &options).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    });
+    print_stats(&stats, &options);
 }
 
-const HELP: &'static str = r#"
+const HELP: &str = r#"
 langtons_ant: Simple terminal implementation of Langton's ant
+usage:
+    langtons_ant [flags]              ~ Watch the ant in the terminal
+    langtons_ant render --gif F       ~ Run without a terminal and write an animated GIF
+    langtons_ant bench                ~ Step as fast as possible and report the speed
+    langtons_ant verify               ~ Run without a terminal and print a hash of the grid
+                                        and ants at the end, failing unless --expect-hash
+    langtons_ant sweep --rules F --out D
+                                      ~ Run every rule in file F, a rule per line, on
+                                        several threads and write an image and the stats
+                                        of each into directory D
+    langtons_ant window               ~ Watch the ant in an X11 window, a pixel per cell,
+                                        with the gui feature. z and Z zoom, the arrows pan
+    langtons_ant list-presets         ~ List the presets --preset can pick
 flags:
     -h | --help        ~ This help page.
+    --config FILE      ~ Read defaults from FILE instead of ~/.config/langtons_ant/config.toml
+    --dump-config      ~ Print the effective settings in config file form and exit
     -p | --path        ~ Show path
-    -d | --delay       ~ Delay between steps in milliseconds, defaults to 20
+    --speed N          ~ Steps per second, 0 for as fast as possible, defaults to 50
+    -d | --delay       ~ Delay between steps in milliseconds instead of --speed
+    --slowmo-on EVENTS ~ Drop to 20 steps/s for a few seconds when one of the events, separated
+                         by commas, comes up: highway, once the first ant builds one, edge, an
+                         ant near the edge of the grid, collision, ants on the same cell
     -c | --no-counter  ~ Hide step counter
+    --stats-json       ~ Print the exit summary as JSON
+    --random-fill D    ~ Start with a fraction D (0-1) of the cells white
+    --seed N           ~ Seed for random setups, defaults to the clock
+    --start X,Y        ~ Column and row the ant starts at, or random, defaults to the center
+    --heading DIR      ~ Initial heading: up, down, left or right (default)
+    --rule RULE        ~ L or R for each cell color, starting with black, defaults to RL,
+                         Langton's ant. Longer rules like LLRR paint cells in more colors.
+                         N goes straight on, U turns around and ^ v < > head that way
+                         ENGINE:ARGS picks another engine, like turmite:1L1-1L1/1R1-0N0,
+                         or scripted:FILE with the script feature, see src/script.rs
+    --schedule S       ~ Switch rules at given steps, like 0:LR,50000:LLRR,100000:RLR. Ants
+                         without a rule of their own follow it, saves keep it
+    --ant X,Y,DIR[,RULE[,COLOR]]
+                       ~ Start with an ant here instead of --start, following its own rule
+                         and drawn in COLOR (#rrggbb or a name like red) in images. Repeat
+                         for more ants
+    --symmetry N       ~ Add copies of every starting ant, mirrored and turned around the
+                         center: 2 (half turn), 4 (mirrored into each quadrant) or 8 (every
+                         quarter turn and mirror image)
+    --preset NAME      ~ Take the rule, pattern, grid size and speed from a named setup like
+                         fibonacci-spiral or binary-counter. Flags after it override it
+    --pattern FILE     ~ Load an RLE or plain text (.#) pattern onto the grid. X in plain
+                         text is a wall
+    --noise P          ~ Make every ant turn the wrong way on a step with probability P (0-1),
+                         drawn from --seed
+    --on-wall WHAT     ~ What an ant does instead of walking into a wall: bounce (default),
+                         turning around, or turn right
+    --boundary WHAT    ~ What happens when an ant reaches the edge of the grid: end (default),
+                         ending the run, or grow, doubling the grid on that side
+    --background WHAT  ~ What the grid does by itself: none (default), or life, a generation
+                         of Conway's Game of Life with painted cells alive. Experimental, no
+                         stepping back
+    --life-every K     ~ Steps between two generations of --background life, defaults to 100
+    --collision WHAT   ~ What ants ending a step on the same cell do: sequential (default),
+                         each painting it in turn, swap, trading headings afterwards,
+                         annihilate, all of them vanishing, or merge into the first one.
+                         Anything but sequential can't step back
+    --pattern-at X,Y   ~ Place the pattern's top left corner here instead of centering it
+    --script FILE      ~ Decide turns and colors with the decide_turn and on_step hooks in
+                         FILE, see src/script.rs. Needs the script feature, no stepping back
+    --export-on-exit F ~ Write the final grid to F (.txt, .pbm or .png) and the ant to F.json
+    --svg-path FILE    ~ Write the ant's trajectory over the final grid as SVG
+    --backend NAME     ~ Terminal backend: curses (default) or ansi, which needs no ncurses
+    --dumb-tty         ~ Print the screen as text every second instead of drawing on the
+                         terminal, COLUMNS by LINES characters, ending the run once it is over.
+                         Also used when stdout isn't a terminal or TERM is unset or dumb
+    --pixels           ~ Draw the grid as a sixel or kitty image when the terminal supports it,
+                         --scale pixels per cell
+    --braille          ~ Draw 2x4 cells per character with braille patterns
+    --ascii            ~ Draw nothing but ASCII, as is done anyway when the locale isn't UTF-8
+                         or TERM is an old VT terminal. --no-ascii draws Unicode regardless
+    --ascii-cells CHARS
+                       ~ Characters for cells in ASCII from black on, defaults to .#@%
+    --zoom N           ~ Start with each character showing an NxN block of cells, making the
+                         grid N times the terminal's size
+    --zoom-blend HOW   ~ Color of a block: majority (default), its most common color, or max,
+                         its highest color
+    --autofit          ~ Keep every visited cell in view, zooming out as the pattern grows.
+                         Start with --zoom N for a grid N times the terminal's size
+    --control-socket A ~ Take commands like pause or step N on port or Unix socket path A
+    --emit-steps FILE  ~ Write a JSON line for every ant and step to FILE, - for stdout
+    --asciicast FILE   ~ Record the screen to FILE as an asciinema recording, to play back
+                         with asciinema play FILE
+    --fade N           ~ Shade changed cells, fading back to their color over N steps
+    --detect-cycles    ~ Watch for the grid and ants coming back to a state they were in, and
+                         show the period and the step the cycle starts at, also in render,
+                         bench and verify. Not with --noise, --script or --schedule
+    --checkpoint-every N
+                       ~ Save the run every N steps, keeping the last two, also in bench
+    --checkpoint-dir D ~ Where checkpoints go, defaults to checkpoints
+    --screenshot-dir D ~ Where the screenshot key writes, defaults to screenshots
+    --load PATH        ~ Carry on from a checkpoint, or the newest one in directory PATH.
+                         The grid size, rules and ants come from the checkpoint
+    --session NAME     ~ Carry on from where session NAME was quit, and save it on the way
+                         out. Kept in ~/.config/langtons_ant/sessions/NAME with its own
+                         quick save slots
+    --split CxR        ~ Tile the terminal into C by R panes stepping together, each with
+                         its own grid. Pane N follows the Nth --rule, or the last one
+    --ant-glyphs UDLR  ~ Characters to draw ants heading up, down, left and right with,
+                         defaults to ▲▼◀▶, or ^v<> in ASCII
+    --theme THEME      ~ Glyphs and colors of cells, walls and ants: classic (default),
+                         solarized, grayscale, high-contrast, or a theme file, see
+                         src/theme.rs. 24-bit colors if COLORTERM says so, else 256
+    --edit             ~ Start paused in edit mode
+    --bind ACTION=KEY  ~ Bind KEY (a character, space, an arrow name or f1 to f12) to ACTION
+    --steps N          ~ Steps to run for, defaults to 11000 when rendering, verifying or
+                         sweeping, 100000000 when benchmarking and no limit otherwise
+    --duration TIME    ~ Run for a time like 30s, 500ms, 5m or 1h instead of a number of
+                         steps, also in bench. Whichever of this and --steps ends first counts
+    --on-finish WHAT   ~ Once the steps or time are up or the ant walks off the grid: freeze the
+                         display, quit (default) or loop from the start
+    --screensaver      ~ Instead of --on-finish, start over on a blank grid with a random rule
+                         and seed. Any key quits
+    --screensaver-rules R1,R2,...
+                       ~ Rules the screensaver picks from, defaults to a few favorites like
+                         RL, LLRR and LRRRRRLLR
+render flags:
+    --gif FILE         ~ Animation to write
+    --frame-every K    ~ Steps between frames, defaults to 100
+    --scale S          ~ Pixels per cell, defaults to 4
+    --frame-delay CS   ~ Time per frame in hundredths of a second, defaults to 4
+    --size WxH         ~ Grid size, defaults to 100x100, 4096x4096 for bench and 512x512 for
+                         window
+headless flags:
+    --trace FILE       ~ Write step, ant, x, y, heading and the color of the ant's cell for
+                         every step and ant to FILE as CSV, gzipped if it ends in .gz
+sweep flags:
+    --rules FILE       ~ Rules to run, one per line, # starting a comment line
+    --out DIR          ~ Where rule-N.png and rule-N.json go for the rule on line N
+    --threads N        ~ Rules run at once, defaults to the number of cores
+verify flags:
+    --expect-hash H    ~ Hash in hex the run has to end with, as printed without this flag
+keys:
+    q                  ~ quit
+    space              ~ pause
+    .                  ~ step, one step at a time while paused
+    ,                  ~ step-back
+    u                  ~ rewind continuously until pressed again or back at the start
+    R                  ~ reverse the direction of time, also once the run is over, undoing
+                         every turn and painted cell back to the start
+    + -                ~ faster, slower
+    arrows             ~ pan-up, pan-down, pan-left, pan-right
+    z Z                ~ zoom-in, zoom-out by one cell per character
+    b                  ~ autofit, keep the visited cells in view until pressed again
+    x                  ~ export the grid, numbered by step, next to --export-on-exit. This
+                         was e until edit mode took it, --bind export=e --bind edit=E puts it
+                         back
+    e                  ~ edit, while paused. Pressing it again resumes the run, and stepping
+                         back stops where the run was edited
+    g                  ~ graph of the speed and of the time each frame takes, to tune --speed
+    h                  ~ histogram of the cells of each color and how often cells were
+                         painted it, at the right edge
+    p                  ~ screenshot of the cells in view as text and PNG, into --screenshot-dir
+    F1-F4              ~ save-1 to save-4, the run to a quick save slot of the --session
+    F5-F8              ~ load-1 to load-4, carrying on from a quick save slot
+    click              ~ Toggle a cell. Stepping back stops here, it can't undo this
+    shift/right click  ~ Drop a new ant facing --heading
+edit mode:
+    arrows             ~ Move the cursor
+    space              ~ Flip the cell under the cursor
+    w                  ~ Put a wall on the cell under the cursor, or take it away
+    a                  ~ Add an ant, or turn the one under the cursor clockwise
+    d                  ~ Remove the ant under the cursor, unless it is the last one
 "#;
\ No newline at end of file
@@ -0,0 +1,110 @@
+//! Recent speed and frame times, shown as sparklines by the graph overlay.
+
+use std::time::Duration;
+
+/// Number of samples kept, one per character of a sparkline.
+pub const SAMPLES: usize = 40;
+
+/// The last values pushed, up to a fixed number.
+pub struct Ring {
+    values: Vec<f64>,
+    /// Where the next value goes once the ring is full, which is also the oldest one.
+    next: usize,
+    capacity: usize,
+}
+
+impl Ring {
+    pub fn new(capacity: usize) -> Ring {
+        Ring {
+            values: Vec::with_capacity(capacity),
+            next: 0,
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Add `value`, dropping the oldest one if the ring is full.
+    pub fn push(&mut self, value: f64) {
+        if self.values.len() < self.capacity {
+            self.values.push(value);
+        } else {
+            self.values[self.next] = value;
+            self.next = (self.next + 1) % self.capacity;
+        }
+    }
+
+    /// The values, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = f64> + '_ {
+        let (newer, older) = self.values.split_at(self.next);
+        older.iter().chain(newer).cloned()
+    }
+
+    /// The most recent value, 0 before the first.
+    pub fn last(&self) -> f64 {
+        match self.next {
+            0 => self.values.last().cloned().unwrap_or(0.0),
+            next => self.values[next - 1],
+        }
+    }
+
+    /// The highest value, 0 before the first.
+    pub fn max(&self) -> f64 {
+        self.iter().fold(0.0, f64::max)
+    }
+
+    /// One of `bars` per value, from the lowest to the highest, scaled so the highest value
+    /// reaches the top.
+    pub fn sparkline(&self, bars: &[char]) -> String {
+        let max = self.max();
+        self.iter()
+            .map(|value| {
+                let bar = if max > 0.0 {
+                    (value / max * (bars.len() - 1) as f64).round() as usize
+                } else {
+                    0
+                };
+                bars[bar.min(bars.len() - 1)]
+            })
+            .collect()
+    }
+}
+
+/// Steps per second and time per frame, averaged over each measurement of the speed.
+pub struct Metrics {
+    /// Steps per second of each measurement.
+    pub rates: Ring,
+    /// Average milliseconds a frame took, steps and drawing together, over each measurement.
+    pub frame_ms: Ring,
+    /// Frames since the last measurement, and the time they took.
+    frames: u32,
+    frame_time: Duration,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics {
+            rates: Ring::new(SAMPLES),
+            frame_ms: Ring::new(SAMPLES),
+            frames: 0,
+            frame_time: Duration::from_secs(0),
+        }
+    }
+
+    /// Count a frame that took `time`.
+    pub fn frame(&mut self, time: Duration) {
+        self.frames += 1;
+        self.frame_time += time;
+    }
+
+    /// Record a new measurement of `rate` steps per second, along with the frames since the
+    /// last one.
+    pub fn sample(&mut self, rate: f64) {
+        self.rates.push(rate);
+        let ms = match self.frames {
+            0 => 0.0,
+            frames => self.frame_time.as_secs_f64() * 1000.0 / f64::from(frames),
+        };
+        self.frame_ms.push(ms);
+        self.frames = 0;
+        self.frame_time = Duration::from_secs(0);
+    }
+}
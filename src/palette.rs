@@ -0,0 +1,118 @@
+//! Colors of cells and ants in the image outputs.
+
+use simulation::{Cell, Simulation, State};
+
+/// RGB of each cell color. Black cells are drawn white and white cells black, as in the
+/// terminal; the colors of longer rules follow.
+const CELLS: [[u8; 3]; 12] = [
+    [0xFF, 0xFF, 0xFF],
+    [0x00, 0x00, 0x00],
+    [0x1F, 0x77, 0xB4],
+    [0xFF, 0x7F, 0x0E],
+    [0x2C, 0xA0, 0x2C],
+    [0x94, 0x67, 0xBD],
+    [0x8C, 0x56, 0x4B],
+    [0xE3, 0x77, 0xC2],
+    [0x7F, 0x7F, 0x7F],
+    [0xBC, 0xBD, 0x22],
+    [0x17, 0xBE, 0xCF],
+    [0xFF, 0xD7, 0x00],
+];
+
+/// RGB of walls.
+pub const WALL: [u8; 3] = [0x40, 0x40, 0x40];
+
+/// RGB of the ants that weren't given a color, by index.
+const ANTS: [[u8; 3]; 6] = [
+    [0xE0, 0x20, 0x20],
+    [0x20, 0x60, 0xE0],
+    [0x20, 0xA0, 0x40],
+    [0xE0, 0x90, 0x10],
+    [0xA0, 0x30, 0xC0],
+    [0x10, 0xB0, 0xB0],
+];
+
+/// Colors understood by `parse` besides `#rrggbb`.
+const NAMES: &[(&str, [u8; 3])] = &[
+    ("red", [0xE0, 0x20, 0x20]),
+    ("blue", [0x20, 0x60, 0xE0]),
+    ("green", [0x20, 0xA0, 0x40]),
+    ("orange", [0xE0, 0x90, 0x10]),
+    ("purple", [0xA0, 0x30, 0xC0]),
+    ("cyan", [0x10, 0xB0, 0xB0]),
+    ("yellow", [0xF0, 0xD0, 0x20]),
+    ("magenta", [0xD0, 0x20, 0xA0]),
+    ("black", [0x00, 0x00, 0x00]),
+    ("white", [0xFF, 0xFF, 0xFF]),
+];
+
+/// RGB of a cell in `state`. Past the twelfth color they repeat.
+pub fn cell(state: State) -> [u8; 3] {
+    CELLS[state.0 as usize % CELLS.len()]
+}
+
+/// RGB of ant `i` of `sim`.
+pub fn ant(sim: &Simulation, i: usize) -> [u8; 3] {
+    sim.ants[i].color.unwrap_or(ANTS[i % ANTS.len()])
+}
+
+/// RGB of every cell color of `sim`, in order.
+pub fn cells(sim: &Simulation) -> Vec<[u8; 3]> {
+    (0..sim.colors()).map(|color| cell(State(color))).collect()
+}
+
+/// The palette for indexed images of `sim`: every cell color, walls, then every ant.
+///
+/// There are at most 256 entries, ants past that share the last one.
+pub fn indexed(sim: &Simulation) -> Vec<[u8; 3]> {
+    let mut colors = cells(sim);
+    colors.push(WALL);
+    colors.extend((0..sim.ants.len()).map(|i| ant(sim, i)));
+    colors.truncate(256);
+    colors
+}
+
+/// Index of `cell` in the `indexed` palette.
+pub fn cell_index(sim: &Simulation, cell: &Cell) -> u8 {
+    if cell.wall {
+        sim.colors()
+    } else {
+        cell.state.0
+    }
+}
+
+/// Index of ant `i` in the `indexed` palette.
+pub fn ant_index(sim: &Simulation, i: usize) -> u8 {
+    (usize::from(sim.colors()) + 1 + i).min(255) as u8
+}
+
+/// Palette index of every cell of `sim`, row by row, with the ants drawn on top.
+pub fn indices(sim: &Simulation) -> Vec<u8> {
+    let mut indices: Vec<u8> = sim.grid.iter().map(|cell| cell_index(sim, cell)).collect();
+    for (i, ant) in sim.ants.iter().enumerate() {
+        indices[ant.y as usize * sim.width() + ant.x as usize] = ant_index(sim, i);
+    }
+    indices
+}
+
+/// Parse a color written as `#rrggbb` or one of a few names like `red`.
+pub fn parse(text: &str) -> Result<[u8; 3], String> {
+    if let Some(&(_, rgb)) = NAMES.iter().find(|&&(name, _)| name == text) {
+        return Ok(rgb);
+    }
+    let error = || format!("Expected a color like #e02020 or red, got {}", text);
+    let hex = text.strip_prefix('#').ok_or_else(error)?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(error());
+    }
+    let mut rgb = [0; 3];
+    for (i, channel) in rgb.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| error())?;
+    }
+    Ok(rgb)
+}
+
+/// `rgb` as `#rrggbb`, which `parse` reads back.
+pub fn hex(rgb: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2])
+}
@@ -0,0 +1,136 @@
+use std::fs;
+use std::path::Path;
+
+/// What a pattern puts on a cell.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mark {
+    Black,
+    White,
+    Wall,
+}
+
+/// A rectangular bitmap of cells to stamp onto the grid before the run starts.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    /// Width in cells.
+    pub width: usize,
+    /// Height in cells.
+    pub height: usize,
+    /// Row-major cells.
+    cells: Vec<Mark>,
+}
+
+impl Pattern {
+    /// Read a pattern from `path`, picking the format from the extension or the RLE header.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Pattern, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        let is_rle = path.extension().is_some_and(|ext| ext == "rle")
+            || text
+                .lines()
+                .find(|line| !line.starts_with('#'))
+                .is_some_and(|line| line.trim_start().starts_with('x'));
+
+        if is_rle {
+            Pattern::parse_rle(&text)
+        } else {
+            Pattern::parse_text(&text)
+        }
+    }
+
+    /// Parse a Golly-style run length encoded pattern.
+    ///
+    /// `b` and `.` are dead cells, any other letter is alive, `$` ends a row and `!` ends the
+    /// pattern. The `x = .., y = ..` header is optional.
+    pub fn parse_rle(text: &str) -> Result<Pattern, String> {
+        let mut rows: Vec<Vec<Mark>> = vec![Vec::new()];
+        let mut count = String::new();
+
+        'lines: for line in text.lines() {
+            let line = line.trim();
+            if line.starts_with('#') || line.starts_with('x') {
+                continue;
+            }
+            for c in line.chars() {
+                let run = if c.is_ascii_digit() {
+                    count.push(c);
+                    continue;
+                } else if count.is_empty() {
+                    1
+                } else {
+                    count.parse::<usize>().map_err(|e| e.to_string())?
+                };
+                count.clear();
+
+                match c {
+                    '!' => break 'lines,
+                    '$' => {
+                        for _ in 0..run {
+                            rows.push(Vec::new());
+                        }
+                    }
+                    'b' | '.' => rows.last_mut().unwrap().extend((0..run).map(|_| Mark::Black)),
+                    c if c.is_ascii_alphabetic() => {
+                        rows.last_mut().unwrap().extend((0..run).map(|_| Mark::White))
+                    }
+                    c if c.is_whitespace() => {}
+                    c => return Err(format!("Unexpected character in RLE: {:?}", c)),
+                }
+            }
+        }
+
+        Ok(Pattern::from_rows(rows))
+    }
+
+    /// Parse a plain text bitmap, one row per line.
+    ///
+    /// `.` is a dead cell, `#`, `O` and `*` are alive and `X` is a wall. Lines starting with `!`
+    /// are comments.
+    pub fn parse_text(text: &str) -> Result<Pattern, String> {
+        let mut rows = Vec::new();
+        for line in text.lines() {
+            if line.starts_with('!') {
+                continue;
+            }
+            let row = line
+                .trim_end()
+                .chars()
+                .map(|c| match c {
+                    '.' | ' ' => Ok(Mark::Black),
+                    '#' | 'O' | '*' => Ok(Mark::White),
+                    'X' => Ok(Mark::Wall),
+                    c => Err(format!("Unexpected character in pattern: {:?}", c)),
+                })
+                .collect::<Result<Vec<Mark>, String>>()?;
+            rows.push(row);
+        }
+
+        Ok(Pattern::from_rows(rows))
+    }
+
+    fn from_rows(mut rows: Vec<Vec<Mark>>) -> Pattern {
+        // Trailing empty rows carry no information.
+        while rows.last().is_some_and(|row| row.is_empty()) {
+            rows.pop();
+        }
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let height = rows.len();
+        let mut cells = Vec::with_capacity(width * height);
+        for mut row in rows {
+            row.resize(width, Mark::Black);
+            cells.extend(row);
+        }
+
+        Pattern {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    /// The cell at column `x`, row `y` of the pattern.
+    pub fn get(&self, x: usize, y: usize) -> Mark {
+        self.cells[y * self.width + x]
+    }
+}
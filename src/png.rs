@@ -0,0 +1,164 @@
+//! A minimal PNG encoder for palette images.
+//!
+//! The image data is written as stored (uncompressed) deflate blocks. Grid snapshots are small,
+//! so the size cost is acceptable and the encoder stays dependency free.
+
+use std::io::{self, Write};
+
+use gzip::Crc32;
+
+/// Write an 8-bit indexed PNG.
+///
+/// `pixels` holds one palette index per pixel, row by row.
+pub fn write<W: Write>(
+    out: &mut W,
+    width: u32,
+    height: u32,
+    palette: &[[u8; 3]],
+    pixels: &[u8],
+) -> io::Result<()> {
+    assert_eq!(pixels.len(), width as usize * height as usize);
+
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, color type 3 (indexed), default compression, filter and no interlacing.
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+    chunk(out, b"IHDR", &header)?;
+
+    let plte: Vec<u8> = palette.iter().flat_map(|rgb| rgb.iter().cloned()).collect();
+    chunk(out, b"PLTE", &plte)?;
+
+    // Every scanline is prefixed with filter type 0.
+    let mut raw = Vec::with_capacity(pixels.len() + height as usize);
+    for row in pixels.chunks(width.max(1) as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    chunk(out, b"IDAT", &zlib_stored(&raw))?;
+
+    chunk(out, b"IEND", &[])
+}
+
+fn chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let mut crc = Crc32::new();
+    crc.update(kind);
+    crc.update(data);
+    out.write_all(&crc.finish().to_be_bytes())
+}
+
+/// Wrap `data` in a zlib stream made of stored deflate blocks.
+pub fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 65535 * 5 + 11);
+    // 32K window, no preset dictionary, fastest compression.
+    out.extend_from_slice(&[0x78, 0x01]);
+    out.extend(deflate_stored(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// Encode `data` as a sequence of stored deflate blocks.
+pub fn deflate_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 65535 * 5 + 5);
+    let mut blocks = data.chunks(65535).peekable();
+    if blocks.peek().is_none() {
+        // An empty stream still needs one final block.
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The chunks of the PNG `bytes`, checked against their CRCs.
+    fn chunks(bytes: &[u8]) -> Vec<([u8; 4], &[u8])> {
+        assert_eq!(bytes[..8], *b"\x89PNG\r\n\x1a\n");
+        let mut chunks = Vec::new();
+        let mut rest = &bytes[8..];
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let kind = [rest[4], rest[5], rest[6], rest[7]];
+            let data = &rest[8..8 + len];
+            let mut crc = Crc32::new();
+            crc.update(&rest[4..8 + len]);
+            assert_eq!(rest[8 + len..12 + len], crc.finish().to_be_bytes());
+            chunks.push((kind, data));
+            rest = &rest[12 + len..];
+        }
+        chunks
+    }
+
+    /// The data in a zlib stream of stored blocks.
+    fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+        assert_eq!(zlib[..2], [0x78, 0x01]);
+        let mut out = Vec::new();
+        let mut rest = &zlib[2..];
+        loop {
+            let last = rest[0] == 1;
+            let len = usize::from(u16::from_le_bytes([rest[1], rest[2]]));
+            assert_eq!(u16::from_le_bytes([rest[3], rest[4]]), !(len as u16));
+            out.extend_from_slice(&rest[5..5 + len]);
+            rest = &rest[5 + len..];
+            if last {
+                break;
+            }
+        }
+        assert_eq!(rest, adler32(&out).to_be_bytes());
+        out
+    }
+
+    #[test]
+    fn round_trips() {
+        // Wide enough that the image data takes more than one stored block.
+        let (width, height) = (300, 250);
+        let pixels: Vec<u8> = (0..width * height).map(|i| (i % 7 + i / 1000) as u8).collect();
+        let palette: Vec<[u8; 3]> = (0..80).map(|i| [i, 255 - i, 3 * i]).collect();
+        let mut out = Vec::new();
+        write(&mut out, width as u32, height as u32, &palette, &pixels).unwrap();
+
+        let chunks = chunks(&out);
+        let kinds: Vec<&[u8]> = chunks.iter().map(|(kind, _)| &kind[..]).collect();
+        assert_eq!(kinds, [&b"IHDR"[..], b"PLTE", b"IDAT", b"IEND"]);
+        let header = chunks[0].1;
+        assert_eq!(header[..8], [0, 0, 1, 44, 0, 0, 0, 250]);
+        assert_eq!(header[8..], [8, 3, 0, 0, 0]);
+        let plte: Vec<u8> = palette.iter().flat_map(|rgb| rgb.iter().cloned()).collect();
+        assert_eq!(chunks[1].1, &plte[..]);
+
+        let raw = inflate_stored(chunks[2].1);
+        let rows: Vec<&[u8]> = raw.chunks(width + 1).collect();
+        assert_eq!(rows.len(), height);
+        for (row, expected) in rows.iter().zip(pixels.chunks(width)) {
+            assert_eq!(row[0], 0);
+            assert_eq!(&row[1..], expected);
+        }
+    }
+}
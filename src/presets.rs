@@ -0,0 +1,144 @@
+//! Named setups worth watching, picked with `--preset`.
+//!
+//! A preset bundles a rule with the pattern the grid starts from and the grid size and speed
+//! that show it off. Everything it sets can still be changed by the flags after it.
+
+use std::sync::Arc;
+
+use pattern::Pattern;
+use rule::{self, Rule};
+
+/// A named setup.
+#[derive(Copy, Clone, Debug)]
+pub struct Preset {
+    pub name: &'static str,
+    /// What there is to see, in one line.
+    pub description: &'static str,
+    /// The rule spec, as given to `--rule`.
+    pub spec: &'static str,
+    /// Plain text pattern stamped onto the middle of the grid, empty for a blank grid.
+    pattern: &'static str,
+    /// Columns and rows of the grid when there is no terminal to size it.
+    pub size: (u16, u16),
+    /// Steps per second.
+    pub speed: f64,
+}
+
+/// Every preset, in the order they are listed.
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "langton",
+        description: "Langton's ant: about 10000 steps of chaos, then it builds a highway",
+        spec: "RL",
+        pattern: "",
+        size: (120, 120),
+        speed: 500.0,
+    },
+    Preset {
+        name: "fibonacci-spiral",
+        description: "A two state turmite growing a spiral with arms in Fibonacci proportions",
+        spec: "turmite:1L1-1L1/1R1-0N0",
+        pattern: "",
+        size: (120, 120),
+        speed: 200.0,
+    },
+    Preset {
+        name: "binary-counter",
+        description: "Counts in binary along a row, ones white and zeros grey, lowest bit right",
+        spec: "turmite:1>1-2<0-1>1/0<0-1>1-2>1",
+        pattern: "",
+        size: (40, 10),
+        speed: 20.0,
+    },
+    Preset {
+        name: "symmetric",
+        description: "LLRR keeps growing a shape that is symmetric about the ant's first move",
+        spec: "LLRR",
+        pattern: "",
+        size: (120, 120),
+        speed: 500.0,
+    },
+    Preset {
+        name: "square",
+        description: "LRRRRRLLR fills a square that grows around the start",
+        spec: "LRRRRRLLR",
+        pattern: "",
+        size: (120, 120),
+        speed: 1000.0,
+    },
+    Preset {
+        name: "convoluted-highway",
+        description: "LLRRRLRLRLLR builds a highway out of a convoluted trail",
+        spec: "LLRRRLRLRLLR",
+        pattern: "",
+        size: (160, 160),
+        speed: 1000.0,
+    },
+    Preset {
+        name: "triangle",
+        description: "RRLLLRLLLRRR pushes a filled triangle across the grid",
+        spec: "RRLLLRLLLRRR",
+        pattern: "",
+        size: (160, 160),
+        speed: 1000.0,
+    },
+    Preset {
+        name: "boxed",
+        description: "Langton's ant shut in a walled room, where the highway has nowhere to go",
+        spec: "RL",
+        pattern: "XXXXXXXXXXXXXXXXXXXXXX
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+X....................X
+XXXXXXXXXXXXXXXXXXXXXX",
+        size: (40, 40),
+        speed: 200.0,
+    },
+];
+
+impl Preset {
+    /// The preset called `name`.
+    pub fn find(name: &str) -> Result<&'static Preset, String> {
+        PRESETS
+            .iter()
+            .find(|preset| preset.name == name)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown preset {}, see list-presets for the ones there are",
+                    name
+                )
+            })
+    }
+
+    /// The rule the preset runs.
+    pub fn rule(&self) -> Result<Arc<dyn Rule>, String> {
+        rule::parse(self.spec).map_err(|e| format!("Preset {}: {}", self.name, e))
+    }
+
+    /// The pattern the grid starts from, if any.
+    pub fn pattern(&self) -> Result<Option<Pattern>, String> {
+        if self.pattern.is_empty() {
+            return Ok(None);
+        }
+        Pattern::parse_text(self.pattern)
+            .map(Some)
+            .map_err(|e| format!("Preset {}: {}", self.name, e))
+    }
+}
@@ -0,0 +1,90 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use gif;
+use palette;
+use simulation::{LeftArena, Simulation};
+
+/// Settings for a headless animation.
+pub struct GifOptions {
+    /// Steps to run for; the run also ends when the ant leaves the grid.
+    pub steps: u64,
+    /// Steps between two frames.
+    pub frame_every: u64,
+    /// Size in pixels of one cell.
+    pub scale: u16,
+    /// How long each frame is shown, in hundredths of a second.
+    pub frame_delay: u16,
+}
+
+/// Run `sim` without a terminal, writing a frame to `path` every `frame_every` steps.
+///
+/// Returns how the ant left the arena if that is what ended the run.
+pub fn gif(
+    sim: &mut Simulation,
+    path: &Path,
+    options: &GifOptions,
+) -> Result<Option<LeftArena>, String> {
+    let scale = options.scale.max(1) as usize;
+    let width = sim.width() * scale;
+    let height = sim.height() * scale;
+    if width > u16::MAX as usize || height > u16::MAX as usize {
+        return Err(format!("A {}x{} image is too big for a GIF", width, height));
+    }
+
+    let error = |e| format!("Could not write {}: {}", path.display(), e);
+    let file = File::create(path).map_err(error)?;
+    let mut encoder = gif::Encoder::new(
+        BufWriter::new(file),
+        width as u16,
+        height as u16,
+        &palette::indexed(sim),
+    )
+    .map_err(error)?;
+
+    let mut pixels = vec![0; width * height];
+    let mut left_arena = None;
+    loop {
+        draw(sim, scale, &mut pixels);
+        encoder
+            .frame(&pixels, options.frame_delay)
+            .map_err(error)?;
+        if left_arena.is_some() || sim.steps >= options.steps {
+            break;
+        }
+
+        for _ in 0..options.frame_every.max(1) {
+            if sim.steps >= options.steps {
+                break;
+            }
+            if let Err(left) = sim.step() {
+                left_arena = Some(left);
+                break;
+            }
+        }
+    }
+
+    encoder.finish().map(|_| left_arena).map_err(error)
+}
+
+/// Paint the grid into `pixels`, `scale` pixels per cell.
+fn draw(sim: &Simulation, scale: usize, pixels: &mut [u8]) {
+    let width = sim.width() * scale;
+    let mut paint = |x: usize, y: usize, color: u8| {
+        for py in y * scale..(y + 1) * scale {
+            let start = py * width + x * scale;
+            for pixel in &mut pixels[start..start + scale] {
+                *pixel = color;
+            }
+        }
+    };
+    for (y, row) in sim.rows().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            paint(x, y, palette::cell_index(sim, cell));
+        }
+    }
+    for (i, ant) in sim.ants.iter().enumerate() {
+        paint(ant.x as usize, ant.y as usize, palette::ant_index(sim, i));
+    }
+}
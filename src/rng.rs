@@ -0,0 +1,65 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A small, seedable xorshift64* generator.
+///
+/// Runs only need to be reproducible from a `--seed`, not cryptographically strong, so this
+/// avoids pulling in a dependency.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // Run the seed through splitmix64 so that small seeds still give a well mixed state, and
+        // so that zero (a fixed point of xorshift) can't be reached.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Rng {
+            state: if z == 0 { 1 } else { z },
+        }
+    }
+
+    /// A generator continuing from `state`, as returned by `state`.
+    pub fn from_state(state: u64) -> Rng {
+        Rng {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    /// Where the generator is in its sequence, to save it.
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    /// A seed derived from the current time, for runs without `--seed`.
+    pub fn time_seed() -> u64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        now.as_secs() ^ (u64::from(now.subsec_nanos()) << 32)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A uniformly distributed float in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A uniformly distributed integer in `[0, n)`.
+    pub fn below(&mut self, n: u64) -> u64 {
+        if n == 0 {
+            0
+        } else {
+            self.next_u64() % n
+        }
+    }
+}
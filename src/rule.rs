@@ -0,0 +1,666 @@
+//! How ants decide what to do, and the engines rules can be written for.
+//!
+//! Any type implementing `Rule` can drive ants. A `Registry` maps engine names to parsers, so
+//! rules are picked at runtime with specs like `turmite:1L1-1L1/1R1-0N0`. Without a name a spec
+//! is read by the generalized engine, one letter per color like `LLRR`. Engines added with
+//! `register` are known wherever a spec is read: flags, snapshots and schedules alike.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use simulation::Direction;
+
+/// Which way an ant turns after landing on a cell.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Turn {
+    Left,
+    Right,
+    /// Keep going the same way.
+    Straight,
+    /// Turn around.
+    Back,
+    /// Head this way, whichever way the ant was heading.
+    Face(Direction),
+}
+
+impl Turn {
+    /// The turn the other way. Turns that don't depend on the heading stay as they are.
+    pub fn mirrored(self) -> Turn {
+        match self {
+            Turn::Left => Turn::Right,
+            Turn::Right => Turn::Left,
+            other => other,
+        }
+    }
+
+    /// Whether the turn is relative to the heading, so the heading before it can be told from
+    /// the one after.
+    pub fn is_relative(self) -> bool {
+        !matches!(self, Turn::Face(_))
+    }
+
+    /// The heading before turning to `heading`, `None` for a move by compass direction.
+    pub fn undo(self, heading: Direction) -> Option<Direction> {
+        if self.is_relative() {
+            Some(heading.apply(self.mirrored()))
+        } else {
+            None
+        }
+    }
+
+    /// The letter the turn is written as: `L`, `R`, `N` for no turn, `U` for a U-turn, or one of
+    /// `^`, `v`, `<` and `>` to head up, down, left or right.
+    pub fn letter(self) -> char {
+        match self {
+            Turn::Left => 'L',
+            Turn::Right => 'R',
+            Turn::Straight => 'N',
+            Turn::Back => 'U',
+            Turn::Face(Direction::Up) => '^',
+            Turn::Face(Direction::Down) => 'v',
+            Turn::Face(Direction::Left) => '<',
+            Turn::Face(Direction::Right) => '>',
+        }
+    }
+
+    /// The turn written as `c`, letters in either case.
+    pub fn from_letter(c: char) -> Option<Turn> {
+        match c.to_ascii_uppercase() {
+            'L' => Some(Turn::Left),
+            'R' => Some(Turn::Right),
+            'N' => Some(Turn::Straight),
+            'U' => Some(Turn::Back),
+            '^' => Some(Turn::Face(Direction::Up)),
+            'V' => Some(Turn::Face(Direction::Down)),
+            '<' => Some(Turn::Face(Direction::Left)),
+            '>' => Some(Turn::Face(Direction::Right)),
+            _ => None,
+        }
+    }
+}
+
+/// What an ant does on a cell.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transition {
+    /// The color to paint the cell. Colors past the last one on the grid wrap around to black.
+    pub color: u8,
+    /// Which way to turn.
+    pub turn: Turn,
+    /// The state the ant is in afterwards.
+    pub state: u8,
+}
+
+/// Something deciding what ants do.
+///
+/// Ants carry a state of their own, always 0 for rules with a single state. A grid shared by
+/// rules with different numbers of colors has as many colors as all of them need, rules see
+/// those colors starting over at their own number of colors.
+pub trait Rule: fmt::Debug + fmt::Display + Send + Sync {
+    /// What an ant in `ant_state` does on a cell of color `cell`.
+    fn transition(&self, cell: u8, ant_state: u8) -> Transition;
+
+    /// Number of cell colors the rule tells apart.
+    fn colors(&self) -> u8;
+
+    /// Number of states an ant following the rule can be in.
+    fn states(&self) -> u8 {
+        1
+    }
+
+    /// The cell color and ant state before a transition, given the color the cell got on a grid
+    /// with `colors` colors and the state the ant went to. `None` when there isn't exactly one.
+    ///
+    /// This is what stepping back uses, the default tries every color and state.
+    fn undo(&self, color: u8, ant_state: u8, colors: u8) -> Option<(u8, u8)> {
+        let mut found = None;
+        for cell in 0..colors {
+            for state in 0..self.states() {
+                let t = self.transition(cell, state);
+                if t.state == ant_state && u16::from(t.color) % u16::from(colors) == u16::from(color)
+                {
+                    if found.is_some() {
+                        return None;
+                    }
+                    found = Some((cell, state));
+                }
+            }
+        }
+        found
+    }
+
+    /// The rule of a mirror image: every left turn made right and the other way around. `None`
+    /// if the rule has no such thing.
+    fn mirrored(&self) -> Option<Arc<dyn Rule>> {
+        None
+    }
+
+    /// Whether every turn is relative to the ant's heading. Ants that move by compass direction
+    /// can't be stepped back, and turned copies of them don't stay copies.
+    fn is_relative(&self) -> bool {
+        (0..self.states()).all(|state| {
+            (0..self.colors()).all(|cell| self.transition(cell, state).turn.is_relative())
+        })
+    }
+}
+
+/// The turn an ant makes on each cell color, written as one letter per color.
+///
+/// Colors are counted from the one a fresh grid starts with, so Langton's ant is `RL`: turn
+/// right on a black cell and left on a white one. Longer rules such as `LLRR` paint cells
+/// through more colors before they come back to black. Besides `L` and `R` there are `N` and
+/// `U` for going straight on and turning around, and `^v<>` for heading up, down, left or right
+/// as in Golly's absolute turmites.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Letters {
+    turns: Vec<Turn>,
+}
+
+impl Letters {
+    /// The turn made on a cell of color `cell`.
+    ///
+    /// Grids shared with longer rules have more colors than this rule has letters, the letters
+    /// repeat for those.
+    pub fn turn(&self, cell: u8) -> Turn {
+        self.turns[cell as usize % self.turns.len()]
+    }
+}
+
+impl Default for Letters {
+    fn default() -> Letters {
+        Letters {
+            turns: vec![Turn::Right, Turn::Left],
+        }
+    }
+}
+
+impl Rule for Letters {
+    fn transition(&self, cell: u8, _: u8) -> Transition {
+        Transition {
+            // Always the next color, so the cell goes through every color of a shared grid.
+            color: cell.wrapping_add(1),
+            turn: self.turn(cell),
+            state: 0,
+        }
+    }
+
+    fn colors(&self) -> u8 {
+        self.turns.len() as u8
+    }
+
+    fn undo(&self, color: u8, _: u8, colors: u8) -> Option<(u8, u8)> {
+        let previous = (u16::from(color) + u16::from(colors) - 1) % u16::from(colors);
+        Some((previous as u8, 0))
+    }
+
+    fn mirrored(&self) -> Option<Arc<dyn Rule>> {
+        if !self.is_relative() {
+            return None;
+        }
+        Some(Arc::new(Letters {
+            turns: self.turns.iter().map(|turn| turn.mirrored()).collect(),
+        }))
+    }
+
+    fn is_relative(&self) -> bool {
+        self.turns.iter().all(|turn| turn.is_relative())
+    }
+}
+
+impl FromStr for Letters {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Letters, String> {
+        let turns = s
+            .chars()
+            .map(|c| {
+                Turn::from_letter(c).ok_or_else(|| {
+                    format!(
+                        "Unknown turn {:?} in rule {}, expected L, R, N, U, ^, v, < or >",
+                        c, s
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // With a single color nothing ever changes.
+        if turns.len() < 2 || turns.len() > usize::from(u8::MAX) {
+            return Err(format!(
+                "A rule needs one letter for each of 2 to {} cell colors, got {}",
+                u8::MAX,
+                s
+            ));
+        }
+        Ok(Letters { turns })
+    }
+}
+
+impl fmt::Display for Letters {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for turn in &self.turns {
+            write!(f, "{}", turn.letter())?;
+        }
+        Ok(())
+    }
+}
+
+/// A Turing machine on the grid: ants have states, and each state has its own transition for
+/// every color.
+///
+/// Written `turmite:` and then the states separated by `/`, each one listing a transition per
+/// color separated by `-`. A transition is the color to paint, a turn letter (`L`, `R`, `N`, `U`
+/// or one of the compass moves `^v<>`) and the next state, so `turmite:1L1-1L1/1R1-0N0` grows a
+/// Fibonacci spiral.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Turmite {
+    /// Row by row, one row of `colors` transitions per state.
+    table: Vec<Transition>,
+    colors: u8,
+}
+
+impl Rule for Turmite {
+    fn transition(&self, cell: u8, ant_state: u8) -> Transition {
+        let colors = usize::from(self.colors);
+        self.table[usize::from(ant_state) * colors + usize::from(cell) % colors]
+    }
+
+    fn colors(&self) -> u8 {
+        self.colors
+    }
+
+    fn states(&self) -> u8 {
+        (self.table.len() / usize::from(self.colors)) as u8
+    }
+
+    fn mirrored(&self) -> Option<Arc<dyn Rule>> {
+        if !self.is_relative() {
+            return None;
+        }
+        let table = self
+            .table
+            .iter()
+            .map(|&t| Transition {
+                turn: t.turn.mirrored(),
+                ..t
+            })
+            .collect();
+        Some(Arc::new(Turmite {
+            table,
+            colors: self.colors,
+        }))
+    }
+}
+
+impl FromStr for Turmite {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Turmite, String> {
+        let rows: Vec<Vec<&str>> = s.split('/').map(|row| row.split('-').collect()).collect();
+        let colors = rows[0].len();
+        let states = rows.len();
+        if colors < 2 || colors > usize::from(u8::MAX) || states > usize::from(u8::MAX) {
+            return Err(format!(
+                "A turmite needs 2 to {} colors and at most {} states, got {}",
+                u8::MAX,
+                u8::MAX,
+                s
+            ));
+        }
+
+        let mut table = Vec::with_capacity(colors * states);
+        for row in &rows {
+            if row.len() != colors {
+                return Err(format!(
+                    "Every state of turmite {} needs a transition for each of {} colors",
+                    s, colors
+                ));
+            }
+            for cell in row {
+                let error = || {
+                    format!(
+                        "Expected a transition like 1L0 (color, turn, state) in turmite {}, got {}",
+                        s, cell
+                    )
+                };
+                let turn_at = cell.find(|c: char| !c.is_ascii_digit()).ok_or_else(error)?;
+                let letter = cell[turn_at..].chars().next().ok_or_else(error)?;
+                let turn = Turn::from_letter(letter).ok_or_else(error)?;
+                let (color, state) = (&cell[..turn_at], &cell[turn_at + letter.len_utf8()..]);
+                let (color, state): (usize, usize) = match (color.parse(), state.parse()) {
+                    (Ok(color), Ok(state)) => (color, state),
+                    _ => return Err(error()),
+                };
+                if color >= colors || state >= states {
+                    return Err(format!(
+                        "Transition {} of turmite {} goes to a color or state it doesn't have",
+                        cell, s
+                    ));
+                }
+                table.push(Transition {
+                    color: color as u8,
+                    turn,
+                    state: state as u8,
+                });
+            }
+        }
+        Ok(Turmite {
+            table,
+            colors: colors as u8,
+        })
+    }
+}
+
+impl fmt::Display for Turmite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "turmite:")?;
+        for (i, t) in self.table.iter().enumerate() {
+            if i > 0 {
+                let separator = if i % usize::from(self.colors) == 0 { '/' } else { '-' };
+                write!(f, "{}", separator)?;
+            }
+            write!(f, "{}{}{}", t.color, t.turn.letter(), t.state)?;
+        }
+        Ok(())
+    }
+}
+
+/// Makes a rule from the part of a spec after the engine name.
+pub type Engine = Box<dyn Fn(&str) -> Result<Arc<dyn Rule>, String> + Send + Sync>;
+
+/// Rule engines by name.
+pub struct Registry {
+    engines: Vec<(&'static str, Engine)>,
+}
+
+impl Default for Registry {
+    /// The engines built in: `classic` for Langton's ant, `generalized` for letter rules,
+    /// `turmite`, and `scripted` for rule scripts with the script feature.
+    fn default() -> Registry {
+        let mut registry = Registry {
+            engines: Vec::new(),
+        };
+        registry.register("classic", |args| {
+            if args.is_empty() {
+                Ok(Arc::new(Letters::default()))
+            } else {
+                Err(format!("The classic rule takes nothing, got {}", args))
+            }
+        });
+        registry.register("generalized", |args| Ok(Arc::new(args.parse::<Letters>()?)));
+        registry.register("turmite", |args| Ok(Arc::new(args.parse::<Turmite>()?)));
+        #[cfg(feature = "script")]
+        registry.register("scripted", |args| Ok(Arc::new(::script::Scripted::load(args)?)));
+        registry
+    }
+}
+
+impl Registry {
+    /// Make `parse` the engine for specs starting with `name:`, replacing any engine by that
+    /// name.
+    pub fn register<F>(&mut self, name: &'static str, parse: F)
+    where
+        F: Fn(&str) -> Result<Arc<dyn Rule>, String> + Send + Sync + 'static,
+    {
+        self.engines.retain(|&(known, _)| known != name);
+        self.engines.push((name, Box::new(parse)));
+    }
+
+    /// Names of the engines, in the order they were registered.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.engines.iter().map(|&(name, _)| name).collect()
+    }
+
+    /// Parse `spec`, written `engine:args`, just `engine` for engines that take nothing, or
+    /// letters for the generalized engine.
+    pub fn parse(&self, spec: &str) -> Result<Arc<dyn Rule>, String> {
+        let (name, args) = match spec.find(':') {
+            Some(colon) => (&spec[..colon], &spec[colon + 1..]),
+            None if self.engines.iter().any(|&(name, _)| name == spec) => (spec, ""),
+            None => ("generalized", spec),
+        };
+        let (_, engine) = self
+            .engines
+            .iter()
+            .find(|&&(known, _)| known == name)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown rule engine {}, expected one of: {}",
+                    name,
+                    self.names().join(", ")
+                )
+            })?;
+        engine(args)
+    }
+}
+
+/// The engines `parse` reads specs with, the built in ones until more are registered.
+fn registry() -> &'static RwLock<Registry> {
+    static REGISTRY: OnceLock<RwLock<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(Registry::default()))
+}
+
+/// Make `parse` the engine for specs starting with `name:` from now on, replacing any engine by
+/// that name.
+pub fn register<F>(name: &'static str, parse: F)
+where
+    F: Fn(&str) -> Result<Arc<dyn Rule>, String> + Send + Sync + 'static,
+{
+    registry().write().unwrap().register(name, parse);
+}
+
+/// Parse `spec` with the built in engines and the registered ones.
+pub fn parse(spec: &str) -> Result<Arc<dyn Rule>, String> {
+    registry().read().unwrap().parse(spec)
+}
+
+/// Langton's ant.
+pub fn classic() -> Arc<dyn Rule> {
+    Arc::new(Letters::default())
+}
+
+/// Rules taking over from each other during a run, written like `0:LR,50000:LLRR`: from
+/// step 0 on `LR`, from step 50000 on `LLRR`. The first one starts at step 0.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    /// The step each rule starts at, in order.
+    entries: Vec<(u64, Arc<dyn Rule>)>,
+}
+
+impl Schedule {
+    /// The steps the rules start at and the rules, in order.
+    pub fn entries(&self) -> &[(u64, Arc<dyn Rule>)] {
+        &self.entries
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Schedule, String> {
+        let mut entries: Vec<(u64, Arc<dyn Rule>)> = Vec::new();
+        for part in s.split(',').map(str::trim) {
+            let (step, spec) = part
+                .split_once(':')
+                .ok_or_else(|| format!("Expected STEP:RULE in the schedule, got {}", part))?;
+            let step: u64 = step
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid step in the schedule: {}", step))?;
+            match entries.last() {
+                None if step != 0 => {
+                    return Err(format!("The schedule should start at step 0, not {}", step))
+                }
+                Some(&(last, _)) if step <= last => {
+                    return Err(format!(
+                        "Steps in the schedule should go up, {} follows {}",
+                        step, last
+                    ))
+                }
+                _ => {}
+            }
+            let rule = parse(spec.trim())?;
+            // Ants carry their state over to the next rule, which has to know it.
+            if let Some((_, first)) = entries.first() {
+                if rule.states() != first.states() {
+                    return Err(format!(
+                        "Rules in a schedule need the same number of ant states, {} has {} and \
+                         {} has {}",
+                        first,
+                        first.states(),
+                        rule,
+                        rule.states()
+                    ));
+                }
+            }
+            entries.push((step, rule));
+        }
+        Ok(Schedule { entries })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, &(step, ref rule)) in self.entries.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}:{}", step, rule)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simulation::Simulation;
+
+    #[test]
+    fn letters_take_every_turn() {
+        let rule: Letters = "lrnu^V<>".parse().unwrap();
+        assert_eq!(rule.to_string(), "LRNU^v<>");
+        assert!(!rule.is_relative());
+        assert!(rule.mirrored().is_none());
+
+        let rule: Letters = "LRNU".parse().unwrap();
+        assert!(rule.is_relative());
+        assert_eq!(rule.mirrored().unwrap().to_string(), "RLNU");
+        let error = "LRX".parse::<Letters>().unwrap_err();
+        assert!(error.contains("Unknown turn 'X'"), "{}", error);
+    }
+
+    #[test]
+    fn turmite_rejects_turns_that_are_not_letters() {
+        for spec in &["1é1-1L1", "1L-1L1", "L1-1L1", "1L1-1L1/1R1", "1L2-1L1"] {
+            assert!(spec.parse::<Turmite>().is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn turmite_rejects_malformed_tables() {
+        let errors = [
+            ("1L0", "needs 2 to 255 colors"),
+            ("1L0-0R0/1L0", "a transition for each of 2 colors"),
+            ("2L0-0R0", "doesn't have"),
+            ("1L1-0R0", "doesn't have"),
+            ("1L0-0X0", "Expected a transition like 1L0"),
+            ("0-0R0", "Expected a transition like 1L0"),
+        ];
+        for &(spec, error) in &errors {
+            let got = spec.parse::<Turmite>().unwrap_err();
+            assert!(got.contains(error), "{}: {}", spec, got);
+        }
+    }
+
+    #[test]
+    fn turmite_round_trips() {
+        let turmite: Turmite = "1L1-1L1/1R1-0N0".parse().unwrap();
+        assert_eq!(turmite.to_string(), "turmite:1L1-1L1/1R1-0N0");
+        assert_eq!((turmite.colors(), turmite.states()), (2, 2));
+    }
+
+    #[test]
+    fn schedule_rejects_malformed_entries() {
+        let errors = [
+            ("LR", "Expected STEP:RULE"),
+            ("0:LR,x:RL", "Invalid step"),
+            ("-1:LR", "Invalid step"),
+            ("10:LR", "should start at step 0"),
+            ("0:LR,50:RL,50:LLRR", "should go up"),
+            ("0:LR,50:RL,20:LLRR", "should go up"),
+            ("0:LR,50:", "A rule needs one letter"),
+        ];
+        for &(spec, error) in &errors {
+            let got = spec.parse::<Schedule>().unwrap_err();
+            assert!(got.contains(error), "{}: {}", spec, got);
+        }
+    }
+
+    #[test]
+    fn schedule_round_trips() {
+        let schedule: Schedule = " 0:LR, 100:LLRR,200:turmite:1L0-1R0".parse().unwrap();
+        assert_eq!(schedule.to_string(), "0:LR,100:LLRR,200:turmite:1L0-1R0");
+    }
+
+    #[test]
+    fn schedule_rejects_rules_with_other_ant_states() {
+        let error = "0:turmite:1L1-1L1/1R1-0N0,101:LR".parse::<Schedule>().unwrap_err();
+        assert!(error.contains("same number of ant states"), "{}", error);
+    }
+
+    #[test]
+    fn schedule_switches_to_rules_with_more_colors() {
+        let schedule: Schedule = "0:LR,100:LLRR,200:RLR".parse().unwrap();
+        let mut sim = Simulation::new(100, 100);
+        sim.set_schedule(&schedule).unwrap();
+        for _ in 0..300 {
+            sim.step().unwrap();
+        }
+        assert_eq!(sim.rule_for(0).to_string(), "RLR");
+    }
+
+    /// Langton's ant turning the other way, written in a spec only its own engine reads.
+    #[derive(Debug)]
+    struct Backwards;
+
+    impl fmt::Display for Backwards {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "backwards")
+        }
+    }
+
+    impl Rule for Backwards {
+        fn transition(&self, cell: u8, _: u8) -> Transition {
+            Transition {
+                color: 1 - cell,
+                turn: if cell == 0 { Turn::Left } else { Turn::Right },
+                state: 0,
+            }
+        }
+
+        fn colors(&self) -> u8 {
+            2
+        }
+    }
+
+    #[test]
+    fn registered_engines_read_snapshots_and_schedules() {
+        register("backwards", |args| {
+            if args.is_empty() {
+                Ok(Arc::new(Backwards))
+            } else {
+                Err(format!("The backwards rule takes nothing, got {}", args))
+            }
+        });
+        let mut sim = Simulation::new(20, 20);
+        sim.set_rule(parse("backwards").unwrap()).unwrap();
+        for _ in 0..50 {
+            sim.step().unwrap();
+        }
+        let restored = Simulation::restore(&sim.snapshot()).unwrap();
+        assert_eq!(restored.rule_for(0).to_string(), "backwards");
+        assert_eq!(restored.fingerprint(), sim.fingerprint());
+
+        let schedule: Schedule = "0:LR,10:backwards".parse().unwrap();
+        assert_eq!(schedule.to_string(), "0:LR,10:backwards");
+    }
+}
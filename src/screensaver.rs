@@ -0,0 +1,72 @@
+//! `--screensaver`: whenever a run is over, start a new one with a rule picked from a pool and
+//! a fresh seed, until a key is pressed.
+
+use std::sync::Arc;
+
+use langton_ant::rng::Rng;
+use langton_ant::rule::{self, Rule};
+use langton_ant::simulation::Simulation;
+use Options;
+
+/// Rules picked from when `--screensaver-rules` isn't given, all of them worth watching for a
+/// while.
+pub const POOL: &[&str] = &[
+    "RL",
+    "LLRR",
+    "RLR",
+    "LRRRRRLLR",
+    "LLRRRLRLRLLR",
+    "RRLLLRLLLRRR",
+    "turmite:1L1-1L1/1R1-0N0",
+];
+
+/// The default pool, parsed.
+pub fn default_pool() -> Vec<Arc<dyn Rule>> {
+    POOL.iter()
+        .filter_map(|spec| rule::parse(spec).ok())
+        .collect()
+}
+
+/// Catch rules in the pool that can't be set up as `options` say, before the terminal is
+/// taken over.
+pub fn check(options: &Options) -> Result<(), String> {
+    if options.screensaver_rules.is_empty() {
+        return Err("--screensaver needs at least one rule to pick from".to_string());
+    }
+    for rule in &options.screensaver_rules {
+        ::setup_with_rule(1, 1, options, rule)?;
+    }
+    Ok(())
+}
+
+/// Sets up the runs after the first one.
+pub struct Screensaver {
+    /// Everything a run is set up from, except for the rule and seed.
+    options: Options,
+    /// Columns and rows of every run, those of the first one before it could grow.
+    size: (u16, u16),
+    rng: Rng,
+}
+
+impl Screensaver {
+    pub fn new(options: &Options, w: u16, h: u16) -> Screensaver {
+        let mut options = options.clone();
+        // Every run starts from scratch.
+        options.load = None;
+        let rng = Rng::new(options.seed.unwrap_or_else(Rng::time_seed));
+        Screensaver {
+            options,
+            size: (w, h),
+            rng,
+        }
+    }
+
+    /// A fresh run with a rule from the pool, and that rule.
+    pub fn next(&mut self) -> Result<(Simulation, Arc<dyn Rule>), String> {
+        let pool = &self.options.screensaver_rules;
+        let rule = pool[self.rng.below(pool.len() as u64) as usize].clone();
+        self.options.seed = Some(self.rng.next_u64());
+        let sim = ::setup_with_rule(self.size.0, self.size.1, &self.options, &rule)?;
+        Ok((sim, rule))
+    }
+}
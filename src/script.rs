@@ -0,0 +1,806 @@
+//! Rules written as scripts, to try out ideas without recompiling.
+//!
+//! A script defines hook functions in a small expression language:
+//!
+//! ```text
+//! # Langton's ant, except that ants heading up go straight for the next color.
+//! fn decide_turn(state, heading) = state == 0 ? R : L;
+//! fn on_step(ant, cell) = ant.heading == up ? cell.state + 2 : cell.state + 1;
+//! ```
+//!
+//! `decide_turn` is called when an ant lands on a cell, with the cell's color and the ant's
+//! heading, and returns `L`, `R`, `N` to go straight on or `U` to turn around. `on_step` is
+//! called when the ant paints the cell it landed on, and returns the new color, wrapped around
+//! to the number of colors the rules have. Both are optional, the ant's rule decides whatever a
+//! script leaves out.
+//!
+//! Values are integers, `true` and `false`, the turns `L`, `R`, `N` and `U`, and the headings
+//! `up`, `down`, `left` and `right`. There are the usual `+ - * / %`, where dividing by zero
+//! gives zero, comparisons, `&& || !` and `condition ? then : else`. Ants have the fields `x`,
+//! `y`, `heading` and `index`, cells have `x`, `y` and `state`. `step`, `colors`, `width` and
+//! `height` can be used anywhere, and `color(x, y)` is the color of any cell, 0 off the grid.
+//!
+//! Scripts are type checked when loaded, so one that loads can't go wrong while running.
+//!
+//! The same language writes whole rules for the `scripted` engine, see `Scripted`.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use rule::{Rule, Transition, Turn};
+use simulation::{Direction, Hooks, Simulation, State};
+
+/// What an expression evaluates to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Type {
+    Int,
+    Bool,
+    Turn,
+    Heading,
+    Ant,
+    Cell,
+}
+
+impl Type {
+    fn name(self) -> &'static str {
+        match self {
+            Type::Int => "an integer",
+            Type::Bool => "a boolean",
+            Type::Turn => "a turn",
+            Type::Heading => "a heading",
+            Type::Ant => "an ant",
+            Type::Cell => "a cell",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Value {
+    Int(i64),
+    Bool(bool),
+    Turn(Turn),
+    Heading(Direction),
+    /// Index into `Simulation::ants`.
+    Ant(usize),
+    /// Column and row.
+    Cell(usize, usize),
+}
+
+impl Value {
+    // Scripts are checked before they run, so the fallbacks are never used.
+    fn int(self) -> i64 {
+        match self {
+            Value::Int(i) => i,
+            _ => 0,
+        }
+    }
+
+    fn bool(self) -> bool {
+        self == Value::Bool(true)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Global {
+    Step,
+    Colors,
+    Width,
+    Height,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Field {
+    X,
+    Y,
+    Heading,
+    Index,
+    State,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Value(Value),
+    /// An argument of the function, by position.
+    Param(usize),
+    Global(Global),
+    Field(Box<Expr>, Field, &'static str),
+    Color(Box<Expr>, Box<Expr>),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+/// A parsed and checked script.
+#[derive(Clone, Debug)]
+pub struct Script {
+    decide_turn: Option<Expr>,
+    on_step: Option<Expr>,
+}
+
+/// A function a script can define: name, argument types and result type.
+type Signature = (&'static str, &'static [Type], Type);
+
+/// The hooks a script can define.
+const HOOKS: &[Signature] = &[
+    ("decide_turn", &[Type::Int, Type::Heading], Type::Turn),
+    ("on_step", &[Type::Ant, Type::Cell], Type::Int),
+];
+
+/// The functions a rule script can define, `turn` being the only one it has to.
+const RULE_FUNCTIONS: &[Signature] = &[
+    ("colors", &[], Type::Int),
+    ("states", &[], Type::Int),
+    ("turn", &[Type::Int, Type::Int], Type::Turn),
+    ("paint", &[Type::Int, Type::Int], Type::Int),
+    ("next_state", &[Type::Int, Type::Int], Type::Int),
+];
+
+impl Script {
+    /// Read the script at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Script, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        Script::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Parse and check the text of a script.
+    pub fn parse(text: &str) -> Result<Script, String> {
+        let mut script = Script {
+            decide_turn: None,
+            on_step: None,
+        };
+        for (name, body) in functions(text, HOOKS, false)? {
+            match name {
+                "decide_turn" => script.decide_turn = Some(body),
+                _ => script.on_step = Some(body),
+            }
+        }
+        Ok(script)
+    }
+}
+
+/// The functions defined in `text`, each one of `allowed` and checked against it. In a `pure`
+/// script they can't look at the run.
+fn functions(
+    text: &str,
+    allowed: &[Signature],
+    pure: bool,
+) -> Result<Vec<(&'static str, Expr)>, String> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        at: 0,
+        params: Vec::new(),
+        pure,
+    };
+    let mut found: Vec<(&'static str, Expr)> = Vec::new();
+    while !parser.done() {
+        parser.expect("fn")?;
+        let name = parser.ident()?;
+        let &(name, args, result) = allowed
+            .iter()
+            .find(|&&(function, _, _)| function == name)
+            .ok_or_else(|| parser.error(&format!("Unknown function {}", name)))?;
+        parser.expect("(")?;
+        parser.params.clear();
+        while !parser.eat(")") {
+            if !parser.params.is_empty() {
+                parser.expect(",")?;
+            }
+            let param = parser.ident()?;
+            parser.params.push(param);
+        }
+        if parser.params.len() != args.len() {
+            return Err(parser.error(&format!(
+                "{} takes {} arguments, got {}",
+                name,
+                args.len(),
+                parser.params.len()
+            )));
+        }
+        parser.expect("=")?;
+        let body = parser.expr()?;
+        parser.expect(";")?;
+
+        let got = check(&body, args).map_err(|e| format!("In {}: {}", name, e))?;
+        if got != result {
+            return Err(format!(
+                "{} should give {}, not {}",
+                name,
+                result.name(),
+                got.name()
+            ));
+        }
+        if found.iter().any(|&(known, _)| known == name) {
+            return Err(format!("{} is defined twice", name));
+        }
+        found.push((name, body));
+    }
+    Ok(found)
+}
+
+/// A rule written as a script, for the `scripted` engine: `scripted:FILE`.
+///
+/// The script defines `turn(color, state)` and, if it needs them, `paint(color, state)` for
+/// the color to paint, the next one by default, `next_state(color, state)`, the same state by
+/// default, and `colors()` and `states()`, 2 and 1 by default:
+///
+/// ```text
+/// # Langton's ant on four colors, turning left on the even ones.
+/// fn colors() = 4;
+/// fn turn(color, state) = color % 2 == 0 ? L : R;
+/// ```
+///
+/// A rule only sees the cell's color and the ant's state, so `step`, `colors`, `width`,
+/// `height` and `color(x, y)` can't be used. Every transition is worked out when the script is
+/// loaded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scripted {
+    /// The file the script was read from, as given to the engine.
+    path: String,
+    /// Row by row, one row of `colors` transitions per state.
+    table: Vec<Transition>,
+    colors: u8,
+}
+
+impl Scripted {
+    /// Read the rule script at `path`.
+    pub fn load(path: &str) -> Result<Scripted, String> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        Scripted::parse(path, &text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Parse, check and work out the rule script `text`, read from `path`.
+    pub fn parse(path: &str, text: &str) -> Result<Scripted, String> {
+        let functions = functions(text, RULE_FUNCTIONS, true)?;
+        let function = |name| functions.iter().find(|&&(known, _)| known == name);
+        let constant = |name, default: i64| match function(name) {
+            Some((_, body)) => eval(body, None, &[]).int(),
+            None => default,
+        };
+        let (colors, states) = (constant("colors", 2), constant("states", 1));
+        let max = i64::from(u8::MAX);
+        if !(2..=max).contains(&colors) || !(1..=max).contains(&states) {
+            return Err(format!(
+                "A rule needs 2 to {} colors and 1 to {} states, got {} and {}",
+                u8::MAX,
+                u8::MAX,
+                colors,
+                states
+            ));
+        }
+        let turn = function("turn").ok_or("A rule script has to define turn(color, state)")?;
+
+        let mut table = Vec::with_capacity((colors * states) as usize);
+        for state in 0..states {
+            for color in 0..colors {
+                let args = [Value::Int(color), Value::Int(state)];
+                let call = |name, default| match function(name) {
+                    Some((_, body)) => eval(body, None, &args).int(),
+                    None => default,
+                };
+                let paint = call("paint", (color + 1) % colors);
+                let next = call("next_state", state);
+                if !(0..colors).contains(&paint) || !(0..states).contains(&next) {
+                    return Err(format!(
+                        "On color {} in state {} the rule goes to color {} and state {}, past \
+                         its {} colors and {} states",
+                        color, state, paint, next, colors, states
+                    ));
+                }
+                let turn = match eval(&turn.1, None, &args) {
+                    Value::Turn(turn) => turn,
+                    _ => Turn::Straight,
+                };
+                table.push(Transition {
+                    color: paint as u8,
+                    turn,
+                    state: next as u8,
+                });
+            }
+        }
+        Ok(Scripted {
+            path: path.to_string(),
+            table,
+            colors: colors as u8,
+        })
+    }
+}
+
+impl Rule for Scripted {
+    fn transition(&self, cell: u8, ant_state: u8) -> Transition {
+        let colors = usize::from(self.colors);
+        self.table[usize::from(ant_state) * colors + usize::from(cell) % colors]
+    }
+
+    fn colors(&self) -> u8 {
+        self.colors
+    }
+
+    fn states(&self) -> u8 {
+        (self.table.len() / usize::from(self.colors)) as u8
+    }
+}
+
+impl fmt::Display for Scripted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "scripted:{}", self.path)
+    }
+}
+
+impl Hooks for Script {
+    fn decide_turn(&self, sim: &Simulation, state: State, heading: Direction) -> Option<Turn> {
+        let body = self.decide_turn.as_ref()?;
+        let args = [Value::Int(i64::from(state.0)), Value::Heading(heading)];
+        match eval(body, Some(sim), &args) {
+            Value::Turn(turn) => Some(turn),
+            _ => None,
+        }
+    }
+
+    fn on_step(&self, sim: &Simulation, ant: usize) -> Option<State> {
+        let body = self.on_step.as_ref()?;
+        let (x, y) = (sim.ants[ant].x as usize, sim.ants[ant].y as usize);
+        let color = eval(body, Some(sim), &[Value::Ant(ant), Value::Cell(x, y)]).int();
+        Some(State(color.rem_euclid(i64::from(sim.colors())) as u8))
+    }
+}
+
+/// The type of `expr` in a function taking `args`.
+fn check(expr: &Expr, args: &[Type]) -> Result<Type, String> {
+    let expect = |expr: &Expr, want: Type, what: &str| -> Result<(), String> {
+        let found = check(expr, args)?;
+        if found == want {
+            Ok(())
+        } else {
+            Err(format!("{} needs {}, got {}", what, want.name(), found.name()))
+        }
+    };
+    Ok(match *expr {
+        Expr::Value(value) => match value {
+            Value::Int(_) => Type::Int,
+            Value::Bool(_) => Type::Bool,
+            Value::Turn(_) => Type::Turn,
+            Value::Heading(_) => Type::Heading,
+            Value::Ant(_) => Type::Ant,
+            Value::Cell(..) => Type::Cell,
+        },
+        Expr::Param(i) => args[i],
+        Expr::Global(_) => Type::Int,
+        Expr::Field(ref object, field, name) => {
+            let object = check(object, args)?;
+            match (object, field) {
+                (Type::Ant, Field::Heading) => Type::Heading,
+                (Type::Ant, Field::X) | (Type::Ant, Field::Y) | (Type::Ant, Field::Index) => {
+                    Type::Int
+                }
+                (Type::Cell, Field::X) | (Type::Cell, Field::Y) | (Type::Cell, Field::State) => {
+                    Type::Int
+                }
+                _ => return Err(format!("No field {} on {}", name, object.name())),
+            }
+        }
+        Expr::Color(ref x, ref y) => {
+            expect(x, Type::Int, "color")?;
+            expect(y, Type::Int, "color")?;
+            Type::Int
+        }
+        Expr::Neg(ref e) => {
+            expect(e, Type::Int, "-")?;
+            Type::Int
+        }
+        Expr::Not(ref e) => {
+            expect(e, Type::Bool, "!")?;
+            Type::Bool
+        }
+        Expr::Binary(op, ref a, ref b) => match op {
+            Op::Eq | Op::Ne => {
+                let left = check(a, args)?;
+                expect(b, left, "Comparing with ==")?;
+                Type::Bool
+            }
+            Op::And | Op::Or => {
+                expect(a, Type::Bool, "&& and ||")?;
+                expect(b, Type::Bool, "&& and ||")?;
+                Type::Bool
+            }
+            Op::Lt | Op::Le | Op::Gt | Op::Ge => {
+                expect(a, Type::Int, "Comparing with < or >")?;
+                expect(b, Type::Int, "Comparing with < or >")?;
+                Type::Bool
+            }
+            _ => {
+                expect(a, Type::Int, "Arithmetic")?;
+                expect(b, Type::Int, "Arithmetic")?;
+                Type::Int
+            }
+        },
+        Expr::If(ref condition, ref then, ref otherwise) => {
+            expect(condition, Type::Bool, "The condition of ?")?;
+            let result = check(then, args)?;
+            expect(otherwise, result, "Both sides of :")?;
+            result
+        }
+    })
+}
+
+/// The value of `expr` given `args`, in the run `run`. Pure scripts have none.
+fn eval(expr: &Expr, run: Option<&Simulation>, args: &[Value]) -> Value {
+    let int = |e: &Expr| eval(e, run, args).int();
+    match *expr {
+        Expr::Value(value) => value,
+        Expr::Param(i) => args[i],
+        Expr::Global(_) | Expr::Field(..) | Expr::Color(..) => match run {
+            Some(sim) => eval_in(expr, sim, args),
+            // Pure scripts are checked to leave the run alone, the fallback is never used.
+            None => Value::Int(0),
+        },
+        Expr::Neg(ref e) => Value::Int(int(e).wrapping_neg()),
+        Expr::Not(ref e) => Value::Bool(!eval(e, run, args).bool()),
+        Expr::Binary(op, ref a, ref b) => {
+            let a = eval(a, run, args);
+            match op {
+                Op::And => return Value::Bool(a.bool() && eval(b, run, args).bool()),
+                Op::Or => return Value::Bool(a.bool() || eval(b, run, args).bool()),
+                _ => {}
+            }
+            let b = eval(b, run, args);
+            let (x, y) = (a.int(), b.int());
+            match op {
+                Op::Add => Value::Int(x.wrapping_add(y)),
+                Op::Sub => Value::Int(x.wrapping_sub(y)),
+                Op::Mul => Value::Int(x.wrapping_mul(y)),
+                Op::Div => Value::Int(x.checked_div(y).unwrap_or(0)),
+                Op::Rem => Value::Int(x.checked_rem(y).unwrap_or(0)),
+                Op::Eq => Value::Bool(a == b),
+                Op::Ne => Value::Bool(a != b),
+                Op::Lt => Value::Bool(x < y),
+                Op::Le => Value::Bool(x <= y),
+                Op::Gt => Value::Bool(x > y),
+                Op::Ge => Value::Bool(x >= y),
+                Op::And | Op::Or => Value::Bool(false),
+            }
+        }
+        Expr::If(ref condition, ref then, ref otherwise) => {
+            if eval(condition, run, args).bool() {
+                eval(then, run, args)
+            } else {
+                eval(otherwise, run, args)
+            }
+        }
+    }
+}
+
+/// The value of `expr`, which looks at the run, in `sim`.
+fn eval_in(expr: &Expr, sim: &Simulation, args: &[Value]) -> Value {
+    let int = |e: &Expr| eval(e, Some(sim), args).int();
+    match *expr {
+        Expr::Global(global) => Value::Int(match global {
+            Global::Step => sim.steps as i64,
+            Global::Colors => i64::from(sim.colors()),
+            Global::Width => sim.width() as i64,
+            Global::Height => sim.height() as i64,
+        }),
+        Expr::Field(ref object, field, _) => match (eval(object, Some(sim), args), field) {
+            (Value::Ant(i), Field::X) => Value::Int(sim.ants[i].x),
+            (Value::Ant(i), Field::Y) => Value::Int(sim.ants[i].y),
+            (Value::Ant(i), Field::Heading) => Value::Heading(sim.ants[i].heading),
+            (Value::Ant(i), _) => Value::Int(i as i64),
+            (Value::Cell(x, _), Field::X) => Value::Int(x as i64),
+            (Value::Cell(_, y), Field::Y) => Value::Int(y as i64),
+            (Value::Cell(x, y), _) => Value::Int(i64::from(sim.cell(x, y).state.0)),
+            (other, _) => other,
+        },
+        Expr::Color(ref x, ref y) => {
+            let (x, y) = (int(x), int(y));
+            let inside =
+                x >= 0 && y >= 0 && (x as usize) < sim.width() && (y as usize) < sim.height();
+            Value::Int(if inside {
+                i64::from(sim.cell(x as usize, y as usize).state.0)
+            } else {
+                0
+            })
+        }
+        _ => eval(expr, Some(sim), args),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Symbol(&'static str),
+}
+
+/// Punctuation, longest first so `<=` isn't read as `<` and `=`.
+const SYMBOLS: &[&str] = &[
+    "==", "!=", "<=", ">=", "&&", "||", "(", ")", ",", ";", "=", "?", ":", ".", "+", "-", "*",
+    "/", "%", "<", ">", "!",
+];
+
+/// Split a script into tokens, each with its line number.
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let mut rest = line.trim_start();
+        while !rest.is_empty() {
+            let c = rest.chars().next().unwrap_or(' ');
+            let (token, len) = if c.is_ascii_digit() {
+                let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                let value = rest[..len]
+                    .parse()
+                    .map_err(|_| format!("line {}: Number {} is too big", number + 1, &rest[..len]))?;
+                (Token::Number(value), len)
+            } else if c.is_alphabetic() || c == '_' {
+                let len = rest
+                    .find(|c: char| !c.is_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                (Token::Ident(rest[..len].to_string()), len)
+            } else {
+                let symbol = SYMBOLS
+                    .iter()
+                    .find(|&&symbol| rest.starts_with(symbol))
+                    .ok_or_else(|| format!("line {}: Unexpected {:?}", number + 1, c))?;
+                (Token::Symbol(symbol), symbol.len())
+            };
+            tokens.push((token, number + 1));
+            rest = rest[len..].trim_start();
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    at: usize,
+    /// Argument names of the function being parsed.
+    params: Vec<String>,
+    /// Whether the run is off limits.
+    pure: bool,
+}
+
+impl Parser {
+    fn done(&self) -> bool {
+        self.at >= self.tokens.len()
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.at).map(|(token, _)| token)
+    }
+
+    fn error(&self, message: &str) -> String {
+        match self.tokens.get(self.at.min(self.tokens.len().saturating_sub(1))) {
+            Some(&(_, line)) => format!("line {}: {}", line, message),
+            None => message.to_string(),
+        }
+    }
+
+    /// Move past `symbol` or keyword if it comes next.
+    fn eat(&mut self, word: &str) -> bool {
+        let found = match self.peek() {
+            Some(&Token::Symbol(symbol)) => symbol == word,
+            Some(Token::Ident(ident)) => ident == word,
+            _ => false,
+        };
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), String> {
+        if self.eat(word) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("Expected {}", word)))
+        }
+    }
+
+    fn unexpected(&self, message: &str) -> String {
+        match self.peek() {
+            Some(&Token::Number(n)) => self.error(&format!("{}, got {}", message, n)),
+            Some(Token::Ident(ident)) => self.error(&format!("{}, got {}", message, ident)),
+            Some(&Token::Symbol(symbol)) => self.error(&format!("{}, got {}", message, symbol)),
+            None => self.error(&format!("{} at the end", message)),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.peek().cloned() {
+            Some(Token::Ident(ident)) => {
+                self.at += 1;
+                Ok(ident)
+            }
+            _ => Err(self.unexpected("Expected a name")),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let condition = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+        let then = self.expr()?;
+        self.expect(":")?;
+        let otherwise = self.expr()?;
+        Ok(Expr::If(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    /// Operators binding at least as tightly as `level`, from `||` at 0 to `*` at 4.
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        const LEVELS: &[&[(&str, Op)]] = &[
+            &[("||", Op::Or)],
+            &[("&&", Op::And)],
+            &[
+                ("==", Op::Eq),
+                ("!=", Op::Ne),
+                ("<=", Op::Le),
+                (">=", Op::Ge),
+                ("<", Op::Lt),
+                (">", Op::Gt),
+            ],
+            &[("+", Op::Add), ("-", Op::Sub)],
+            &[("*", Op::Mul), ("/", Op::Div), ("%", Op::Rem)],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        'operators: loop {
+            for &(symbol, op) in LEVELS[level] {
+                if self.eat(symbol) {
+                    let right = self.binary(level + 1)?;
+                    left = Expr::Binary(op, Box::new(left), Box::new(right));
+                    continue 'operators;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else if self.eat("!") {
+            Ok(Expr::Not(Box::new(self.unary()?)))
+        } else {
+            let mut expr = self.primary()?;
+            while self.eat(".") {
+                let name = self.ident()?;
+                let (field, name) = match name.as_str() {
+                    "x" => (Field::X, "x"),
+                    "y" => (Field::Y, "y"),
+                    "heading" => (Field::Heading, "heading"),
+                    "index" => (Field::Index, "index"),
+                    "state" => (Field::State, "state"),
+                    _ => return Err(self.error(&format!("Unknown field {}", name))),
+                };
+                expr = Expr::Field(Box::new(expr), field, name);
+            }
+            Ok(expr)
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        if self.eat("(") {
+            let expr = self.expr()?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+        let name = match self.peek().cloned() {
+            Some(Token::Number(n)) => {
+                self.at += 1;
+                return Ok(Expr::Value(Value::Int(n)));
+            }
+            Some(Token::Ident(name)) => name,
+            _ => return Err(self.unexpected("Expected a value")),
+        };
+        self.at += 1;
+        if let Some(i) = self.params.iter().position(|param| *param == name) {
+            return Ok(Expr::Param(i));
+        }
+        let value = |value| Ok(Expr::Value(value));
+        let global = ["step", "colors", "width", "height", "color"].contains(&name.as_str());
+        if global && self.pure {
+            return Err(self.error(&format!(
+                "{} can't be used in a rule, which only sees the color and the ant's state",
+                name
+            )));
+        }
+        match name.as_str() {
+            "true" => value(Value::Bool(true)),
+            "false" => value(Value::Bool(false)),
+            "L" => value(Value::Turn(Turn::Left)),
+            "R" => value(Value::Turn(Turn::Right)),
+            "N" => value(Value::Turn(Turn::Straight)),
+            "U" => value(Value::Turn(Turn::Back)),
+            "up" => value(Value::Heading(Direction::Up)),
+            "down" => value(Value::Heading(Direction::Down)),
+            "left" => value(Value::Heading(Direction::Left)),
+            "right" => value(Value::Heading(Direction::Right)),
+            "step" => Ok(Expr::Global(Global::Step)),
+            "colors" => Ok(Expr::Global(Global::Colors)),
+            "width" => Ok(Expr::Global(Global::Width)),
+            "height" => Ok(Expr::Global(Global::Height)),
+            "color" => {
+                self.expect("(")?;
+                let x = self.expr()?;
+                self.expect(",")?;
+                let y = self.expr()?;
+                self.expect(")")?;
+                Ok(Expr::Color(Box::new(x), Box::new(y)))
+            }
+            _ => Err(self.error(&format!("Unknown name {}", name))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rule::Letters;
+
+    #[test]
+    fn scripted_rule_matches_its_letters() {
+        let script = "fn colors() = 4;\nfn turn(color, state) = color % 2 == 0 ? L : R;";
+        let scripted = Scripted::parse("even.rule", script).unwrap();
+        let letters: Letters = "LRLR".parse().unwrap();
+        for color in 0..4 {
+            let (got, want) = (scripted.transition(color, 0), letters.transition(color, 0));
+            assert_eq!((got.turn, u16::from(got.color)), (want.turn, u16::from(want.color) % 4));
+        }
+        assert_eq!(scripted.to_string(), "scripted:even.rule");
+    }
+
+    #[test]
+    fn scripted_rule_has_states() {
+        let script = "fn states() = 2;
+            fn turn(color, state) = state == 0 ? L : R;
+            fn next_state(color, state) = color == 0 ? 1 - state : state;";
+        let scripted = Scripted::parse("flip.rule", script).unwrap();
+        assert_eq!(scripted.states(), 2);
+        assert_eq!(scripted.transition(0, 0).state, 1);
+        assert_eq!(scripted.transition(1, 1).turn, Turn::Right);
+    }
+
+    #[test]
+    fn scripted_rule_cannot_look_at_the_run() {
+        let error = Scripted::parse("x", "fn turn(color, state) = step > 5 ? L : R;").unwrap_err();
+        assert!(error.contains("can't be used in a rule"), "{}", error);
+        assert!(Scripted::parse("x", "fn paint(color, state) = 0;").is_err());
+        assert!(Scripted::parse("x", "fn turn(c, s) = L; fn paint(c, s) = 7;").is_err());
+    }
+
+    #[test]
+    fn hooks_still_parse() {
+        let script = "fn decide_turn(state, heading) = state == 0 ? R : L;
+            fn on_step(ant, cell) = ant.heading == up ? cell.state + 2 : cell.state + 1;";
+        assert!(Script::parse(script).is_ok());
+        assert!(Script::parse("fn turn(color, state) = L;").is_err());
+    }
+}
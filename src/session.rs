@@ -0,0 +1,88 @@
+//! Named sessions and their quick save slots, kept under the config directory.
+//!
+//! ```text
+//! ~/.config/langtons_ant/sessions/NAME/last.ant     the run as it was when the session quit
+//! ~/.config/langtons_ant/sessions/NAME/slot-1.ant   quick save slots 1 to 4
+//! ```
+//!
+//! `--session NAME` picks up from `last.ant` and writes it again on the way out. Without it, the
+//! quick save slots belong to the session called `default`, which is never loaded or saved by
+//! itself. Every file is a checkpoint, so `--load` reads them as well.
+
+use std::path::PathBuf;
+
+use config;
+use langton_ant::checkpoint;
+use langton_ant::simulation::Simulation;
+
+/// The session a run belongs to.
+#[derive(Clone, Debug)]
+pub struct Session {
+    dir: PathBuf,
+    /// Whether it was picked with `--session`, and so loads and saves `last.ant`.
+    named: bool,
+}
+
+impl Session {
+    /// The session called `name`, or the default one.
+    pub fn open(name: Option<&str>) -> Result<Session, String> {
+        if let Some(name) = name {
+            if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+                return Err(format!("{:?} can't be the name of a session", name));
+            }
+        }
+        let dir = config::dir()
+            .ok_or("No config directory for sessions, set HOME or XDG_CONFIG_HOME")?
+            .join("sessions")
+            .join(name.unwrap_or("default"));
+        Ok(Session {
+            dir,
+            named: name.is_some(),
+        })
+    }
+
+    /// Where the run was when the session last quit, if it did.
+    pub fn last(&self) -> Option<PathBuf> {
+        let path = self.dir.join("last.ant");
+        if self.named && path.exists() {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    /// Save `sim` as the state the session goes on from next time, if it has a name.
+    pub fn autosave(&self, sim: &Simulation) -> Result<(), String> {
+        if !self.named {
+            return Ok(());
+        }
+        self.create()?;
+        checkpoint::save(&self.dir.join("last.ant"), sim)
+    }
+
+    /// Save `sim` to quick save `slot`, returning the path written.
+    pub fn save(&self, slot: u8, sim: &Simulation) -> Result<PathBuf, String> {
+        self.create()?;
+        let path = self.slot(slot);
+        checkpoint::save(&path, sim)?;
+        Ok(path)
+    }
+
+    /// The run saved to quick save `slot`.
+    pub fn load(&self, slot: u8) -> Result<Simulation, String> {
+        let path = self.slot(slot);
+        if !path.exists() {
+            return Err(format!("Slot {} is empty", slot));
+        }
+        checkpoint::load(&path)
+    }
+
+    fn slot(&self, slot: u8) -> PathBuf {
+        self.dir.join(format!("slot-{}.ant", slot))
+    }
+
+    fn create(&self) -> Result<(), String> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Could not create {}: {}", self.dir.display(), e))
+    }
+}
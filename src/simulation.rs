@@ -0,0 +1,1639 @@
+use std::fmt;
+use std::slice::Chunks;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use life;
+use pattern::{Mark, Pattern};
+use rng::Rng;
+use rule::{self, Rule, Schedule, Transition, Turn};
+use stats::Stats;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub fn rotate_left(self) -> Direction {
+        use self::Direction::*;
+        match self {
+            Up => Left,
+            Left => Down,
+            Down => Right,
+            Right => Up,
+        }
+    }
+    pub fn rotate_right(self) -> Direction {
+        use self::Direction::*;
+        match self {
+            Up => Right,
+            Right => Down,
+            Down => Left,
+            Left => Up,
+        }
+    }
+    /// The heading after making `turn`.
+    pub fn apply(self, turn: Turn) -> Direction {
+        match turn {
+            Turn::Left => self.rotate_left(),
+            Turn::Right => self.rotate_right(),
+            Turn::Straight => self,
+            Turn::Back => self.rotate_right().rotate_right(),
+            Turn::Face(heading) => heading,
+        }
+    }
+    /// Column and row deltas of one step, rows growing downwards.
+    pub fn offset(&self) -> (i8, i8) {
+        use self::Direction::*;
+        match *self {
+            Up => (0, -1),
+            Down => (0, 1),
+            Left => (-1, 0),
+            Right => (1, 0),
+        }
+    }
+    /// The heading of a step by `ox` columns and `oy` rows, only looking at the signs. A step
+    /// that goes nowhere counts as up.
+    pub fn from_offset(ox: i64, oy: i64) -> Direction {
+        use self::Direction::*;
+        match (ox.signum(), oy.signum()) {
+            (1, _) => Right,
+            (-1, _) => Left,
+            (_, 1) => Down,
+            _ => Up,
+        }
+    }
+    /// Lowercase name, as accepted by `from_str`.
+    pub fn name(self) -> &'static str {
+        use self::Direction::*;
+        match self {
+            Up => "up",
+            Down => "down",
+            Left => "left",
+            Right => "right",
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Direction, String> {
+        use self::Direction::*;
+        match s {
+            "up" => Ok(Up),
+            "down" => Ok(Down),
+            "left" => Ok(Left),
+            "right" => Ok(Right),
+            _ => Err(format!(
+                "Unknown heading {}, expected up, down, left or right",
+                s
+            )),
+        }
+    }
+}
+
+/// The color of a cell, counted from the color a fresh grid starts with.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct State(pub u8);
+
+impl State {
+    /// The color of a fresh grid, drawn as blank.
+    pub const BLACK: State = State(0);
+    /// The color a black cell is painted first, and the only other one under two color rules.
+    pub const WHITE: State = State(1);
+
+    /// Flip between black and white. Any other color goes back to black.
+    pub fn toggle(self) -> State {
+        if self == State::BLACK {
+            State::WHITE
+        } else {
+            State::BLACK
+        }
+    }
+
+    /// The color after this one on a grid with `colors` colors.
+    pub fn next(self, colors: u8) -> State {
+        State(((u16::from(self.0) + 1) % u16::from(colors)) as u8)
+    }
+
+    /// The color before this one on a grid with `colors` colors.
+    pub fn previous(self, colors: u8) -> State {
+        State(((u16::from(self.0) + u16::from(colors) - 1) % u16::from(colors)) as u8)
+    }
+
+    /// Whether the cell still has the color of a fresh grid.
+    pub fn is_blank(self) -> bool {
+        self == State::BLACK
+    }
+}
+
+/// A cell in the grid.
+#[derive(Copy, Clone, Debug, Hash)]
+pub struct Cell {
+    pub state: State,
+    /// Walls are never painted. An ant about to walk into one turns instead, as set by
+    /// `Simulation::on_wall`.
+    pub wall: bool,
+}
+
+/// What an ant does instead of walking into a wall.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OnWall {
+    /// Turn around.
+    Bounce,
+    /// Turn right.
+    Turn,
+}
+
+impl OnWall {
+    /// The name accepted by `from_str`.
+    pub fn name(self) -> &'static str {
+        match self {
+            OnWall::Bounce => "bounce",
+            OnWall::Turn => "turn",
+        }
+    }
+
+    /// The heading after running into a wall with `heading`.
+    fn apply(self, heading: Direction) -> Direction {
+        match self {
+            OnWall::Bounce => heading.rotate_right().rotate_right(),
+            OnWall::Turn => heading.rotate_right(),
+        }
+    }
+
+    /// The heading before running into a wall and ending up with `heading`.
+    fn undo(self, heading: Direction) -> Direction {
+        match self {
+            OnWall::Bounce => heading.rotate_left().rotate_left(),
+            OnWall::Turn => heading.rotate_left(),
+        }
+    }
+}
+
+impl FromStr for OnWall {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OnWall, String> {
+        match s {
+            "bounce" => Ok(OnWall::Bounce),
+            "turn" => Ok(OnWall::Turn),
+            _ => Err(format!("Unknown wall behavior {}, expected bounce or turn", s)),
+        }
+    }
+}
+
+/// What happens when an ant is about to walk off the grid.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Boundary {
+    /// The run is over.
+    End,
+    /// The grid doubles in size on that side, keeping what is already there in the middle.
+    Grow,
+}
+
+impl Boundary {
+    /// The name accepted by `from_str`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Boundary::End => "end",
+            Boundary::Grow => "grow",
+        }
+    }
+}
+
+impl FromStr for Boundary {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Boundary, String> {
+        match s {
+            "end" => Ok(Boundary::End),
+            "grow" => Ok(Boundary::Grow),
+            _ => Err(format!("Unknown boundary {}, expected end or grow", s)),
+        }
+    }
+}
+
+/// What the grid does by itself, besides being painted by the ants.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Background {
+    /// Nothing, cells only change under ants.
+    Still,
+    /// A generation of Conway's Game of Life every so many steps, see `life`.
+    Life,
+}
+
+impl Background {
+    /// The name accepted by `from_str`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Background::Still => "none",
+            Background::Life => "life",
+        }
+    }
+}
+
+impl FromStr for Background {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Background, String> {
+        match s {
+            "none" => Ok(Background::Still),
+            "life" => Ok(Background::Life),
+            _ => Err(format!("Unknown background {}, expected none or life", s)),
+        }
+    }
+}
+
+/// What ants do when several of them end a step on the same cell.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Collision {
+    /// Nothing special, each of them paints the cell in turn, in the order of `ants`.
+    Sequential,
+    /// They paint in turn and then trade headings, each taking the heading of the next ant on
+    /// the cell and the last one that of the first.
+    Swap,
+    /// The first one paints the cell, then they all disappear. When that would leave no ant
+    /// at all, the first ant of the first cell with a collision stays.
+    Annihilate,
+    /// The first one paints the cell and carries on, the others disappear.
+    Merge,
+}
+
+impl Collision {
+    /// The name accepted by `from_str`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Collision::Sequential => "sequential",
+            Collision::Swap => "swap",
+            Collision::Annihilate => "annihilate",
+            Collision::Merge => "merge",
+        }
+    }
+}
+
+impl FromStr for Collision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Collision, String> {
+        match s {
+            "sequential" => Ok(Collision::Sequential),
+            "swap" => Ok(Collision::Swap),
+            "annihilate" => Ok(Collision::Annihilate),
+            "merge" => Ok(Collision::Merge),
+            _ => Err(format!(
+                "Unknown collision {}, expected sequential, swap, annihilate or merge",
+                s
+            )),
+        }
+    }
+}
+
+/// Decisions taken over from the rules, by a script for instance.
+///
+/// Hooks can't be undone, so runs using them don't step back.
+pub trait Hooks: Send + Sync {
+    /// The turn of an ant heading `heading` that landed on a cell of color `state`, or `None`
+    /// to leave it to the ant's rule.
+    fn decide_turn(&self, sim: &Simulation, state: State, heading: Direction) -> Option<Turn>;
+
+    /// The color ant number `ant` paints the cell it is on, or `None` for the next one.
+    fn on_step(&self, sim: &Simulation, ant: usize) -> Option<State>;
+}
+
+/// Something following a run as it goes, like a trace file or a display, registered with
+/// `Simulation::observe`. Every method does nothing unless implemented.
+pub trait Observer: Send {
+    /// Every ant finished step number `sim.steps`.
+    fn on_step(&mut self, _sim: &Simulation) {}
+
+    /// The cell at column `x` and row `y` changed from color `from` to `to`, painted by an ant
+    /// or moved on by the background.
+    fn on_cell_changed(&mut self, _x: usize, _y: usize, _from: State, _to: State) {}
+
+    /// Ant number `index` took its step and is now `ant`, on a cell it painted from color `from`
+    /// to `to`. Ants keep the numbers they had when the step began, before collisions took any
+    /// away. An ant turned away by a wall, or following the first ant onto a cell both leave,
+    /// paints nothing and has `from` and `to` the same.
+    fn on_ant_moved(&mut self, _index: usize, _ant: &Ant, _from: State, _to: State) {}
+
+    /// The cell at column `x` and row `y` was painted back from color `from` to `to` by
+    /// `step_back`. A change like any other unless implemented.
+    fn on_cell_restored(&mut self, x: usize, y: usize, from: State, to: State) {
+        self.on_cell_changed(x, y, from, to)
+    }
+
+    /// The grid grew with `Boundary::Grow`, moving every cell and ant by the change in
+    /// `origin`. Comes before the changes of the step it grew during.
+    fn on_grew(&mut self, _sim: &Simulation) {}
+
+    /// Whoever runs the simulation is done with it, see `Simulation::finish`.
+    fn on_finished(&mut self, _sim: &Simulation) {}
+}
+
+/// The observers of a simulation. Clones of it start without any, so a copy can be run on its
+/// own without writing to the trace file of the original, for instance.
+#[derive(Default)]
+struct Observers(Vec<Arc<Mutex<dyn Observer>>>);
+
+impl Clone for Observers {
+    fn clone(&self) -> Observers {
+        Observers::default()
+    }
+}
+
+/// A rectangle of cells, inclusive on all sides.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounds {
+    pub min_x: i64,
+    pub min_y: i64,
+    pub max_x: i64,
+    pub max_y: i64,
+}
+
+impl Bounds {
+    /// The single cell at column `x`, row `y`.
+    pub fn point(x: i64, y: i64) -> Bounds {
+        Bounds {
+            min_x: x,
+            min_y: y,
+            max_x: x,
+            max_y: y,
+        }
+    }
+
+    /// Whether column `x`, row `y` lies inside.
+    pub fn contains(&self, x: i64, y: i64) -> bool {
+        x >= self.min_x && x <= self.max_x && y >= self.min_y && y <= self.max_y
+    }
+
+    /// Grow to include column `x`, row `y`.
+    pub fn include(&mut self, x: i64, y: i64) {
+        self.min_x = self.min_x.min(x);
+        self.min_y = self.min_y.min(y);
+        self.max_x = self.max_x.max(x);
+        self.max_y = self.max_y.max(y);
+    }
+
+    /// Number of columns covered.
+    pub fn width(&self) -> u64 {
+        (self.max_x - self.min_x + 1) as u64
+    }
+
+    /// Number of rows covered.
+    pub fn height(&self) -> u64 {
+        (self.max_y - self.min_y + 1) as u64
+    }
+}
+
+/// An ant walking off the edge of the arena, which ends the run.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LeftArena {
+    /// Index of the ant in `Simulation::ants`.
+    pub ant: usize,
+    /// The step it would have taken.
+    pub step: u64,
+    /// The cell outside the arena it was heading for.
+    pub x: i64,
+    pub y: i64,
+}
+
+impl fmt::Display for LeftArena {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Most runs have a single ant, so only count ants when there are several.
+        if self.ant == 0 {
+            write!(f, "The ant left the arena at step {}", self.step)
+        } else {
+            write!(f, "Ant {} left the arena at step {}", self.ant + 1, self.step)
+        }
+    }
+}
+
+/// One ant on the grid.
+#[derive(Copy, Clone, Debug)]
+pub struct Ant {
+    /// The column.
+    pub x: i64,
+    /// The row, counted from the top.
+    pub y: i64,
+    /// Current heading of the ant
+    pub heading: Direction,
+    /// Index into `Simulation::rules` of the rule this ant follows.
+    pub rule: usize,
+    /// State of the ant for rules that have several, otherwise 0.
+    pub state: u8,
+    /// RGB color to draw the ant in, instead of the one picked for its index.
+    pub color: Option<[u8; 3]>,
+    /// Step count when the ant was placed. Stepping back past it removes the ant again.
+    born: u64,
+}
+
+/// The grid and the ants walking on it, without any notion of how it is displayed.
+#[derive(Clone)]
+pub struct Simulation {
+    /// The grid.
+    ///
+    /// The cells are enumerated like you would read a book. Left to right, until you reach the
+    /// line ending, so the cell at column `x` and row `y` is at `y * width + x`.
+    pub grid: Vec<Cell>,
+    /// Number of columns.
+    width: usize,
+    /// The ants, moved in this order on every step. There is always at least one.
+    pub ants: Vec<Ant>,
+    /// The rules the ants follow, the first one being the default.
+    rules: Vec<Arc<dyn Rule>>,
+    /// The steps from which ants on the default rule follow another one, and the index into
+    /// `rules` of that one, in order. Empty without a schedule.
+    schedule: Vec<(u64, usize)>,
+    /// The transitions of every rule, indexed by `ant_state * colors + cell`, so steps don't
+    /// have to ask the rule.
+    transitions: Vec<Vec<Transition>>,
+    /// Number of cell colors, enough to cycle through every rule.
+    colors: u8,
+    /// What ants do at walls.
+    pub on_wall: OnWall,
+    /// What happens at the edge of the grid.
+    pub boundary: Boundary,
+    /// Where the cell that started out in the top left corner is now, after the grid grew.
+    origin: (i64, i64),
+    /// What the grid does by itself.
+    pub background: Background,
+    /// Steps between two moves of the background.
+    pub background_every: u64,
+    /// What ants ending a step on the same cell do.
+    pub collision: Collision,
+    /// Step number and ant index of every time an ant turned at a wall, so stepping back knows
+    /// the ant didn't move.
+    bounces: Vec<(u64, usize)>,
+    /// Chance of an ant turning the wrong way on a step.
+    pub noise: f64,
+    /// Where the noise comes from.
+    pub rng: Rng,
+    /// Step number and ant index of every wrong turn, so stepping back can undo them.
+    flukes: Vec<(u64, usize)>,
+    /// Step the run was last changed by hand at, stepping back doesn't go past it.
+    edited: u64,
+    /// Number of steps taken so far.
+    pub steps: u64,
+    /// Bounding box of the visited cells.
+    pub visited: Option<Bounds>,
+    /// Every position the first ant has been at, when recording is turned on.
+    pub trail: Option<Vec<(i64, i64)>>,
+    /// Decisions made outside the rules.
+    pub hooks: Option<Arc<dyn Hooks>>,
+    /// Told about every step and change.
+    observers: Observers,
+}
+
+impl Simulation {
+    /// An all black grid of `w` columns and `h` rows with an ant in the middle.
+    pub fn new(w: u16, h: u16) -> Simulation {
+        Simulation {
+            grid: vec![
+                Cell {
+                    state: State::BLACK,
+                    wall: false,
+                };
+                w as usize * h as usize
+            ],
+            width: w as usize,
+            ants: vec![Ant {
+                x: i64::from(w / 2),
+                y: i64::from(h / 2),
+                heading: Direction::Right,
+                rule: 0,
+                state: 0,
+                color: None,
+                born: 0,
+            }],
+            rules: vec![rule::classic()],
+            schedule: Vec::new(),
+            transitions: vec![table(&*rule::classic(), 2)],
+            colors: 2,
+            on_wall: OnWall::Bounce,
+            boundary: Boundary::End,
+            origin: (0, 0),
+            background: Background::Still,
+            background_every: 100,
+            collision: Collision::Sequential,
+            bounces: Vec::new(),
+            noise: 0.0,
+            rng: Rng::new(0),
+            flukes: Vec::new(),
+            edited: 0,
+            steps: 0,
+            visited: None,
+            trail: None,
+            hooks: None,
+            observers: Observers::default(),
+        }
+    }
+
+    /// Number of columns.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Number of rows.
+    pub fn height(&self) -> usize {
+        self.grid.len().checked_div(self.width).unwrap_or(0)
+    }
+
+    /// The cell at column `x` and row `y`.
+    pub fn cell(&self, x: usize, y: usize) -> Cell {
+        self.grid[y * self.width + x]
+    }
+
+    /// The cell at column `x` and row `y`, to change it.
+    pub fn cell_mut(&mut self, x: usize, y: usize) -> &mut Cell {
+        &mut self.grid[y * self.width + x]
+    }
+
+    /// The cells ants can walk on.
+    pub fn arena(&self) -> Bounds {
+        Bounds {
+            min_x: 0,
+            min_y: 0,
+            max_x: self.width() as i64 - 1,
+            max_y: self.height() as i64 - 1,
+        }
+    }
+
+    /// How far the grid has grown to the left and to the top, in columns and rows, with
+    /// `Boundary::Grow`. Cells and ants move by as much every time it grows.
+    pub fn origin(&self) -> (i64, i64) {
+        self.origin
+    }
+
+    /// Double the number of columns, the number of rows, or both, keeping the cells that are
+    /// there in the middle. Sizes stop at `u16::MAX`, returns `false` when nothing could grow.
+    fn grow(&mut self, columns: bool, rows: bool) -> bool {
+        let (width, height) = (self.width(), self.height());
+        let max = usize::from(u16::MAX);
+        let new_width = if columns { (width * 2).min(max) } else { width };
+        let new_height = if rows { (height * 2).min(max) } else { height };
+        if (new_width, new_height) == (width, height) {
+            return false;
+        }
+
+        let (dx, dy) = ((new_width - width) / 2, (new_height - height) / 2);
+        let mut grid = vec![
+            Cell {
+                state: State::BLACK,
+                wall: false,
+            };
+            new_width * new_height
+        ];
+        for (y, row) in self.rows().enumerate() {
+            let start = (y + dy) * new_width + dx;
+            grid[start..start + width].copy_from_slice(row);
+        }
+        self.grid = grid;
+        self.width = new_width;
+
+        let (dx, dy) = (dx as i64, dy as i64);
+        self.origin = (self.origin.0 + dx, self.origin.1 + dy);
+        for ant in &mut self.ants {
+            ant.x += dx;
+            ant.y += dy;
+        }
+        if let Some(ref mut bounds) = self.visited {
+            *bounds = Bounds {
+                min_x: bounds.min_x + dx,
+                min_y: bounds.min_y + dy,
+                max_x: bounds.max_x + dx,
+                max_y: bounds.max_y + dy,
+            };
+        }
+        if let Some(ref mut trail) = self.trail {
+            for position in trail.iter_mut() {
+                position.0 += dx;
+                position.1 += dy;
+            }
+        }
+        for observer in &self.observers.0 {
+            observer.lock().unwrap().on_grew(self);
+        }
+        true
+    }
+
+    /// The rows of the grid, from the top.
+    pub fn rows(&self) -> Chunks<'_, Cell> {
+        self.grid.chunks(self.width.max(1))
+    }
+
+    /// The rules the ants follow, indexed by `Ant::rule`.
+    pub fn rules(&self) -> &[Arc<dyn Rule>] {
+        &self.rules
+    }
+
+    /// Number of colors a cell cycles through.
+    pub fn colors(&self) -> u8 {
+        self.colors
+    }
+
+    /// Replace the default rule, the one followed by ants that weren't given their own, for
+    /// the whole run, dropping any schedule.
+    ///
+    /// Like `add_rule`, this belongs before the first step.
+    pub fn set_rule(&mut self, rule: Arc<dyn Rule>) -> Result<(), String> {
+        let old = std::mem::replace(&mut self.rules[0], rule);
+        self.update_colors().inspect_err(|_| self.rules[0] = old)?;
+        self.schedule.clear();
+        Ok(())
+    }
+
+    /// Have ants on the default rule follow the rules of `schedule` in turn.
+    ///
+    /// Every rule of it is added at once, so cells have the colors of all of them from the
+    /// start and steps can be undone across a change of rule. Like `add_rule`, this belongs
+    /// before the first step.
+    pub fn set_schedule(&mut self, schedule: &Schedule) -> Result<(), String> {
+        let mut entries = schedule.entries().iter();
+        let first = match entries.next() {
+            Some((_, rule)) => rule.clone(),
+            None => return Ok(()),
+        };
+        self.set_rule(first)?;
+        let mut indices = vec![(0, 0)];
+        for &(step, ref rule) in entries {
+            indices.push((step, self.add_rule(rule.clone())?));
+        }
+        self.schedule = indices;
+        Ok(())
+    }
+
+    /// The steps from which ants on the default rule follow another rule, and the index into
+    /// `rules` of that rule, empty without a schedule.
+    pub fn schedule(&self) -> &[(u64, usize)] {
+        &self.schedule
+    }
+
+    /// The rule ants with `Ant::rule` set to `rule` go by on the next step.
+    pub fn rule_for(&self, rule: usize) -> &Arc<dyn Rule> {
+        &self.rules[self.scheduled(rule, self.steps)]
+    }
+
+    /// The index of the rule ants with `Ant::rule` set to `rule` go by on the step after
+    /// `step`. Only the default rule has a schedule.
+    fn scheduled(&self, rule: usize, step: u64) -> usize {
+        if rule != 0 {
+            return rule;
+        }
+        self.schedule
+            .iter()
+            .rev()
+            .find(|&&(start, _)| start <= step)
+            .map_or(0, |&(_, index)| index)
+    }
+
+    /// Make `rule` available to ants, returning the index to put in `Ant::rule`.
+    ///
+    /// This may change the number of colors, so rules should be added before the first step;
+    /// steps taken with a different number of colors can't be undone.
+    pub fn add_rule(&mut self, rule: Arc<dyn Rule>) -> Result<usize, String> {
+        let spec = rule.to_string();
+        if let Some(i) = self.rules.iter().position(|known| known.to_string() == spec) {
+            return Ok(i);
+        }
+        self.rules.push(rule);
+        match self.update_colors() {
+            Ok(()) => Ok(self.rules.len() - 1),
+            Err(e) => {
+                self.rules.pop();
+                Err(e)
+            }
+        }
+    }
+
+    /// Pick the number of colors so every rule sees its own colors in order.
+    ///
+    /// Cells cycle through the least common multiple of the rules' colors, and each ant reads
+    /// the color modulo the colors of its rule. An ant with `RL` on a grid shared with an `LLRR`
+    /// ant then sees black, white, black, white as the cell goes through the four colors, so
+    /// every ant behaves as it would alone, whichever ant last painted the cell.
+    fn update_colors(&mut self) -> Result<(), String> {
+        let colors = self
+            .rules
+            .iter()
+            .fold(1, |colors, rule| lcm(colors, usize::from(rule.colors())));
+        if colors > usize::from(u8::MAX) {
+            return Err(format!(
+                "The rules {} need {} cell colors together, at most {} are supported",
+                self.rules
+                    .iter()
+                    .map(|rule| rule.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                colors,
+                u8::MAX
+            ));
+        }
+        self.colors = colors as u8;
+        self.transitions = self
+            .rules
+            .iter()
+            .map(|rule| table(&**rule, self.colors))
+            .collect();
+        Ok(())
+    }
+
+    /// The transition of an ant following rule `rule` in state `ant_state` on a `cell` color,
+    /// on the next step.
+    fn transition(&self, rule: usize, cell: State, ant_state: u8) -> Transition {
+        let index = usize::from(ant_state) * usize::from(self.colors) + usize::from(cell.0);
+        self.transitions[self.scheduled(rule, self.steps)][index]
+    }
+
+    /// Drop a new ant at column `x` and row `y`, following the default rule. It starts moving
+    /// with the next step.
+    pub fn add_ant(&mut self, x: i64, y: i64, heading: Direction) -> &mut Ant {
+        self.ants.push(Ant {
+            x,
+            y,
+            heading,
+            rule: 0,
+            state: 0,
+            color: None,
+            born: self.steps,
+        });
+        self.ants.last_mut().unwrap()
+    }
+
+    /// Move every ant one cell forward, then turn it and paint the cell it landed on as its rule
+    /// says.
+    ///
+    /// Afterwards each ant stands on the cell it painted, except for ants facing a wall, which
+    /// stay where they are and only turn. With `noise`, each ant turns the other way than its
+    /// rule says with that probability. Nothing moves if one of the ants would walk off the
+    /// arena, that is reported instead, unless `boundary` lets the grid grow to make room.
+    pub fn step(&mut self) -> Result<(), LeftArena> {
+        let mut i = 0;
+        while i < self.ants.len() {
+            let ant = self.ants[i];
+            let (ox, oy) = ant.heading.offset();
+            let (x, y) = (ant.x + i64::from(ox), ant.y + i64::from(oy));
+            if !self.arena().contains(x, y) {
+                let columns = x < 0 || x >= self.width() as i64;
+                let rows = y < 0 || y >= self.height() as i64;
+                if self.boundary == Boundary::Grow && self.grow(columns, rows) {
+                    // Every ant moved along with the grid, check this one again.
+                    continue;
+                }
+                return Err(LeftArena {
+                    ant: i,
+                    step: self.steps + 1,
+                    x,
+                    y,
+                });
+            }
+            i += 1;
+        }
+
+        // Every ant turns by the grid as it was before the step, and only then are the cells
+        // painted. Ants meeting on a cell all turn by the same color, which keeps mirrored
+        // ants mirrored, and then paint the cell one after the other.
+        let first_bounce = self.bounces.len();
+        // Out of the way while the hooks look at the simulation.
+        let hooks = self.hooks.take();
+        for i in 0..self.ants.len() {
+            let mut ant = self.ants[i];
+            let (ox, oy) = ant.heading.offset();
+            let (x, y) = (ant.x + i64::from(ox), ant.y + i64::from(oy));
+
+            let cell = self.cell(x as usize, y as usize);
+            if cell.wall {
+                ant.heading = self.on_wall.apply(ant.heading);
+                self.bounces.push((self.steps + 1, i));
+            } else {
+                ant.x = x;
+                ant.y = y;
+                let mut turn = hooks
+                    .as_ref()
+                    .and_then(|hooks| hooks.decide_turn(self, cell.state, ant.heading))
+                    .unwrap_or_else(|| self.transition(ant.rule, cell.state, ant.state).turn);
+                if self.noise > 0.0 && self.rng.next_f64() < self.noise {
+                    turn = turn.mirrored();
+                    self.flukes.push((self.steps + 1, i));
+                }
+                ant.heading = ant.heading.apply(turn);
+                self.visit(x, y);
+            }
+            self.ants[i] = ant;
+        }
+        let meetings = match self.collision {
+            Collision::Sequential => Vec::new(),
+            _ => self.meetings(),
+        };
+        // Only the first ant on a cell paints when the others are going away.
+        let followers: Vec<usize> = match self.collision {
+            Collision::Annihilate | Collision::Merge => {
+                meetings.iter().flat_map(|ants| ants[1..].iter().cloned()).collect()
+            }
+            Collision::Sequential | Collision::Swap => Vec::new(),
+        };
+        for i in 0..self.ants.len() {
+            if self.bounces[first_bounce..].iter().any(|&(_, bounced)| bounced == i)
+                || followers.contains(&i)
+            {
+                let ant = self.ants[i];
+                let state = self.cell(ant.x as usize, ant.y as usize).state;
+                self.moved(i, state, state);
+                continue;
+            }
+            let painted = hooks.as_ref().and_then(|hooks| hooks.on_step(self, i));
+            let ant = self.ants[i];
+            let index = ant.y as usize * self.width + ant.x as usize;
+            let before = self.grid[index].state;
+            let t = self.transition(ant.rule, before, ant.state);
+            let after = painted.unwrap_or(State(t.color));
+            self.grid[index].state = after;
+            if before != after {
+                self.changed(index, before, after, false);
+            }
+            self.ants[i].state = t.state;
+            self.moved(i, before, after);
+        }
+        self.hooks = hooks;
+        self.collide(&meetings);
+
+        self.steps += 1;
+        // Only once the ants are done, so the background sees what they painted.
+        if self.evolved() {
+            for (index, before) in life::generation(&mut self.grid, self.width) {
+                let after = self.grid[index].state;
+                self.changed(index, before, after, false);
+            }
+        }
+        if let Some(ref mut trail) = self.trail {
+            trail.push((self.ants[0].x, self.ants[0].y));
+        }
+        for observer in &self.observers.0 {
+            observer.lock().unwrap().on_step(self);
+        }
+        Ok(())
+    }
+
+    /// The ants sharing a cell, a list of indices into `ants` for every cell with more than one,
+    /// in order.
+    pub fn meetings(&self) -> Vec<Vec<usize>> {
+        let mut order: Vec<usize> = (0..self.ants.len()).collect();
+        // Stable, so the ants of a cell stay in the order of `ants`.
+        order.sort_by_key(|&i| (self.ants[i].y, self.ants[i].x));
+        let mut meetings: Vec<Vec<usize>> = Vec::new();
+        let mut start = 0;
+        while start < order.len() {
+            let at = |i: usize| (self.ants[order[i]].x, self.ants[order[i]].y);
+            let mut end = start + 1;
+            while end < order.len() && at(end) == at(start) {
+                end += 1;
+            }
+            if end - start > 1 {
+                meetings.push(order[start..end].to_vec());
+            }
+            start = end;
+        }
+        meetings.sort_by_key(|ants| ants[0]);
+        meetings
+    }
+
+    /// Apply `collision` to the ants in `meetings`, once they painted.
+    fn collide(&mut self, meetings: &[Vec<usize>]) {
+        if meetings.is_empty() {
+            return;
+        }
+        let mut gone = vec![false; self.ants.len()];
+        for ants in meetings {
+            match self.collision {
+                Collision::Sequential => {}
+                Collision::Swap => {
+                    let headings: Vec<Direction> =
+                        ants.iter().map(|&i| self.ants[i].heading).collect();
+                    for (k, &i) in ants.iter().enumerate() {
+                        self.ants[i].heading = headings[(k + 1) % headings.len()];
+                    }
+                }
+                Collision::Annihilate => ants.iter().for_each(|&i| gone[i] = true),
+                Collision::Merge => ants[1..].iter().for_each(|&i| gone[i] = true),
+            }
+        }
+        if gone.iter().all(|&gone| gone) {
+            gone[meetings[0][0]] = false;
+        }
+        let mut i = 0;
+        self.ants.retain(|_| {
+            i += 1;
+            !gone[i - 1]
+        });
+    }
+
+    /// Whether the background moved on at the end of the last step.
+    pub fn evolved(&self) -> bool {
+        self.background == Background::Life
+            && self.steps > 0
+            && self.steps.is_multiple_of(self.background_every.max(1))
+    }
+
+    /// Forget how the run got here after it was changed by hand, which no rule can undo.
+    ///
+    /// Stepping back stops at the current step from now on, and the turns at walls and wrong
+    /// turns remembered so far go, their ant indices may not match the ants anymore.
+    pub fn forget_history(&mut self) {
+        self.edited = self.steps;
+        self.bounces.clear();
+        self.flukes.clear();
+    }
+
+    /// Undo the last step: paint each ant's cell back, undo its turn and walk it backwards.
+    ///
+    /// Rules work out what a cell and ant were before, so only turns at walls and wrong turns
+    /// from noise need to be remembered. Noise drawn afterwards doesn't repeat the undone wrong
+    /// turns. Ants placed during the undone step are removed. Returns `false` when the run is
+    /// already at the start or where `forget_history` was last called, uses hooks, a background
+    /// or a collision other than sequential, or a rule can't tell what came before, such as a
+    /// rule moving ants by compass direction.
+    pub fn step_back(&mut self) -> bool {
+        if self.steps <= self.edited
+            || self.hooks.is_some()
+            || self.background != Background::Still
+            || self.collision != Collision::Sequential
+            || !self.rules.iter().all(|r| r.is_relative())
+        {
+            return false;
+        }
+        let steps = self.steps - 1;
+        let colors = self.colors;
+        let bounced = after(&self.bounces, steps);
+        let flukes = after(&self.flukes, steps);
+
+        // The other way around from `step`: first every cell is painted back, last painter
+        // first, then the ants undo their turns by the colors from before the step. Nothing
+        // changes until every rule could undo its part.
+        let mut restored: Vec<(usize, usize, u8, u8)> = Vec::new();
+        for (i, ant) in self.ants.iter().enumerate().rev() {
+            if ant.born > steps || bounced.contains(&i) {
+                continue;
+            }
+            let index = ant.y as usize * self.width + ant.x as usize;
+            let color = restored
+                .iter()
+                .rev()
+                .find(|&&(_, painted, _, _)| painted == index)
+                .map_or(self.grid[index].state.0, |&(_, _, color, _)| color);
+            match self.rules[self.scheduled(ant.rule, steps)].undo(color, ant.state, colors) {
+                Some((color, state)) => restored.push((i, index, color, state)),
+                None => return false,
+            }
+        }
+
+        self.steps = steps;
+        self.bounces.truncate(self.bounces.len() - bounced.len());
+        self.flukes.truncate(self.flukes.len() - flukes.len());
+        for (i, index, color, state) in restored {
+            let before = self.grid[index].state;
+            self.grid[index].state = State(color);
+            if before != State(color) {
+                self.changed(index, before, State(color), true);
+            }
+            self.ants[i].state = state;
+        }
+        for i in 0..self.ants.len() {
+            let mut ant = self.ants[i];
+            if ant.born > steps {
+                continue;
+            }
+            if bounced.contains(&i) {
+                self.ants[i].heading = self.on_wall.undo(ant.heading);
+                continue;
+            }
+            let original = self.cell(ant.x as usize, ant.y as usize).state;
+            let mut turn = self.transition(ant.rule, original, ant.state).turn;
+            if flukes.contains(&i) {
+                turn = turn.mirrored();
+            }
+            // Every rule is relative, checked above.
+            ant.heading = turn.undo(ant.heading).unwrap_or(ant.heading);
+
+            let (ox, oy) = ant.heading.offset();
+            ant.x -= i64::from(ox);
+            ant.y -= i64::from(oy);
+            self.ants[i] = ant;
+        }
+
+        self.ants.retain(|ant| ant.born <= steps);
+        if let Some(ref mut trail) = self.trail {
+            trail.pop();
+        }
+        true
+    }
+
+    /// Turn each cell white with probability `density`.
+    pub fn fill_random(&mut self, density: f64, rng: &mut Rng) {
+        for cell in self.grid.iter_mut() {
+            if rng.next_f64() < density {
+                cell.state = State::WHITE;
+            }
+        }
+    }
+
+    /// Copy `pattern` onto the grid with its top left corner at column and row `at`.
+    ///
+    /// Without a position the pattern is centered. Cells falling outside the grid are dropped.
+    pub fn stamp(&mut self, pattern: &Pattern, at: Option<(u16, u16)>) {
+        let rows = self.height();
+        let columns = self.width();
+        let (left, top) = match at {
+            Some((column, row)) => (column as usize, row as usize),
+            None => (
+                columns.saturating_sub(pattern.width) / 2,
+                rows.saturating_sub(pattern.height) / 2,
+            ),
+        };
+
+        for py in 0..pattern.height.min(rows.saturating_sub(top)) {
+            for px in 0..pattern.width.min(columns.saturating_sub(left)) {
+                let (state, wall) = match pattern.get(px, py) {
+                    Mark::Black => (State::BLACK, false),
+                    Mark::White => (State::WHITE, false),
+                    Mark::Wall => (State::BLACK, true),
+                };
+                *self.cell_mut(left + px, top + py) = Cell { state, wall };
+            }
+        }
+    }
+
+    /// Grow the visited bounding box to include column `x`, row `y`.
+    fn visit(&mut self, x: i64, y: i64) {
+        match self.visited {
+            Some(ref mut bounds) => bounds.include(x, y),
+            None => self.visited = Some(Bounds::point(x, y)),
+        }
+    }
+
+    /// Tell `observer` about everything from now on, unless it already is.
+    ///
+    /// Changes made directly to `grid` or `ants` aren't reported.
+    pub fn observe(&mut self, observer: Arc<Mutex<dyn Observer>>) {
+        if !self.observers.0.iter().any(|known| Arc::ptr_eq(known, &observer)) {
+            self.observers.0.push(observer);
+        }
+    }
+
+    /// Say the run is over to every observer, so they can write out what they kept.
+    pub fn finish(&self) {
+        for observer in &self.observers.0 {
+            observer.lock().unwrap().on_finished(self);
+        }
+    }
+
+    /// Let the observers know ant `i` took its step, painting its cell from `from` to `to`.
+    fn moved(&self, i: usize, from: State, to: State) {
+        for observer in &self.observers.0 {
+            observer.lock().unwrap().on_ant_moved(i, &self.ants[i], from, to);
+        }
+    }
+
+    /// Tell the observers the cell at `index` went from `from` to `to`, painted back by
+    /// `step_back` if `restored`.
+    fn changed(&self, index: usize, from: State, to: State, restored: bool) {
+        let (x, y) = (index % self.width, index / self.width);
+        for observer in &self.observers.0 {
+            let mut observer = observer.lock().unwrap();
+            if restored {
+                observer.on_cell_restored(x, y, from, to);
+            } else {
+                observer.on_cell_changed(x, y, from, to);
+            }
+        }
+    }
+
+    /// Collect the summary of the run so far.
+    pub fn stats(&self, elapsed: Duration) -> Stats {
+        let white = self
+            .grid
+            .iter()
+            .filter(|cell| !cell.state.is_blank())
+            .count();
+
+        Stats {
+            steps: self.steps,
+            black: self.width() * self.height() - white,
+            white,
+            bounds: self.visited,
+            elapsed,
+        }
+    }
+
+    /// A hash of the step count, the grid and the ants, the same on every platform and build,
+    /// to check that a run still ends up where it used to.
+    pub fn fingerprint(&self) -> u64 {
+        // 64-bit FNV-1a.
+        let mut hash = 0xCBF2_9CE4_8422_2325u64;
+        let mut feed = |bytes: &[u8]| {
+            for &byte in bytes {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(0x0100_0000_01B3);
+            }
+        };
+        feed(&(self.width as u64).to_le_bytes());
+        feed(&(self.height() as u64).to_le_bytes());
+        feed(&self.steps.to_le_bytes());
+        for cell in &self.grid {
+            feed(&[cell.state.0, cell.wall as u8]);
+        }
+        for ant in &self.ants {
+            feed(&ant.x.to_le_bytes());
+            feed(&ant.y.to_le_bytes());
+            let heading = HEADINGS.iter().position(|&h| h == ant.heading).unwrap_or(0);
+            feed(&[heading as u8, ant.state]);
+        }
+        hash
+    }
+
+    /// Everything needed to carry on with the run later, as bytes read back by `restore`.
+    ///
+    /// The grid is run length encoded, since most of it is usually still black. Hooks, and the
+    /// trail which grows with every step, aren't saved.
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut out = SNAPSHOT_MAGIC.to_vec();
+        out.push(SNAPSHOT_VERSION);
+        out.extend_from_slice(&(self.width as u16).to_le_bytes());
+        out.extend_from_slice(&(self.height() as u16).to_le_bytes());
+        out.extend_from_slice(&self.steps.to_le_bytes());
+        out.push((self.on_wall == OnWall::Turn) as u8);
+        out.push((self.boundary == Boundary::Grow) as u8);
+        for value in &[self.origin.0, self.origin.1] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out.push((self.background == Background::Life) as u8);
+        out.extend_from_slice(&self.background_every.to_le_bytes());
+        out.push(COLLISIONS.iter().position(|&c| c == self.collision).unwrap_or(0) as u8);
+        out.extend_from_slice(&self.noise.to_bits().to_le_bytes());
+        out.extend_from_slice(&self.rng.state().to_le_bytes());
+        match self.visited {
+            Some(bounds) => {
+                out.push(1);
+                for value in &[bounds.min_x, bounds.min_y, bounds.max_x, bounds.max_y] {
+                    out.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            None => out.push(0),
+        }
+
+        out.extend_from_slice(&(self.rules.len() as u16).to_le_bytes());
+        for rule in &self.rules {
+            let spec = rule.to_string();
+            out.extend_from_slice(&(spec.len() as u16).to_le_bytes());
+            out.extend_from_slice(spec.as_bytes());
+        }
+        out.extend_from_slice(&(self.schedule.len() as u16).to_le_bytes());
+        for &(step, rule) in &self.schedule {
+            out.extend_from_slice(&step.to_le_bytes());
+            out.extend_from_slice(&(rule as u16).to_le_bytes());
+        }
+
+        out.extend_from_slice(&(self.ants.len() as u32).to_le_bytes());
+        for ant in &self.ants {
+            out.extend_from_slice(&ant.x.to_le_bytes());
+            out.extend_from_slice(&ant.y.to_le_bytes());
+            out.push(HEADINGS.iter().position(|&h| h == ant.heading).unwrap_or(0) as u8);
+            out.extend_from_slice(&(ant.rule as u16).to_le_bytes());
+            out.push(ant.state);
+            match ant.color {
+                Some(rgb) => {
+                    out.push(1);
+                    out.extend_from_slice(&rgb);
+                }
+                None => out.push(0),
+            }
+            out.extend_from_slice(&ant.born.to_le_bytes());
+        }
+
+        for history in &[&self.bounces, &self.flukes] {
+            out.extend_from_slice(&(history.len() as u64).to_le_bytes());
+            for &(step, ant) in history.iter() {
+                out.extend_from_slice(&step.to_le_bytes());
+                out.extend_from_slice(&(ant as u32).to_le_bytes());
+            }
+        }
+        out.extend_from_slice(&self.edited.to_le_bytes());
+
+        // Colors and walls go separately, walls are usually all off.
+        let colors: Vec<u8> = self.grid.iter().map(|cell| cell.state.0).collect();
+        let walls: Vec<u8> = self.grid.iter().map(|cell| cell.wall as u8).collect();
+        pack_bits(&colors, &mut out);
+        pack_bits(&walls, &mut out);
+        out
+    }
+
+    /// A run saved with `snapshot`.
+    pub fn restore(bytes: &[u8]) -> Result<Simulation, String> {
+        let mut input = Reader { bytes };
+        if input.take(SNAPSHOT_MAGIC.len())? != SNAPSHOT_MAGIC {
+            return Err("Not a snapshot of a run".to_string());
+        }
+        let version = input.u8()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(format!("Snapshot format {} isn't supported by this version", version));
+        }
+        let width = input.u16()?;
+        let height = input.u16()?;
+        let mut sim = Simulation::new(width, height);
+        sim.steps = input.u64()?;
+        sim.on_wall = if input.u8()? == 1 {
+            OnWall::Turn
+        } else {
+            OnWall::Bounce
+        };
+        sim.boundary = if input.u8()? == 1 {
+            Boundary::Grow
+        } else {
+            Boundary::End
+        };
+        sim.origin = (input.u64()? as i64, input.u64()? as i64);
+        sim.background = if input.u8()? == 1 {
+            Background::Life
+        } else {
+            Background::Still
+        };
+        sim.background_every = input.u64()?;
+        sim.collision = *COLLISIONS
+            .get(usize::from(input.u8()?))
+            .ok_or("Unknown collision in snapshot")?;
+        sim.noise = f64::from_bits(input.u64()?);
+        sim.rng = Rng::from_state(input.u64()?);
+        if input.u8()? == 1 {
+            sim.visited = Some(Bounds {
+                min_x: input.u64()? as i64,
+                min_y: input.u64()? as i64,
+                max_x: input.u64()? as i64,
+                max_y: input.u64()? as i64,
+            });
+        }
+
+        let rules = input.u16()?;
+        for i in 0..rules {
+            let len = input.u16()?;
+            let spec = std::str::from_utf8(input.take(usize::from(len))?)
+                .map_err(|_| "Invalid rule in snapshot".to_string())?;
+            let rule = rule::parse(spec)?;
+            if i == 0 {
+                sim.set_rule(rule)?;
+            } else {
+                sim.add_rule(rule)?;
+            }
+        }
+        for _ in 0..input.u16()? {
+            let step = input.u64()?;
+            let rule = usize::from(input.u16()?);
+            if rule >= sim.rules.len() {
+                return Err("Invalid schedule in snapshot".to_string());
+            }
+            sim.schedule.push((step, rule));
+        }
+
+        let ants = input.u32()?;
+        sim.ants.clear();
+        for _ in 0..ants {
+            let x = input.u64()? as i64;
+            let y = input.u64()? as i64;
+            let heading = *HEADINGS
+                .get(usize::from(input.u8()?))
+                .ok_or("Invalid heading in snapshot")?;
+            let rule = usize::from(input.u16()?);
+            let state = input.u8()?;
+            let color = match input.u8()? {
+                1 => {
+                    let rgb = input.take(3)?;
+                    Some([rgb[0], rgb[1], rgb[2]])
+                }
+                _ => None,
+            };
+            let born = input.u64()?;
+            if rule >= sim.rules.len()
+                || state >= sim.rules[rule].states()
+                || !sim.arena().contains(x, y)
+            {
+                return Err("Invalid ant in snapshot".to_string());
+            }
+            sim.ants.push(Ant {
+                x,
+                y,
+                heading,
+                rule,
+                state,
+                color,
+                born,
+            });
+        }
+        if sim.ants.is_empty() {
+            return Err("No ants in snapshot".to_string());
+        }
+
+        for history in &mut [&mut sim.bounces, &mut sim.flukes] {
+            for _ in 0..input.u64()? {
+                let step = input.u64()?;
+                let ant = input.u32()? as usize;
+                history.push((step, ant));
+            }
+        }
+        sim.edited = input.u64()?;
+
+        let colors = input.unpack_bits(sim.grid.len())?;
+        let walls = input.unpack_bits(sim.grid.len())?;
+        for (cell, (color, wall)) in sim.grid.iter_mut().zip(colors.into_iter().zip(walls)) {
+            cell.state = State(color % sim.colors);
+            cell.wall = wall == 1;
+        }
+        Ok(sim)
+    }
+}
+
+/// What every snapshot starts with, followed by the format version.
+const SNAPSHOT_MAGIC: &[u8] = b"LANTSNAP";
+
+/// Format of the snapshots written.
+const SNAPSHOT_VERSION: u8 = 1;
+
+/// Collisions by the number they are saved as.
+const COLLISIONS: [Collision; 4] = [
+    Collision::Sequential,
+    Collision::Swap,
+    Collision::Annihilate,
+    Collision::Merge,
+];
+
+/// Headings by the number they are saved as.
+const HEADINGS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+];
+
+/// Reads a snapshot from the front.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < len {
+            return Err("The snapshot is cut short".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// `len` bytes written by `pack_bits`.
+    fn unpack_bits(&mut self, len: usize) -> Result<Vec<u8>, String> {
+        let mut data = Vec::with_capacity(len);
+        while data.len() < len {
+            let control = self.u8()?;
+            let count = usize::from(control & 0x7F) + 1;
+            if control & 0x80 != 0 {
+                let byte = self.u8()?;
+                data.extend(std::iter::repeat_n(byte, count));
+            } else {
+                data.extend_from_slice(self.take(count)?);
+            }
+        }
+        if data.len() != len {
+            return Err("The grid in the snapshot has the wrong size".to_string());
+        }
+        Ok(data)
+    }
+}
+
+/// Run length encode `data` onto `out`, PackBits style.
+///
+/// A control byte with the high bit set repeats the next byte, otherwise that many bytes follow
+/// as they are. The low seven bits are the count less one.
+fn pack_bits(data: &[u8], out: &mut Vec<u8>) {
+    let run_at = |i: usize| {
+        data[i..]
+            .iter()
+            .take(128)
+            .take_while(|&&byte| byte == data[i])
+            .count()
+    };
+    let mut i = 0;
+    while i < data.len() {
+        let run = run_at(i);
+        if run >= 3 {
+            out.push(0x80 | (run - 1) as u8);
+            out.push(data[i]);
+            i += run;
+            continue;
+        }
+        // Bytes as they are, up to the next run worth repeating.
+        let start = i;
+        while i < data.len() && i - start < 128 && run_at(i) < 3 {
+            i += 1;
+        }
+        out.push((i - start - 1) as u8);
+        out.extend_from_slice(&data[start..i]);
+    }
+}
+
+/// Ant indices of the entries of `history` for steps after `steps`.
+fn after(history: &[(u64, usize)], steps: u64) -> Vec<usize> {
+    let first = history
+        .iter()
+        .rposition(|&(step, _)| step <= steps)
+        .map_or(0, |i| i + 1);
+    history[first..].iter().map(|&(_, i)| i).collect()
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Every transition of `rule` on a grid of `colors` colors, by ant state and then cell color,
+/// with the colors wrapped around to the grid's.
+fn table(rule: &dyn Rule, colors: u8) -> Vec<Transition> {
+    (0..rule.states())
+        .flat_map(|state| {
+            (0..colors).map(move |cell| {
+                let t = rule.transition(cell, state);
+                Transition {
+                    color: (u16::from(t.color) % u16::from(colors)) as u8,
+                    ..t
+                }
+            })
+        })
+        .collect()
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    a / gcd(a, b) * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshots_restore_the_run() {
+        let mut sim = Simulation::new(12, 10);
+        sim.set_schedule(&"0:LR,40:LLRR".parse().unwrap()).unwrap();
+        let rule = sim.add_rule(rule::parse("RRL").unwrap()).unwrap();
+        let ant = sim.add_ant(2, 2, Direction::Down);
+        ant.rule = rule;
+        ant.color = Some([200, 10, 10]);
+        for y in 3..7 {
+            sim.cell_mut(8, y).wall = true;
+        }
+        sim.boundary = Boundary::Grow;
+        sim.noise = 0.2;
+        sim.rng = Rng::new(9);
+        for _ in 0..300 {
+            sim.step().unwrap();
+        }
+        assert!(!sim.flukes.is_empty() && !sim.bounces.is_empty());
+        assert_ne!(sim.origin(), (0, 0));
+
+        let bytes = sim.snapshot();
+        let mut restored = Simulation::restore(&bytes).unwrap();
+        assert_eq!(restored.snapshot(), bytes);
+        for _ in 0..100 {
+            sim.step().unwrap();
+            restored.step().unwrap();
+        }
+        assert_eq!(restored.snapshot(), sim.snapshot());
+        for _ in 0..200 {
+            assert!(sim.step_back());
+            assert!(restored.step_back());
+        }
+        assert_eq!(restored.snapshot(), sim.snapshot());
+    }
+
+    #[test]
+    fn fingerprints_stay_the_same() {
+        // What `verify` prints for the classic ant, so a change to stepping shows up here.
+        let mut sim = Simulation::new(100, 100);
+        for _ in 0..11_000 {
+            sim.step().unwrap();
+        }
+        assert_eq!(sim.fingerprint(), 0xd49e_9f55_b27a_b053);
+
+        let restored = Simulation::restore(&sim.snapshot()).unwrap();
+        assert_eq!(restored.fingerprint(), sim.fingerprint());
+        sim.step().unwrap();
+        assert_ne!(restored.fingerprint(), sim.fingerprint());
+    }
+
+    #[test]
+    fn restore_rejects_broken_snapshots() {
+        let bytes = Simulation::new(5, 5).snapshot();
+        assert!(Simulation::restore(&bytes).is_ok());
+        assert!(Simulation::restore(b"LANTSNIP").is_err());
+        assert!(Simulation::restore(&bytes[..bytes.len() - 1]).is_err());
+        let mut newer = bytes.clone();
+        newer[SNAPSHOT_MAGIC.len()] = SNAPSHOT_VERSION + 1;
+        let error = Simulation::restore(&newer).err().unwrap();
+        assert!(error.contains("isn't supported"), "{}", error);
+    }
+
+    #[test]
+    fn restore_rejects_ants_in_states_their_rule_lacks() {
+        let mut sim = Simulation::new(5, 5);
+        sim.ants[0].state = 1;
+        let error = Simulation::restore(&sim.snapshot()).err().unwrap();
+        assert!(error.contains("Invalid ant"), "{}", error);
+    }
+
+    /// Every move of every ant: its number, where it ended up, and the colors of its cell.
+    #[derive(Default)]
+    struct Moves(Vec<(usize, (i64, i64), u8, u8)>);
+
+    impl Observer for Moves {
+        fn on_ant_moved(&mut self, index: usize, ant: &Ant, from: State, to: State) {
+            self.0.push((index, (ant.x, ant.y), from.0, to.0));
+        }
+    }
+
+    #[test]
+    fn moves_keep_their_ants_through_collisions() {
+        let mut sim = Simulation::new(10, 10);
+        sim.ants.clear();
+        sim.add_ant(4, 5, Direction::Right);
+        sim.add_ant(6, 5, Direction::Left);
+        sim.add_ant(1, 1, Direction::Up);
+        // The cell at 1, 0, painted beforehand so the last ant has a color of its own to report.
+        sim.grid[1].state = State(1);
+        sim.collision = Collision::Annihilate;
+        let moves = Arc::new(Mutex::new(Moves::default()));
+        sim.observe(moves.clone());
+
+        sim.step().unwrap();
+        assert_eq!(sim.ants.len(), 1);
+        assert_eq!(
+            moves.lock().unwrap().0,
+            vec![(0, (5, 5), 0, 1), (1, (5, 5), 1, 1), (2, (1, 0), 1, 0)]
+        );
+        assert_eq!((sim.ants[0].x, sim.ants[0].y), (1, 0));
+
+        moves.lock().unwrap().0.clear();
+        sim.step().unwrap();
+        let moves = &moves.lock().unwrap().0;
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].0, 0);
+    }
+
+    #[test]
+    fn stepping_back_stops_at_edits() {
+        let mut sim = Simulation::new(20, 20);
+        sim.add_ant(3, 3, Direction::Up);
+        for _ in 0..10 {
+            sim.step().unwrap();
+        }
+        sim.ants.remove(0);
+        sim.forget_history();
+        let colors = |sim: &Simulation| sim.grid.iter().map(|cell| cell.state).collect::<Vec<_>>();
+        let edited = colors(&sim);
+        for _ in 0..5 {
+            sim.step().unwrap();
+        }
+
+        let mut sim = Simulation::restore(&sim.snapshot()).unwrap();
+        for _ in 0..5 {
+            assert!(sim.step_back());
+        }
+        assert!(!sim.step_back());
+        assert_eq!(sim.steps, 10);
+        assert_eq!(colors(&sim), edited);
+    }
+}
@@ -0,0 +1,114 @@
+//! `--slowmo-on`: dropping to a slow pace when something worth watching happens, and going
+//! back to the speed asked for a little while after it is over.
+
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use langton_ant::highway::Highway;
+use langton_ant::simulation::Simulation;
+
+/// Steps per second while slowed down, unless the run is slower than that anyway.
+const SPEED: f64 = 20.0;
+
+/// How long the run stays slow once the event is over.
+const HOLD: Duration = Duration::from_secs(3);
+
+/// How close to the edge of the grid an ant has to come, in cells.
+const EDGE_MARGIN: i64 = 3;
+
+/// Something that slows the run down.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The first ant started building a highway.
+    Highway,
+    /// An ant is close to the edge of the grid.
+    Edge,
+    /// Ants ended a step on the same cell.
+    Collision,
+}
+
+impl Event {
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Highway => "highway",
+            Event::Edge => "edge",
+            Event::Collision => "collision",
+        }
+    }
+}
+
+impl FromStr for Event {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Event, String> {
+        match s {
+            "highway" => Ok(Event::Highway),
+            "edge" => Ok(Event::Edge),
+            "collision" => Ok(Event::Collision),
+            _ => Err(format!(
+                "Unknown event {}, expected highway, edge or collision",
+                s
+            )),
+        }
+    }
+}
+
+/// The events to watch for, written like `highway,edge`.
+pub fn parse_events(s: &str) -> Result<Vec<Event>, String> {
+    s.split(',').map(|event| event.trim().parse()).collect()
+}
+
+/// Watches a run for `events`.
+pub struct Slowmo {
+    events: Vec<Event>,
+    highway: Highway,
+    /// The last event seen, and until when it keeps the run slow.
+    slow: Option<(Event, Instant)>,
+}
+
+impl Slowmo {
+    pub fn new(events: Vec<Event>) -> Slowmo {
+        Slowmo {
+            events,
+            highway: Highway::new(),
+            slow: None,
+        }
+    }
+
+    /// Look at the step `sim` just took, staying slow for a while if one of the events came up.
+    pub fn update(&mut self, sim: &Simulation) {
+        // Followed on every step, even while slow for something else.
+        let highway = self.events.contains(&Event::Highway) && self.highway.update(sim).is_some();
+        let (width, height) = (sim.width() as i64, sim.height() as i64);
+        let seen = self.events.iter().cloned().find(|&event| match event {
+            Event::Highway => highway,
+            Event::Edge => sim.ants.iter().any(|ant| {
+                ant.x < EDGE_MARGIN
+                    || ant.y < EDGE_MARGIN
+                    || ant.x >= width - EDGE_MARGIN
+                    || ant.y >= height - EDGE_MARGIN
+            }),
+            Event::Collision => sim.ants.len() > 1 && !sim.meetings().is_empty(),
+        });
+        if let Some(event) = seen {
+            self.slow = Some((event, Instant::now() + HOLD));
+        }
+    }
+
+    /// The event the run is slowed down for, if it is.
+    pub fn cause(&self) -> Option<Event> {
+        match self.slow {
+            Some((event, until)) if Instant::now() < until => Some(event),
+            _ => None,
+        }
+    }
+
+    /// Steps per second to go at instead of `speed`, 0 meaning as fast as possible.
+    pub fn pace(&self, speed: f64) -> f64 {
+        match self.cause() {
+            Some(_) if speed == 0.0 => SPEED,
+            Some(_) => speed.min(SPEED),
+            None => speed,
+        }
+    }
+}
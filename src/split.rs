@@ -0,0 +1,410 @@
+//! Several runs side by side, with `--split`.
+//!
+//! The terminal is tiled into panes, and each pane is a `Main` of its own drawing through a
+//! `Pane` backend, which puts everything into the pane's rectangle of the real window. Keys act
+//! on every pane at once and all panes take their steps together, so rules can be watched
+//! against each other step for step.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Instant;
+
+use backend::{Backend, Input, Style};
+use keys::{Action, KeyMap};
+use langton_ant::export;
+use langton_ant::rule::Rule;
+use langton_ant::stats::Stats;
+use langton_ant::svg;
+use {Main, Options, Renderer};
+
+/// A rectangle of the window, drawn into as if it were a whole one.
+#[derive(Clone)]
+struct Pane {
+    window: Rc<RefCell<Box<dyn Backend>>>,
+    top: u16,
+    left: u16,
+    columns: u16,
+    rows: u16,
+}
+
+impl Pane {
+    fn contains(&self, row: u16, column: u16) -> bool {
+        row >= self.top
+            && column >= self.left
+            && row - self.top < self.rows
+            && column - self.left < self.columns
+    }
+}
+
+impl Backend for Pane {
+    fn size(&self) -> (u16, u16) {
+        (self.columns, self.rows)
+    }
+
+    fn put(&mut self, row: u16, column: u16, text: &str) {
+        self.put_styled(row, column, text, Style::default());
+    }
+
+    fn put_styled(&mut self, row: u16, column: u16, text: &str, style: Style) {
+        if row >= self.rows || column >= self.columns {
+            return;
+        }
+        // Cut off at the pane's right edge, every character drawn takes one column.
+        let text: String = text.chars().take(usize::from(self.columns - column)).collect();
+        self.window
+            .borrow_mut()
+            .put_styled(self.top + row, self.left + column, &text, style);
+    }
+
+    fn clear(&mut self) {
+        let blank = " ".repeat(usize::from(self.columns));
+        for row in 0..self.rows {
+            self.put(row, 0, &blank);
+        }
+    }
+
+    // The whole window is refreshed once every pane is drawn.
+    fn refresh(&mut self) {}
+
+    // Input is read from the window and handed to the panes by `Split`.
+    fn poll(&mut self) -> Option<Input> {
+        None
+    }
+}
+
+/// The panes and what they share.
+struct Split {
+    window: Rc<RefCell<Box<dyn Backend>>>,
+    /// Where every pane is and the run it shows, row by row.
+    panes: Vec<(Pane, Main)>,
+    keys: KeyMap,
+    /// Steps per second for all panes, 0 for as fast as possible.
+    speed: f64,
+    /// Whether stepping is on hold in all panes.
+    paused: bool,
+}
+
+/// Catch what keeps `options` from running in a `layout` of panes, before the terminal is
+/// taken over.
+pub fn check(layout: (u16, u16), options: &Options) -> Result<(), String> {
+    let unsupported = [
+        (options.edit, "--edit"),
+        (options.pixels, "--pixels"),
+        (options.control_socket.is_some(), "--control-socket"),
+        (options.emit_steps.is_some(), "--emit-steps"),
+        (options.checkpoint_every > 0, "--checkpoint-every"),
+        (options.load.is_some(), "--load"),
+        (options.screensaver, "--screensaver"),
+    ];
+    if let Some(&(_, flag)) = unsupported.iter().find(|&&(set, _)| set) {
+        return Err(format!("{} doesn't work with --split", flag));
+    }
+
+    let count = usize::from(layout.0) * usize::from(layout.1);
+    if options.pane_rules.len() > count {
+        return Err(format!(
+            "--split {}x{} has room for {} panes, got {} rules",
+            layout.0,
+            layout.1,
+            count,
+            options.pane_rules.len()
+        ));
+    }
+    for rule in rules(count, options) {
+        ::setup_with_rule(1, 1, options, &rule)?;
+    }
+    Ok(())
+}
+
+/// The rule of each of `count` panes: the rules given in order, then the last one again.
+fn rules(count: usize, options: &Options) -> Vec<Arc<dyn Rule>> {
+    (0..count)
+        .map(|i| options.pane_rules.get(i).unwrap_or(&options.rule).clone())
+        .collect()
+}
+
+/// Run a pane for every rule in a `layout` of columns and rows, until the user quits or the
+/// run in one of the panes is over.
+///
+/// Returns the rule and summary of every pane.
+pub fn run(
+    window: Box<dyn Backend>,
+    renderer: Renderer,
+    layout: (u16, u16),
+    options: &Options,
+) -> Result<Vec<(String, Stats)>, String> {
+    let window = Rc::new(RefCell::new(window));
+    let tiles = tile(&window, layout)?;
+    let count = tiles.len();
+
+    let mut panes = Vec::new();
+    for (i, (tile, rule)) in tiles.into_iter().zip(rules(count, options)).enumerate() {
+        let (w, h) = ::grid_size(&tile, renderer, options);
+        let sim = ::setup_with_rule(w, h, options, &rule)?;
+        let mut main = Main::new(Box::new(tile.clone()), renderer, sim, options);
+        main.title = rule.to_string();
+        // Panes export next to each other, numbered by pane and then by step.
+        main.export_path = export::numbered_path(&main.export_path, i as u64 + 1);
+        panes.push((tile, main));
+    }
+
+    let mut split = Split {
+        window,
+        panes,
+        keys: options.keys.clone(),
+        speed: options.speed,
+        paused: false,
+    };
+    split.draw_lines(layout);
+    for (_, main) in &mut split.panes {
+        main.draw_grid();
+    }
+    split.window.borrow_mut().refresh();
+
+    let stats = split.start();
+    for (_, main) in &split.panes {
+        main.sim.finish();
+    }
+    let mut errors = Vec::new();
+    let mut summaries = Vec::new();
+    for (i, ((_, main), stats)) in split.panes.iter().zip(stats).enumerate() {
+        if let Some(ref path) = options.export_on_exit {
+            let path = export::numbered_path(path, i as u64 + 1);
+            errors.extend(export::export(&path, &main.sim).err());
+        }
+        if let Some(ref path) = options.svg_path {
+            let path = export::numbered_path(path, i as u64 + 1);
+            errors.extend(svg::write(&path, &main.sim).err());
+        }
+        errors.extend(main.left_arena.map(|left| format!("{}: {}", main.title, left)));
+        summaries.push((main.title.clone(), stats));
+    }
+
+    errors.extend(split.window.borrow_mut().close().err());
+
+    // Close the window before reporting anything.
+    drop(split);
+    for e in errors {
+        eprintln!("{}", e);
+    }
+    Ok(summaries)
+}
+
+/// The panes of a `layout` of columns and rows of `window`, with a line between neighbours.
+fn tile(
+    window: &Rc<RefCell<Box<dyn Backend>>>,
+    (columns, rows): (u16, u16),
+) -> Result<Vec<Pane>, String> {
+    let (width, height) = window.borrow().size();
+    let pane_columns = width.saturating_sub(columns - 1) / columns;
+    let pane_rows = height.saturating_sub(rows - 1) / rows;
+    if pane_columns == 0 || pane_rows == 0 {
+        return Err(format!(
+            "A {}x{} terminal is too small for --split {}x{}",
+            width, height, columns, rows
+        ));
+    }
+
+    let mut panes = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            panes.push(Pane {
+                window: window.clone(),
+                top: row * (pane_rows + 1),
+                left: column * (pane_columns + 1),
+                columns: pane_columns,
+                rows: pane_rows,
+            });
+        }
+    }
+    Ok(panes)
+}
+
+impl Split {
+    fn start(&mut self) -> Vec<Stats> {
+        let started = Instant::now();
+        let mut next_step = Instant::now();
+        loop {
+            let statuses: Vec<String> = self
+                .panes
+                .iter_mut()
+                .map(|(_, main)| main.draw_status())
+                .collect();
+
+            let mut step_once = false;
+            loop {
+                // Not borrowed across the loop, every pane draws into the window.
+                let input = match self.window.borrow_mut().poll() {
+                    Some(input) => input,
+                    None => break,
+                };
+                let key = match input {
+                    Input::Key(key) => key,
+                    Input::Click { row, column, shift } => {
+                        self.click(row, column, shift);
+                        continue;
+                    }
+                };
+                match self.keys.action(key) {
+                    Some(Action::Quit) => return self.stats(started),
+                    Some(Action::Pause) => {
+                        self.paused = !self.paused;
+                        // Unpausing also resumes panes frozen at the end of their run.
+                        for (_, main) in &mut self.panes {
+                            main.paused = self.paused;
+                        }
+                    }
+                    Some(Action::Step) => step_once = true,
+                    Some(Action::Reverse) => {
+                        self.paused = false;
+                        for (_, main) in &mut self.panes {
+                            main.reverse();
+                        }
+                    }
+                    Some(Action::Faster) => self.speed *= 2.0,
+                    Some(Action::Slower) if self.speed == 0.0 => {
+                        self.speed = (self.panes[0].1.rate / 2.0).max(1.0)
+                    }
+                    Some(Action::Slower) => self.speed = (self.speed / 2.0).max(::MIN_SPEED),
+                    Some(action) => {
+                        for ((_, main), status) in self.panes.iter_mut().zip(&statuses) {
+                            pane_key(main, action, status);
+                        }
+                    }
+                    None => {}
+                }
+            }
+
+            if self.paused && !step_once {
+                self.window.borrow_mut().refresh();
+                std::thread::sleep(::PAUSED_POLL);
+                next_step = Instant::now();
+                continue;
+            }
+
+            let frame_start = Instant::now();
+            if frame_start > next_step + ::MAX_LAG {
+                next_step = frame_start;
+            }
+            let mut taken = 0;
+            let mut over = false;
+            // One pane slowing down slows them all, they step together.
+            let speed = self.pace();
+            let paused = self.paused;
+            while self.pace() == speed
+                && ::step_due(speed, paused, taken, step_once, frame_start, &mut next_step)
+            {
+                for ((_, main), status) in self.panes.iter_mut().zip(&statuses) {
+                    // Frozen at the end of its run, while the others go on.
+                    if main.paused && !step_once {
+                        continue;
+                    }
+                    // The others still take this step, to stay in step.
+                    over |= !main.advance(status);
+                }
+                if over {
+                    return self.stats(started);
+                }
+                taken += 1;
+            }
+
+            for (_, main) in &mut self.panes {
+                main.fit();
+                main.draw_fading();
+                main.draw_graph();
+                main.draw_histogram();
+            }
+            self.window.borrow_mut().refresh();
+            for (_, main) in &mut self.panes {
+                main.metrics.frame(frame_start.elapsed());
+            }
+            let now = Instant::now();
+            if speed > 0.0 && next_step > now {
+                std::thread::sleep((next_step - now).min(::PAUSED_POLL));
+            }
+        }
+    }
+
+    /// The speed every pane steps at, the slowest `Main::pace` of them.
+    fn pace(&self) -> f64 {
+        self.panes.iter().fold(self.speed, |speed, (_, main)| main.pace(speed))
+    }
+
+    /// The summary of every pane.
+    fn stats(&self, started: Instant) -> Vec<Stats> {
+        self.panes
+            .iter()
+            .map(|(_, main)| main.sim.stats(started.elapsed()))
+            .collect()
+    }
+
+    /// Hand a click to the pane it landed in.
+    fn click(&mut self, row: u16, column: u16, shift: bool) {
+        for (pane, main) in &mut self.panes {
+            if pane.contains(row, column) {
+                main.click(row - pane.top, column - pane.left, shift);
+            }
+        }
+    }
+
+    /// Draw the lines between the panes of a `layout` of columns and rows.
+    fn draw_lines(&mut self, (columns, rows): (u16, u16)) {
+        let (width, height) = self.window.borrow().size();
+        let (pane_columns, pane_rows) = (self.panes[0].0.columns, self.panes[0].0.rows);
+        let lines_at = |size: u16, count: u16| (1..count).map(move |i| i * (size + 1) - 1);
+        let [across, down, cross] = self.panes[0].1.theme.glyphs.lines;
+        let mut window = self.window.borrow_mut();
+        for row in 0..height {
+            for column in lines_at(pane_columns, columns) {
+                window.put(row, column, &down.to_string());
+            }
+        }
+        for row in lines_at(pane_rows, rows) {
+            let line: String = (0..width)
+                .map(|column| {
+                    if lines_at(pane_columns, columns).any(|at| at == column) {
+                        cross
+                    } else {
+                        across
+                    }
+                })
+                .collect();
+            window.put(row, 0, &line);
+        }
+    }
+}
+
+/// Carry out a key that concerns every pane's own grid.
+fn pane_key(main: &mut Main, action: Action, status: &str) {
+    match action {
+        Action::StepBack => {
+            main.step_backward();
+        }
+        Action::Rewind => main.rewinding = !main.rewinding,
+        Action::PanUp => main.pan(0, -1),
+        Action::PanDown => main.pan(0, 1),
+        Action::PanLeft => main.pan(-1, 0),
+        Action::PanRight => main.pan(1, 0),
+        Action::ZoomIn => main.zoom_by(-1),
+        Action::ZoomOut => main.zoom_by(1),
+        Action::Graph => main.toggle_graph(),
+        Action::Histogram => main.toggle_histogram(),
+        Action::Screenshot => main.screenshot(status),
+        Action::Autofit => main.toggle_autofit(),
+        Action::Export => {
+            let path = export::numbered_path(&main.export_path, main.sim.steps);
+            main.export_to(&path, status);
+        }
+        // Handled by `Split` itself, or not available with panes.
+        Action::Quit
+        | Action::Pause
+        | Action::Step
+        | Action::Reverse
+        | Action::Faster
+        | Action::Slower
+        | Action::Edit
+        | Action::QuickSave(_)
+        | Action::QuickLoad(_) => {}
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+use std::time::Duration;
+
+use simulation::{Bounds, Cell, Observer, Simulation, State};
+
+/// Summary of a finished run, printed once the terminal has been restored.
+#[derive(Clone, Debug)]
+pub struct Stats {
+    /// Number of steps the ant completed.
+    pub steps: u64,
+    /// Number of black cells left on the grid.
+    pub black: usize,
+    /// Number of white cells left on the grid.
+    pub white: usize,
+    /// Bounding box of the visited region.
+    pub bounds: Option<Bounds>,
+    /// Wall-clock time spent in the step loop.
+    pub elapsed: Duration,
+}
+
+impl Stats {
+    /// Average number of steps per second over the whole run.
+    pub fn steps_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.steps as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Render the summary as a single JSON object.
+    pub fn to_json(&self) -> String {
+        let bounds = match self.bounds {
+            Some(b) => format!(
+                r#"{{"min_x":{},"min_y":{},"max_x":{},"max_y":{}}}"#,
+                b.min_x, b.min_y, b.max_x, b.max_y
+            ),
+            None => "null".to_string(),
+        };
+        format!(
+            r#"{{"steps":{},"black":{},"white":{},"bounds":{},"elapsed":{:.3},"steps_per_second":{:.1}}}"#,
+            self.steps,
+            self.black,
+            self.white,
+            bounds,
+            self.elapsed.as_secs_f64(),
+            self.steps_per_second()
+        )
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "steps:        {}", self.steps)?;
+        writeln!(f, "black cells:  {}", self.black)?;
+        writeln!(f, "white cells:  {}", self.white)?;
+        match self.bounds {
+            Some(b) => writeln!(
+                f,
+                "bounding box: ({}, {}) - ({}, {}), {}x{}",
+                b.min_x,
+                b.min_y,
+                b.max_x,
+                b.max_y,
+                b.width(),
+                b.height()
+            )?,
+            None => writeln!(f, "bounding box: none")?,
+        }
+        write!(
+            f,
+            "speed:        {:.1} steps/s over {:.3}s",
+            self.steps_per_second(),
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+/// Number of cells of each color and number of times a cell was painted each color, kept up
+/// to date as cells change instead of counting the grid again. Walls aren't counted.
+///
+/// Follows a run as an observer, changes made to the grid directly need a `recount`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    /// Cells of each color, indexed by color.
+    pub cells: Vec<u64>,
+    /// Cells that changed to each color, indexed by color.
+    pub flips: Vec<u64>,
+}
+
+impl Histogram {
+    /// The colors of `grid`, with no flips yet.
+    pub fn of(grid: &[Cell]) -> Histogram {
+        let mut histogram = Histogram {
+            cells: vec![0; 256],
+            flips: vec![0; 256],
+        };
+        histogram.recount(grid);
+        histogram
+    }
+
+    /// Count the colors of `grid` again, after it changed other than by `paint`. Flips so far
+    /// are kept.
+    pub fn recount(&mut self, grid: &[Cell]) {
+        for count in &mut self.cells {
+            *count = 0;
+        }
+        for cell in grid.iter().filter(|cell| !cell.wall) {
+            self.cells[usize::from(cell.state.0)] += 1;
+        }
+    }
+
+    /// A cell was painted from color `from` to `to`.
+    pub fn paint(&mut self, from: State, to: State) {
+        if from != to {
+            self.cells[usize::from(from.0)] -= 1;
+            self.cells[usize::from(to.0)] += 1;
+            self.flips[usize::from(to.0)] += 1;
+        }
+    }
+
+    /// Undo `paint(to, from)`, when a step is taken back.
+    pub fn unpaint(&mut self, from: State, to: State) {
+        if from != to {
+            self.cells[usize::from(from.0)] -= 1;
+            self.cells[usize::from(to.0)] += 1;
+            self.flips[usize::from(from.0)] = self.flips[usize::from(from.0)].saturating_sub(1);
+        }
+    }
+}
+
+impl Observer for Histogram {
+    fn on_cell_changed(&mut self, _x: usize, _y: usize, from: State, to: State) {
+        self.paint(from, to);
+    }
+
+    fn on_cell_restored(&mut self, _x: usize, _y: usize, from: State, to: State) {
+        self.unpaint(from, to);
+    }
+
+    fn on_grew(&mut self, sim: &Simulation) {
+        self.recount(&sim.grid);
+    }
+}
@@ -0,0 +1,86 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use palette;
+use simulation::{Cell, Simulation, State};
+
+/// Write the grid and the ant's recorded trajectory to `path` as SVG.
+///
+/// One user unit is one cell. Painted cells are merged into one rectangle per horizontal run of
+/// the same color to keep the file small, and the trajectory is drawn on top as a single polyline through the
+/// centers of the visited cells.
+pub fn write(path: &Path, sim: &Simulation) -> Result<(), String> {
+    write_svg(path, sim).map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+fn write_svg(path: &Path, sim: &Simulation) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let (width, height) = (sim.width(), sim.height());
+
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {w} {h}" width="{pw}" height="{ph}" shape-rendering="crispEdges">"#,
+        w = width,
+        h = height,
+        pw = width * 8,
+        ph = height * 8
+    )?;
+    writeln!(out, r##"<rect width="{}" height="{}" fill="#fff"/>"##, width, height)?;
+
+    writeln!(out, r##"<g fill="#000">"##)?;
+    for (y, row) in sim.rows().enumerate() {
+        let fill_at = |x: usize| row.get(x).and_then(fill);
+        let mut run_start = 0;
+        // One past the end, so a run touching the right edge is closed too.
+        for x in 0..=row.len() {
+            if x < row.len() && fill_at(x) == fill_at(run_start) {
+                continue;
+            }
+            if let Some(fill) = fill_at(run_start) {
+                writeln!(
+                    out,
+                    r#"<rect x="{}" y="{}" width="{}" height="1"{}/>"#,
+                    run_start,
+                    y,
+                    x - run_start,
+                    fill
+                )?;
+            }
+            run_start = x;
+        }
+    }
+    writeln!(out, "</g>")?;
+
+    if let Some(ref trail) = sim.trail {
+        write!(
+            out,
+            r##"<polyline fill="none" stroke="#e02020" stroke-width="0.2" stroke-linejoin="round" stroke-opacity="0.6" points=""##
+        )?;
+        for (i, &(x, y)) in trail.iter().enumerate() {
+            let separator = if i == 0 { "" } else { " " };
+            write!(out, "{}{}.5,{}.5", separator, x, y)?;
+        }
+        writeln!(out, r#""/>"#)?;
+    }
+
+    writeln!(out, "</svg>")?;
+    out.flush()
+}
+
+/// The fill attribute of a rectangle showing `cell`, `None` if it isn't drawn at all.
+///
+/// White is the fill of the group the rectangles are in, only walls and the colors of longer
+/// rules need their own.
+fn fill(cell: &Cell) -> Option<String> {
+    let color = if cell.wall {
+        palette::WALL
+    } else if cell.state.is_blank() {
+        return None;
+    } else if cell.state == State::WHITE {
+        return Some(String::new());
+    } else {
+        palette::cell(cell.state)
+    };
+    Some(format!(r#" fill="{}""#, palette::hex(color)))
+}
@@ -0,0 +1,136 @@
+//! `sweep`: run every rule in a file for the same number of steps, several at once, and write
+//! a thumbnail and the stats of each into a directory.
+//!
+//! The file has a rule per line, written as `--rule` takes them. Blank lines and lines
+//! starting with `#` are skipped. The rule on line `n` gets `rule-n.png` and `rule-n.json`.
+
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use langton_ant::export;
+use langton_ant::rule::{self, Rule};
+use langton_ant::simulation::LeftArena;
+use langton_ant::stats::Stats;
+use Options;
+
+/// A rule and the line of the file it is on.
+pub struct Entry {
+    pub line: usize,
+    pub rule: Arc<dyn Rule>,
+}
+
+/// How the run of one rule went.
+pub struct Outcome {
+    pub line: usize,
+    pub rule: Arc<dyn Rule>,
+    pub stats: Stats,
+    /// Set when an ant walked off the grid before the last step.
+    pub left: Option<LeftArena>,
+}
+
+/// The rules in the file at `path`.
+pub fn read(path: &Path) -> Result<Vec<Entry>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let spec = line.trim();
+        if spec.is_empty() || spec.starts_with('#') {
+            continue;
+        }
+        let rule = rule::parse(spec)
+            .map_err(|e| format!("{}, line {}: {}", path.display(), i + 1, e))?;
+        entries.push(Entry { line: i + 1, rule });
+    }
+    if entries.is_empty() {
+        return Err(format!("No rules in {}", path.display()));
+    }
+    Ok(entries)
+}
+
+/// Run every rule of `entries` on a `w` by `h` grid set up as `options` say, on `threads`
+/// threads, writing the results into `out`. Outcomes come back in the order of the file.
+pub fn run(
+    entries: &[Entry],
+    w: u16,
+    h: u16,
+    threads: usize,
+    out: &Path,
+    options: &Options,
+) -> Result<Vec<Outcome>, String> {
+    fs::create_dir_all(out).map_err(|e| format!("Could not create {}: {}", out.display(), e))?;
+    let steps = options.steps.unwrap_or(11_000);
+
+    // Every thread takes the next rule nobody has started on, until there are none left.
+    let next = AtomicUsize::new(0);
+    let outcomes = Mutex::new(Vec::with_capacity(entries.len()));
+    let error = Mutex::new(None);
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, entries.len()) {
+            scope.spawn(|| {
+                while let Some(entry) = entries.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    match sweep_one(entry, w, h, steps, out, options) {
+                        Ok(outcome) => outcomes.lock().unwrap().push(outcome),
+                        Err(e) => {
+                            *error.lock().unwrap() = Some(e);
+                            // Nobody starts another rule.
+                            next.store(entries.len(), Ordering::Relaxed);
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    if let Some(e) = error.into_inner().unwrap() {
+        return Err(e);
+    }
+    let mut outcomes = outcomes.into_inner().unwrap();
+    outcomes.sort_by_key(|outcome| outcome.line);
+    Ok(outcomes)
+}
+
+/// Run the rule of `entry` for `steps` steps and write its thumbnail and stats.
+fn sweep_one(
+    entry: &Entry,
+    w: u16,
+    h: u16,
+    steps: u64,
+    out: &Path,
+    options: &Options,
+) -> Result<Outcome, String> {
+    let mut sim = ::setup_with_rule(w, h, options, &entry.rule)?;
+    let started = Instant::now();
+    let mut left = None;
+    while sim.steps < steps {
+        if let Err(error) = sim.step() {
+            left = Some(error);
+            break;
+        }
+    }
+    let stats = sim.stats(started.elapsed());
+
+    let name = format!("rule-{}", entry.line);
+    export::thumbnail(&out.join(format!("{}.png", name)), &sim)?;
+    let json = out.join(format!("{}.json", name));
+    let text = format!(
+        r#"{{"rule":"{}","line":{},"left_arena":{},"stats":{}}}"#,
+        entry.rule,
+        entry.line,
+        left.is_some(),
+        stats.to_json()
+    );
+    fs::write(&json, text + "\n")
+        .map_err(|e| format!("Could not write {}: {}", json.display(), e))?;
+
+    Ok(Outcome {
+        line: entry.line,
+        rule: entry.rule.clone(),
+        stats,
+        left,
+    })
+}
@@ -0,0 +1,152 @@
+//! Mirrored and rotated copies of the starting ants, for kaleidoscopic patterns.
+
+use std::fmt;
+use std::str::FromStr;
+
+use simulation::{Direction, Simulation};
+
+/// How many copies of each ant to place around the center of the grid.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Symmetry {
+    /// Just the ant.
+    Single,
+    /// The ant and its copy turned half way around the center.
+    Point,
+    /// The ant mirrored into every quadrant.
+    Quadrants,
+    /// Every rotation by a quarter turn and their mirror images.
+    Full,
+}
+
+impl Symmetry {
+    /// Number of ants each starting ant turns into.
+    pub fn order(self) -> u8 {
+        match self {
+            Symmetry::Single => 1,
+            Symmetry::Point => 2,
+            Symmetry::Quadrants => 4,
+            Symmetry::Full => 8,
+        }
+    }
+
+    /// The maps from a position relative to the center to the copy's, as a 2×2 matrix written
+    /// row by row, and whether each is a mirror image.
+    fn maps(self) -> &'static [([i64; 4], bool)] {
+        const IDENTITY: ([i64; 4], bool) = ([1, 0, 0, 1], false);
+        const HALF_TURN: ([i64; 4], bool) = ([-1, 0, 0, -1], false);
+        const MIRROR_X: ([i64; 4], bool) = ([-1, 0, 0, 1], true);
+        const MIRROR_Y: ([i64; 4], bool) = ([1, 0, 0, -1], true);
+        const QUARTER: ([i64; 4], bool) = ([0, -1, 1, 0], false);
+        const THREE_QUARTERS: ([i64; 4], bool) = ([0, 1, -1, 0], false);
+        const DIAGONAL: ([i64; 4], bool) = ([0, 1, 1, 0], true);
+        const ANTI_DIAGONAL: ([i64; 4], bool) = ([0, -1, -1, 0], true);
+        match self {
+            Symmetry::Single => &[IDENTITY],
+            Symmetry::Point => &[IDENTITY, HALF_TURN],
+            Symmetry::Quadrants => &[IDENTITY, MIRROR_X, MIRROR_Y, HALF_TURN],
+            Symmetry::Full => &[
+                IDENTITY,
+                QUARTER,
+                HALF_TURN,
+                THREE_QUARTERS,
+                MIRROR_X,
+                MIRROR_Y,
+                DIAGONAL,
+                ANTI_DIAGONAL,
+            ],
+        }
+    }
+
+    /// Add the copies of every ant on `sim`.
+    ///
+    /// Mirror images follow the rule with left and right swapped, so all the copies keep
+    /// drawing the same pattern for as long as they don't meet. Copies landing on an ant
+    /// heading the same way are left out. Fails when a copy would be off the grid, which can
+    /// happen with quarter turns on grids that aren't square.
+    pub fn apply(self, sim: &mut Simulation) -> Result<(), String> {
+        // Positions are doubled so the center can fall between two cells. Quarter turns only
+        // map cells onto cells when both coordinates of the center do, so for those the center
+        // moves up half a cell on grids whose width and height differ in parity.
+        let center_x = sim.width() as i64 - 1;
+        let mut center_y = sim.height() as i64 - 1;
+        if self == Symmetry::Full && (center_x - center_y) % 2 != 0 {
+            center_y -= 1;
+        }
+        let arena = sim.arena();
+
+        let originals = sim.ants.clone();
+        for original in &originals {
+            let rule = &sim.rules()[original.rule];
+            if self != Symmetry::Single && !rule.is_relative() {
+                return Err(format!(
+                    "The rule {} moves ants by compass direction, their copies for --symmetry {} \
+                     wouldn't stay copies",
+                    rule, self
+                ));
+            }
+            let (dx, dy) = (2 * original.x - center_x, 2 * original.y - center_y);
+            let (ox, oy) = original.heading.offset();
+            for &(m, mirrored) in &self.maps()[1..] {
+                let x = (m[0] * dx + m[1] * dy + center_x) / 2;
+                let y = (m[2] * dx + m[3] * dy + center_y) / 2;
+                if !arena.contains(x, y) {
+                    return Err(format!(
+                        "The ant at {},{} has a copy off the grid at {},{}, --symmetry {} needs \
+                         it closer to the center",
+                        original.x,
+                        original.y,
+                        x,
+                        y,
+                        self
+                    ));
+                }
+                let offset = (
+                    m[0] * i64::from(ox) + m[1] * i64::from(oy),
+                    m[2] * i64::from(ox) + m[3] * i64::from(oy),
+                );
+                let heading = Direction::from_offset(offset.0, offset.1);
+                let taken = sim
+                    .ants
+                    .iter()
+                    .any(|ant| (ant.x, ant.y) == (x, y) && ant.heading == heading);
+                if taken {
+                    continue;
+                }
+
+                let rule = if mirrored {
+                    let rule = &sim.rules()[original.rule];
+                    let mirrored = rule.mirrored().ok_or_else(|| {
+                        format!("The rule {} has no mirror image for --symmetry {}", rule, self)
+                    })?;
+                    sim.add_rule(mirrored)?
+                } else {
+                    original.rule
+                };
+                let ant = sim.add_ant(x, y, heading);
+                ant.rule = rule;
+                ant.color = original.color;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.order())
+    }
+}
+
+impl FromStr for Symmetry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Symmetry, String> {
+        match s {
+            "1" => Ok(Symmetry::Single),
+            "2" => Ok(Symmetry::Point),
+            "4" => Ok(Symmetry::Quadrants),
+            "8" => Ok(Symmetry::Full),
+            _ => Err(format!("Unknown symmetry {}, expected 1, 2, 4 or 8", s)),
+        }
+    }
+}
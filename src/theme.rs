@@ -0,0 +1,358 @@
+//! How cells, walls and ants look in the terminal, picked with `--theme`.
+//!
+//! A theme is either built in or read from a TOML file. The file holds the changes to the
+//! classic look, in tables for each part:
+//!
+//! ```toml
+//! [screen]
+//! fg = "#839496"
+//! bg = "#002b36"
+//!
+//! [cell.1]
+//! glyph = "█"
+//! fg = "#b58900"
+//!
+//! [wall]
+//! glyph = "#"
+//! bg = "#073642"
+//!
+//! [ant]
+//! fg = "red"
+//! ```
+//!
+//! `screen` colors the status line and everything drawn without colors of its own. Cells are
+//! numbered by color, 0 being black. Painted colors past the last one given repeat those from 1
+//! on. Colors are `#rrggbb`, a name like `red`, or `default` for the screen's own.
+//!
+//! In the config file, the `[theme]` table picks the theme with `name` and changes it with
+//! the same settings as dotted keys, like `cell.1.fg = "red"`.
+//!
+//! Everything else drawn on the grid, from fading cells to the lines between panes, comes
+//! from a set of `Glyphs`: Unicode shading and lines, or plain ASCII with `--ascii` and where
+//! the locale or the terminal can't show more.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use backend::Style;
+use config::{self, Value};
+use langton_ant::palette;
+use langton_ant::simulation::State;
+
+/// A glyph and its colors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Look {
+    pub glyph: String,
+    pub style: Style,
+}
+
+impl Look {
+    fn plain(glyph: &str) -> Look {
+        Look {
+            glyph: glyph.to_string(),
+            style: Style::default(),
+        }
+    }
+
+    fn colored(glyph: &str, fg: [u8; 3], bg: Option<[u8; 3]>) -> Look {
+        Look {
+            glyph: glyph.to_string(),
+            style: Style { fg: Some(fg), bg },
+        }
+    }
+}
+
+/// Characters drawn for anything but cells and walls.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Glyphs {
+    /// Light, medium and dark shading, for fading cells, the path and the cursor in edit mode.
+    pub shades: [&'static str; 3],
+    /// What the bars of the histogram are made of.
+    pub bar: &'static str,
+    /// Bars of a sparkline, from the lowest to the highest.
+    pub sparks: [char; 8],
+    /// Lines between the panes of `--split`: across, down, and where they cross.
+    pub lines: [char; 3],
+    /// Ants heading up, down, left and right, unless `--ant-glyphs` picks others.
+    pub ants: [char; 4],
+}
+
+pub const UNICODE: Glyphs = Glyphs {
+    shades: ["░", "▒", "▓"],
+    bar: "█",
+    sparks: ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'],
+    lines: ['─', '│', '┼'],
+    ants: ['▲', '▼', '◀', '▶'],
+};
+
+pub const ASCII: Glyphs = Glyphs {
+    shades: [":", "+", "*"],
+    bar: "#",
+    sparks: ['_', '.', ':', '-', '=', '+', '*', '#'],
+    lines: ['-', '|', '+'],
+    ants: ['^', 'v', '<', '>'],
+};
+
+/// Characters for cells from black on with `--ascii`, unless `--ascii-cells` picks others.
+pub const ASCII_CELLS: &str = ".#@%";
+
+/// The look of everything on the grid.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    /// Name of the built in theme or the file it was read from, as given to `--theme`.
+    source: String,
+    /// The settings applied on top of `source` by a config file, to write them back.
+    changes: Vec<(String, Value)>,
+    /// Colors of the status line and of cleared parts of the screen.
+    pub screen: Style,
+    /// Cell colors from black on, at least black and one painted color.
+    cells: Vec<Look>,
+    pub wall: Look,
+    pub ant: Style,
+    pub glyphs: Glyphs,
+}
+
+/// Names of the built in themes.
+pub const NAMES: &[&str] = &["classic", "solarized", "grayscale", "high-contrast"];
+
+impl Theme {
+    /// Shades of black on white, as the terminal draws them.
+    pub fn classic() -> Theme {
+        Theme {
+            source: "classic".to_string(),
+            changes: Vec::new(),
+            screen: Style::default(),
+            cells: vec![
+                Look::plain(" "),
+                Look::plain("█"),
+                Look::plain("▓"),
+                Look::plain("▒"),
+            ],
+            wall: Look::plain("#"),
+            ant: Style::default(),
+            glyphs: UNICODE,
+        }
+    }
+
+    /// The built in theme called `name`.
+    pub fn builtin(name: &str) -> Option<Theme> {
+        let mut theme = Theme::classic();
+        theme.source = name.to_string();
+        let block = |rgb: &[[u8; 3]]| {
+            let mut cells = vec![Look::plain(" ")];
+            cells.extend(rgb.iter().map(|&fg| Look::colored("█", fg, None)));
+            cells
+        };
+        match name {
+            "classic" => {}
+            // Solarized dark, https://ethanschoonover.com/solarized/
+            "solarized" => {
+                theme.screen = Style {
+                    fg: Some([0x83, 0x94, 0x96]),
+                    bg: Some([0x00, 0x2B, 0x36]),
+                };
+                theme.cells = block(&[
+                    [0xB5, 0x89, 0x00],
+                    [0xCB, 0x4B, 0x16],
+                    [0xDC, 0x32, 0x2F],
+                    [0xD3, 0x36, 0x82],
+                    [0x6C, 0x71, 0xC4],
+                    [0x26, 0x8B, 0xD2],
+                    [0x2A, 0xA1, 0x98],
+                    [0x85, 0x99, 0x00],
+                ]);
+                theme.wall = Look::colored("#", [0x58, 0x6E, 0x75], Some([0x07, 0x36, 0x42]));
+                theme.ant = Style {
+                    fg: Some([0xFD, 0xF6, 0xE3]),
+                    bg: None,
+                };
+            }
+            "grayscale" => {
+                theme.screen = Style {
+                    fg: Some([0x00, 0x00, 0x00]),
+                    bg: Some([0xFF, 0xFF, 0xFF]),
+                };
+                theme.cells = block(&[
+                    [0x20, 0x20, 0x20],
+                    [0x60, 0x60, 0x60],
+                    [0x90, 0x90, 0x90],
+                    [0xC0, 0xC0, 0xC0],
+                ]);
+                theme.wall = Look::colored("#", [0x00, 0x00, 0x00], Some([0xD8, 0xD8, 0xD8]));
+                theme.ant = Style {
+                    fg: Some([0x00, 0x00, 0x00]),
+                    bg: None,
+                };
+            }
+            "high-contrast" => {
+                theme.screen = Style {
+                    fg: Some([0xFF, 0xFF, 0xFF]),
+                    bg: Some([0x00, 0x00, 0x00]),
+                };
+                theme.cells = block(&[
+                    [0xFF, 0xFF, 0xFF],
+                    [0xFF, 0xFF, 0x00],
+                    [0x00, 0xFF, 0xFF],
+                    [0xFF, 0x00, 0xFF],
+                    [0x00, 0xFF, 0x00],
+                    [0x00, 0x80, 0xFF],
+                ]);
+                theme.wall = Look::colored("#", [0x00, 0x00, 0x00], Some([0xFF, 0xFF, 0x00]));
+                theme.ant = Style {
+                    fg: Some([0xFF, 0x00, 0x00]),
+                    bg: Some([0x00, 0x00, 0x00]),
+                };
+            }
+            _ => return None,
+        }
+        Some(theme)
+    }
+
+    /// The theme in the file at `path`, on top of the classic one.
+    pub fn load(path: &Path) -> Result<Theme, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read theme {}: {}", path.display(), e))?;
+        let mut theme = Theme::classic();
+        theme.source = path.display().to_string();
+        let entries = config::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        for (key, value) in &entries {
+            theme
+                .set(key, value)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        Ok(theme)
+    }
+
+    /// Change the part named by `key`, like `cell.1.fg` or `wall.glyph`, to `value`.
+    pub fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        let text: String = value.parse(key)?;
+        let (part, field) = match key.rfind('.') {
+            Some(dot) => (&key[..dot], &key[dot + 1..]),
+            None => return Err(format!("Unknown theme setting {}", key)),
+        };
+        let look = match part {
+            "screen" => return set_color(&mut self.screen, field, &text, key),
+            "ant" => return set_color(&mut self.ant, field, &text, key),
+            "wall" => &mut self.wall,
+            _ => {
+                let color = part
+                    .strip_prefix("cell.")
+                    .and_then(|color| color.parse::<u8>().ok())
+                    .ok_or_else(|| format!("Unknown theme setting {}", key))?;
+                // Colors in between get the look they had until now.
+                while self.cells.len() <= usize::from(color) {
+                    let look = self.cell(State(self.cells.len() as u8)).clone();
+                    self.cells.push(look);
+                }
+                &mut self.cells[usize::from(color)]
+            }
+        };
+        match field {
+            "glyph" if text.chars().count() == 1 => look.glyph = text,
+            "glyph" => return Err(format!("{} should be a single character", key)),
+            _ => set_color(&mut look.style, field, &text, key)?,
+        }
+        Ok(())
+    }
+
+    /// `set`, remembering the change for `changes`.
+    pub fn change(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        self.set(key, value)?;
+        self.changes.push((key.to_string(), value.clone()));
+        Ok(())
+    }
+
+    /// Draw nothing but ASCII: cells from black on with the characters of `cells`, colors past
+    /// the last one repeating them from the second on, walls as `X`, which patterns use for
+    /// them, and the rest from `ASCII`. Colors stay as they are.
+    pub fn use_ascii(&mut self, cells: &[char]) {
+        let glyph = |color: usize| match cells.get(color) {
+            Some(&c) => c,
+            None => cells[1 + (color - 1) % (cells.len() - 1)],
+        };
+        self.cells = (0..self.cells.len().max(cells.len()))
+            .map(|color| Look {
+                glyph: glyph(color).to_string(),
+                style: self.cell(State(color as u8)).style,
+            })
+            .collect();
+        self.wall.glyph = "X".to_string();
+        self.glyphs = ASCII;
+    }
+
+    /// What was given to `--theme`.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The settings applied with `change`, in order.
+    pub fn changes(&self) -> &[(String, Value)] {
+        &self.changes
+    }
+
+    /// The look of cells in `state`.
+    pub fn cell(&self, state: State) -> &Look {
+        let color = usize::from(state.0);
+        let painted = self.cells.len() - 1;
+        if color < self.cells.len() {
+            &self.cells[color]
+        } else {
+            &self.cells[1 + (color - 1) % painted]
+        }
+    }
+}
+
+/// Characters for `--ascii-cells`: printable ASCII, one for black and at least one more.
+pub fn parse_ascii_cells(value: &str) -> Result<Vec<char>, String> {
+    let cells: Vec<char> = value.chars().collect();
+    if cells.len() < 2 || !cells.iter().all(|&c| c == ' ' || c.is_ascii_graphic()) {
+        return Err(format!(
+            "Expected printable ASCII characters for black and painted colors like {}, got {}",
+            ASCII_CELLS, value
+        ));
+    }
+    Ok(cells)
+}
+
+/// Whether the terminal may not show Unicode: the locale names a character set other than
+/// UTF-8, like the C locale, or TERM is one of the old VT terminals. Without any locale set,
+/// the terminal is trusted.
+pub fn needs_ascii() -> bool {
+    let set = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+    let locale = set("LC_ALL").or_else(|| set("LC_CTYPE")).or_else(|| set("LANG"));
+    let utf8 = |locale: &str| locale.to_lowercase().replace('-', "").contains("utf8");
+    let old = |term: String| term.starts_with("vt1") || term.starts_with("vt2");
+    locale.is_some_and(|locale| !utf8(&locale)) || set("TERM").is_some_and(old)
+}
+
+fn set_color(style: &mut Style, field: &str, text: &str, key: &str) -> Result<(), String> {
+    let color = match text {
+        "default" => None,
+        _ => Some(palette::parse(text).map_err(|e| format!("{}: {}", key, e))?),
+    };
+    match field {
+        "fg" => style.fg = color,
+        "bg" => style.bg = color,
+        _ => return Err(format!("Unknown theme setting {}", key)),
+    }
+    Ok(())
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    /// A built in theme by name, or else the theme file at that path.
+    fn from_str(s: &str) -> Result<Theme, String> {
+        match Theme::builtin(s) {
+            Some(theme) => Ok(theme),
+            None if Path::new(s).exists() => Theme::load(Path::new(s)),
+            None => Err(format!(
+                "Unknown theme {}, expected a theme file or one of: {}",
+                s,
+                NAMES.join(", ")
+            )),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! The `--trace` file: a CSV line for every ant and step, for analysing trajectories with other
+//! tools. Files ending in `.gz` are compressed.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use gzip::GzipWriter;
+use simulation::{Observer, Simulation};
+
+/// Where the lines go, compressed or not.
+enum Out {
+    Plain(BufWriter<File>),
+    /// Boxed, the writer carries its tables around.
+    Gzip(Box<GzipWriter<BufWriter<File>>>),
+    /// Finished, nothing more goes in.
+    Closed,
+}
+
+/// A trace file being written.
+///
+/// As an observer it writes a line for every step and the rest of the file once the run is
+/// finished, keeping the first error for `error`.
+pub struct Trace {
+    path: PathBuf,
+    out: Out,
+    error: Option<String>,
+}
+
+impl Trace {
+    /// Create the trace file at `path` and write the header line.
+    pub fn create(path: &Path) -> Result<Trace, String> {
+        let error = |e| format!("Could not write {}: {}", path.display(), e);
+        let file = BufWriter::new(File::create(path).map_err(error)?);
+        let out = if path.extension().is_some_and(|ext| ext == "gz") {
+            Out::Gzip(Box::new(GzipWriter::new(file).map_err(error)?))
+        } else {
+            Out::Plain(file)
+        };
+        let mut trace = Trace {
+            path: path.to_path_buf(),
+            out,
+            error: None,
+        };
+        trace.write(b"step,ant,x,y,heading,color\n")?;
+        Ok(trace)
+    }
+
+    /// Write a line for every ant of `sim` after the step it just took: where the ant is now,
+    /// its heading, and the color of the cell it is on.
+    pub fn record(&mut self, sim: &Simulation) -> Result<(), String> {
+        let mut lines = String::new();
+        for (i, ant) in sim.ants.iter().enumerate() {
+            let color = sim.cell(ant.x as usize, ant.y as usize).state.0;
+            lines.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                sim.steps,
+                i,
+                ant.x,
+                ant.y,
+                ant.heading.name(),
+                color
+            ));
+        }
+        self.write(lines.as_bytes())
+    }
+
+    /// Write the rest of the file. Nothing can be recorded afterwards.
+    pub fn finish(&mut self) -> Result<(), String> {
+        let result = match std::mem::replace(&mut self.out, Out::Closed) {
+            Out::Plain(mut out) => out.flush(),
+            Out::Gzip(out) => out.finish().and_then(|mut out| out.flush()),
+            Out::Closed => Ok(()),
+        };
+        result.map_err(|e| format!("Could not write {}: {}", self.path.display(), e))
+    }
+
+    /// What went wrong while observing a run, if anything.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let result = match self.out {
+            Out::Plain(ref mut out) => out.write_all(bytes),
+            Out::Gzip(ref mut out) => out.write_all(bytes),
+            Out::Closed => return Err(format!("{} is already finished", self.path.display())),
+        };
+        result.map_err(|e| format!("Could not write {}: {}", self.path.display(), e))
+    }
+}
+
+impl Observer for Trace {
+    fn on_step(&mut self, sim: &Simulation) {
+        if self.error.is_none() {
+            self.error = self.record(sim).err();
+        }
+    }
+
+    fn on_finished(&mut self, _sim: &Simulation) {
+        if self.error.is_none() {
+            self.error = self.finish().err();
+        }
+    }
+}
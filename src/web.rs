@@ -0,0 +1,222 @@
+//! Entry points for the web build.
+//!
+//! The functions here are exported unmangled so a page can drive the simulation through the
+//! plain WebAssembly API, without any bindings generator. There is a single simulation per
+//! instance; `ant_new` replaces it, set up as the setters like `ant_rule` last asked. See
+//! `web/index.html` for the JavaScript side.
+
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use palette;
+use rng::Rng;
+use rule::{self, Rule};
+use simulation::{Direction, Simulation};
+
+struct Web {
+    sim: Simulation,
+    /// RGBA pixels handed out to the canvas, one per cell.
+    pixels: Vec<u8>,
+}
+
+/// What the next `ant_new` starts with besides the grid size, like the flags of the same names.
+struct Setup {
+    rule: Arc<dyn Rule>,
+    /// Cell of the first ant, the middle of the grid if not given.
+    start: Option<(u32, u32)>,
+    heading: Direction,
+    ants: u32,
+}
+
+impl Default for Setup {
+    fn default() -> Setup {
+        Setup {
+            rule: rule::classic(),
+            start: None,
+            heading: Direction::Right,
+            ants: 1,
+        }
+    }
+}
+
+/// Headings by the number the page passes for them, in the order `--heading` lists them.
+const HEADINGS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+];
+
+thread_local! {
+    static WEB: RefCell<Option<Web>> = const { RefCell::new(None) };
+    static SETUP: RefCell<Option<Setup>> = const { RefCell::new(None) };
+    /// Text the page writes for setters to read.
+    static BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+fn with_setup<T, F: FnOnce(&mut Setup) -> T>(f: F) -> T {
+    SETUP.with(|setup| f(setup.borrow_mut().get_or_insert_with(Setup::default)))
+}
+
+fn with<T, F: FnOnce(&mut Web) -> T>(default: T, f: F) -> T {
+    WEB.with(|web| web.borrow_mut().as_mut().map_or(default, f))
+}
+
+/// Start a new simulation on a black grid of `width` by `height` cells.
+///
+/// With a `density` above zero that fraction of the cells starts out white, picked with `seed`.
+/// Ants past the first stand on cells picked with `seed` as well.
+#[no_mangle]
+pub extern "C" fn ant_new(width: u32, height: u32, density: f64, seed: u32) {
+    let (width, height) = (width.clamp(1, 0xFFFF), height.clamp(1, 0xFFFF));
+    let mut sim = Simulation::new(width as u16, height as u16);
+    let mut rng = Rng::new(u64::from(seed));
+    if density > 0.0 {
+        sim.fill_random(density, &mut rng);
+    }
+    with_setup(|setup| {
+        // A single rule always has few enough colors.
+        sim.set_rule(setup.rule.clone()).ok();
+        let ant = &mut sim.ants[0];
+        if let Some((x, y)) = setup.start {
+            // Pulled back onto the grid like `--start`.
+            ant.x = i64::from(x.min(width - 1));
+            ant.y = i64::from(y.min(height - 1));
+        }
+        ant.heading = setup.heading;
+        for _ in 1..setup.ants {
+            let y = rng.below(u64::from(height)) as i64;
+            let x = rng.below(u64::from(width)) as i64;
+            sim.add_ant(x, y, setup.heading);
+        }
+    });
+    let pixels = vec![0; sim.width() * sim.height() * 4];
+    WEB.with(|web| *web.borrow_mut() = Some(Web { sim, pixels }));
+}
+
+/// Room for `len` bytes of text for a setter like `ant_rule` to read. The pointer stays valid
+/// until the next call.
+#[no_mangle]
+pub extern "C" fn ant_buffer(len: u32) -> *mut u8 {
+    BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        buffer.clear();
+        buffer.resize(len as usize, 0);
+        buffer.as_mut_ptr()
+    })
+}
+
+/// Start the next runs with the rule written as the `len` bytes at `spec`, read like `--rule`.
+/// Returns whether it is a rule; if not the rule stays as it was.
+///
+/// # Safety
+///
+/// `spec` has to point to `len` readable bytes, like those from `ant_buffer`.
+#[no_mangle]
+pub unsafe extern "C" fn ant_rule(spec: *const u8, len: u32) -> bool {
+    let spec = std::slice::from_raw_parts(spec, len as usize);
+    match std::str::from_utf8(spec).map_err(|e| e.to_string()).and_then(rule::parse) {
+        Ok(rule) => {
+            with_setup(|setup| setup.rule = rule);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Start the first ant of the next runs on column `x` and row `y`, heading up, down, left or
+/// right for a `heading` of 0 to 3. Returns `false`, changing nothing, for other headings.
+#[no_mangle]
+pub extern "C" fn ant_start(x: u32, y: u32, heading: u32) -> bool {
+    match HEADINGS.get(heading as usize) {
+        Some(&heading) => {
+            with_setup(|setup| {
+                setup.start = Some((x, y));
+                setup.heading = heading;
+            });
+            true
+        }
+        None => false,
+    }
+}
+
+/// Start the next runs with `count` ants, at least one.
+#[no_mangle]
+pub extern "C" fn ant_count(count: u32) {
+    with_setup(|setup| setup.ants = count.max(1));
+}
+
+/// Advance up to `steps` steps, returning how many were taken before the ant left the grid.
+#[no_mangle]
+pub extern "C" fn ant_step(steps: u32) -> u32 {
+    with(0, |web| {
+        let mut taken = 0;
+        while taken < steps && web.sim.step().is_ok() {
+            taken += 1;
+        }
+        taken
+    })
+}
+
+/// Total number of steps taken. A float, since JavaScript can't receive a `u64` as a number.
+#[no_mangle]
+pub extern "C" fn ant_steps() -> f64 {
+    with(0.0, |web| web.sim.steps as f64)
+}
+
+#[no_mangle]
+pub extern "C" fn ant_width() -> u32 {
+    with(0, |web| web.sim.width() as u32)
+}
+
+#[no_mangle]
+pub extern "C" fn ant_height() -> u32 {
+    with(0, |web| web.sim.height() as u32)
+}
+
+/// Paint the grid and return a pointer to `width * height` RGBA pixels in linear memory.
+///
+/// The pointer stays valid until the next call to `ant_new`.
+#[no_mangle]
+pub extern "C" fn ant_pixels() -> *const u8 {
+    with(std::ptr::null(), |web| {
+        let colors = palette::indexed(&web.sim);
+        let indices = palette::indices(&web.sim);
+        for (&index, pixel) in indices.iter().zip(web.pixels.chunks_mut(4)) {
+            pixel[..3].copy_from_slice(&colors[index as usize]);
+            pixel[3] = 0xFF;
+        }
+        web.pixels.as_ptr()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hand `text` to `ant_rule` through the buffer, the way the page does.
+    fn set_rule(text: &str) -> bool {
+        let buffer = ant_buffer(text.len() as u32);
+        unsafe {
+            std::ptr::copy_nonoverlapping(text.as_ptr(), buffer, text.len());
+            ant_rule(buffer, text.len() as u32)
+        }
+    }
+
+    #[test]
+    fn setters_shape_the_next_run() {
+        assert!(set_rule("LLRR"));
+        assert!(!set_rule("LXR"));
+        assert!(ant_start(3, 40, 0));
+        assert!(!ant_start(3, 4, 4));
+        ant_count(3);
+        ant_new(10, 10, 0.0, 1);
+
+        with((), |web| {
+            assert_eq!(web.sim.rule_for(0).to_string(), "LLRR");
+            assert_eq!(web.sim.ants.len(), 3);
+            let ant = &web.sim.ants[0];
+            assert_eq!((ant.x, ant.y, ant.heading), (3, 9, Direction::Up));
+        });
+    }
+}
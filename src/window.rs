@@ -0,0 +1,261 @@
+//! The `window` command: the grid in an X11 window of its own, one pixel per cell.
+//!
+//! Keys work as they do in the terminal as far as they make sense here. Zooming shows each cell
+//! as a square of pixels instead, doubling its side every time, and the arrows pan the view.
+
+use std::time::Instant;
+
+use backend::Key;
+use keys::Action;
+use langton_ant::palette;
+use langton_ant::simulation::{LeftArena, Simulation};
+use langton_ant::stats::Stats;
+use x11::{Event, Window};
+use {Finish, Options};
+
+/// Color of the window around the grid.
+const OUTSIDE: [u8; 3] = [0x18, 0x18, 0x18];
+
+/// Largest side of a cell in pixels.
+const MAX_SCALE: u16 = 64;
+
+/// A run shown in a window.
+struct Viewer<'a> {
+    window: Window,
+    sim: Simulation,
+    options: &'a Options,
+    /// The grid to start over from with `--on-finish loop`.
+    initial: Option<Simulation>,
+    /// Cell in the top left corner of the window, which can be off the grid.
+    view: (i64, i64),
+    /// Side of a cell in pixels.
+    scale: u16,
+    paused: bool,
+    speed: f64,
+    /// How the last step failed, if it did.
+    left_arena: Option<LeftArena>,
+    /// Whether the window shows something else than the grid now.
+    dirty: bool,
+    /// Whether the run started over since this was last cleared.
+    restarted: bool,
+    started: Instant,
+}
+
+/// Run the `window` command until the window is closed or the run is over, then write what
+/// `options` ask for at the end of a run and return how far it got.
+pub fn run(options: &Options) -> Result<Stats, String> {
+    let (w, h) = options.size.unwrap_or((512, 512));
+    let mut sim = ::setup(w, h, options)?;
+    let initial = if options.on_finish == Finish::Loop {
+        Some(sim.clone())
+    } else {
+        None
+    };
+    let mut cycles = ::watch_cycles(options, &mut sim);
+    let trace = ::open_trace(options, &mut sim);
+
+    let size = |side: usize| side.min(usize::from(u16::MAX)) as u16;
+    let window = Window::open("Langton's ant", size(sim.width()), size(sim.height()))?;
+    let mut viewer = Viewer {
+        window,
+        sim,
+        options,
+        initial,
+        view: (0, 0),
+        scale: 1,
+        paused: false,
+        speed: options.speed,
+        left_arena: None,
+        dirty: true,
+        restarted: false,
+        started: Instant::now(),
+    };
+    viewer.center();
+
+    let mut next_step = Instant::now();
+    let mut last_frame: Option<Instant> = None;
+    'run: loop {
+        let mut step_once = false;
+        for event in viewer.window.poll()? {
+            match event {
+                Event::Key(key) => match viewer.key(key) {
+                    Some(Action::Quit) => break 'run,
+                    Some(Action::Step) => step_once = true,
+                    _ => {}
+                },
+                Event::Resized(..) | Event::Exposed => viewer.dirty = true,
+                Event::Closed => break 'run,
+            }
+        }
+
+        let frame_start = Instant::now();
+        if frame_start > next_step + ::MAX_LAG {
+            next_step = frame_start;
+        }
+        let mut taken = 0;
+        let (speed, paused) = (viewer.speed, viewer.paused);
+        while ::step_due(speed, paused, taken, step_once, frame_start, &mut next_step) {
+            if !viewer.advance() {
+                break 'run;
+            }
+            if viewer.restarted {
+                // Copies of a run start without observers.
+                viewer.restarted = false;
+                cycles = ::watch_cycles(options, &mut viewer.sim);
+                if let Some(ref trace) = trace {
+                    viewer.sim.observe(trace.clone());
+                }
+            }
+            taken += 1;
+        }
+        viewer.dirty |= taken > 0;
+
+        if viewer.dirty && last_frame.is_none_or(|last| last.elapsed() >= ::FRAME_INTERVAL) {
+            viewer.draw()?;
+            last_frame = Some(Instant::now());
+        }
+        let now = Instant::now();
+        if viewer.paused {
+            std::thread::sleep(::PAUSED_POLL);
+            next_step = now;
+        } else if viewer.speed > 0.0 && next_step > now {
+            std::thread::sleep((next_step - now).min(::PAUSED_POLL));
+        }
+    }
+
+    ::finish_trace(trace, &viewer.sim);
+    if let Some(cycle) = ::found_cycle(&cycles) {
+        eprintln!("{}", ::describe_cycle(cycle));
+    }
+    let stats = viewer.sim.stats(viewer.started.elapsed());
+    if let Some(left) = viewer.left_arena {
+        eprintln!("{}", left);
+    }
+    for e in ::write_outputs(&viewer.sim, options) {
+        eprintln!("{}", e);
+    }
+    Ok(stats)
+}
+
+impl<'a> Viewer<'a> {
+    /// Act on `key`, returning what it was bound to.
+    fn key(&mut self, key: Key) -> Option<Action> {
+        let action = self.options.keys.action(key);
+        match action {
+            Some(Action::Pause) => self.paused = !self.paused,
+            Some(Action::StepBack) if self.sim.step_back() => {
+                self.left_arena = None;
+                self.dirty = true;
+            }
+            // Unlimited stays unlimited.
+            Some(Action::Faster) => self.speed *= 2.0,
+            Some(Action::Slower) if self.speed == 0.0 => {
+                let rate = self.sim.steps as f64 / self.started.elapsed().as_secs_f64();
+                self.speed = (rate / 2.0).max(1.0)
+            }
+            Some(Action::Slower) => self.speed = (self.speed / 2.0).max(::MIN_SPEED),
+            Some(Action::PanUp) => self.pan(0, -1),
+            Some(Action::PanDown) => self.pan(0, 1),
+            Some(Action::PanLeft) => self.pan(-1, 0),
+            Some(Action::PanRight) => self.pan(1, 0),
+            Some(Action::ZoomIn) if self.scale < MAX_SCALE => self.zoom(self.scale * 2),
+            Some(Action::ZoomOut) if self.scale > 1 => self.zoom(self.scale / 2),
+            _ => {}
+        }
+        action
+    }
+
+    /// Take the next step, dealing with the run being over. Returns `false` when the window
+    /// should close.
+    fn advance(&mut self) -> bool {
+        let origin = self.sim.origin();
+        let over = self.options.steps.is_some_and(|limit| self.sim.steps >= limit);
+        let result = if over { Ok(()) } else { self.sim.step() };
+        if let Err(left) = result {
+            self.left_arena = Some(left);
+        }
+        if over || result.is_err() {
+            match self.options.on_finish {
+                Finish::Quit => return false,
+                Finish::Freeze => self.paused = true,
+                Finish::Loop => {
+                    if let Some(ref initial) = self.initial {
+                        self.sim = initial.clone();
+                    }
+                    self.left_arena = None;
+                    self.restarted = true;
+                    self.center();
+                }
+            }
+            return true;
+        }
+        // The grid grew to the left or the top, keep the view on the same cells.
+        let (x, y) = self.sim.origin();
+        self.view.0 += x - origin.0;
+        self.view.1 += y - origin.1;
+        true
+    }
+
+    /// How many cells fit across and down the window.
+    fn cells_shown(&self) -> (i64, i64) {
+        let (width, height) = self.window.size();
+        let scale = u32::from(self.scale);
+        (
+            i64::from(u32::from(width).div_ceil(scale)),
+            i64::from(u32::from(height).div_ceil(scale)),
+        )
+    }
+
+    /// Put the middle of the grid in the middle of the window.
+    fn center(&mut self) {
+        let (columns, rows) = self.cells_shown();
+        self.view = (
+            (self.sim.width() as i64 - columns) / 2,
+            (self.sim.height() as i64 - rows) / 2,
+        );
+        self.dirty = true;
+    }
+
+    /// Move the view by a tenth of the window, `dx` and `dy` times.
+    fn pan(&mut self, dx: i64, dy: i64) {
+        let (columns, rows) = self.cells_shown();
+        self.view.0 += dx * (columns / 10).max(1);
+        self.view.1 += dy * (rows / 10).max(1);
+        self.dirty = true;
+    }
+
+    /// Show cells `scale` pixels wide, keeping the cell in the middle of the window there.
+    fn zoom(&mut self, scale: u16) {
+        let (columns, rows) = self.cells_shown();
+        let middle = (self.view.0 + columns / 2, self.view.1 + rows / 2);
+        self.scale = scale;
+        let (columns, rows) = self.cells_shown();
+        self.view = (middle.0 - columns / 2, middle.1 - rows / 2);
+        self.dirty = true;
+    }
+
+    fn draw(&mut self) -> Result<(), String> {
+        let (width, height) = self.window.size();
+        let colors = palette::indexed(&self.sim);
+        let indices = palette::indices(&self.sim);
+        let (grid_width, grid_height) = (self.sim.width() as i64, self.sim.height() as i64);
+        let scale = i64::from(self.scale);
+
+        let mut pixels = Vec::with_capacity(usize::from(width) * usize::from(height));
+        for py in 0..i64::from(height) {
+            let y = self.view.1 + py / scale;
+            for px in 0..i64::from(width) {
+                let x = self.view.0 + px / scale;
+                let inside = x >= 0 && y >= 0 && x < grid_width && y < grid_height;
+                pixels.push(if inside {
+                    colors[usize::from(indices[(y * grid_width + x) as usize])]
+                } else {
+                    OUTSIDE
+                });
+            }
+        }
+        self.window.draw(&pixels, width)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
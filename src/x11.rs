@@ -0,0 +1,664 @@
+//! Just enough of the X11 protocol to show the grid in a window of its own, for the `window`
+//! command of the `gui` feature.
+//!
+//! The X server is spoken to over its socket directly rather than through Xlib, so nothing
+//! has to be installed to build it. Only the few requests and events this needs are spoken:
+//! one window is opened, images are put into it, and key presses, resizes and the window being
+//! closed are read back. Keys are looked up in the core keyboard mapping, as there is no XKB,
+//! so layouts switched through XKB alone aren't followed. Only TrueColor screens with 32 bits
+//! per pixel are supported, which is what every desktop runs at nowadays.
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use backend::Key;
+
+/// Something that happened to the window.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    Key(Key),
+    /// The window is now this many pixels wide and high.
+    Resized(u16, u16),
+    /// Part of the window has to be drawn again.
+    Exposed,
+    /// The window manager was asked to close the window.
+    Closed,
+}
+
+/// The connection to the server, over TCP or a Unix socket.
+enum Socket {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Socket {
+    fn try_clone(&self) -> io::Result<Socket> {
+        match *self {
+            Socket::Tcp(ref stream) => stream.try_clone().map(Socket::Tcp),
+            #[cfg(unix)]
+            Socket::Unix(ref stream) => stream.try_clone().map(Socket::Unix),
+        }
+    }
+}
+
+impl Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Socket::Tcp(ref mut stream) => stream.read(buf),
+            #[cfg(unix)]
+            Socket::Unix(ref mut stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Socket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Socket::Tcp(ref mut stream) => stream.write(buf),
+            #[cfg(unix)]
+            Socket::Unix(ref mut stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Socket::Tcp(ref mut stream) => stream.flush(),
+            #[cfg(unix)]
+            Socket::Unix(ref mut stream) => stream.flush(),
+        }
+    }
+}
+
+/// Where the server of a `DISPLAY` like `:0`, `host:1.0` or an XQuartz socket path listens.
+#[derive(Clone, Debug, PartialEq)]
+enum Address {
+    Tcp(String, u16),
+    Unix(PathBuf),
+}
+
+/// The address of `display`, its number as written in the authority file, and the screen.
+fn parse_display(display: &str) -> Result<(Address, String, usize), String> {
+    let error = || format!("Invalid DISPLAY {:?}", display);
+    let colon = display.rfind(':').ok_or_else(error)?;
+    let (host, rest) = (&display[..colon], &display[colon + 1..]);
+    let mut parts = rest.splitn(2, '.');
+    let number = parts.next().unwrap_or("");
+    let port: u16 = number.parse().map_err(|_| error())?;
+    let screen = match parts.next() {
+        Some(screen) => screen.parse().map_err(|_| error())?,
+        None => 0,
+    };
+    let address = if display.starts_with('/') {
+        // XQuartz hands out the path of its socket.
+        Address::Unix(PathBuf::from(display))
+    } else if host.is_empty() || host == "unix" {
+        Address::Unix(PathBuf::from(format!("/tmp/.X11-unix/X{}", port)))
+    } else {
+        let port = port.checked_add(6000).ok_or_else(error)?;
+        Address::Tcp(host.to_string(), port)
+    };
+    Ok((address, number.to_string(), screen))
+}
+
+fn connect(address: &Address) -> io::Result<Socket> {
+    match *address {
+        Address::Tcp(ref host, port) => TcpStream::connect((host.as_str(), port)).map(|stream| {
+            // Every frame is a few requests, which shouldn't wait for each other.
+            let _ = stream.set_nodelay(true);
+            Socket::Tcp(stream)
+        }),
+        #[cfg(unix)]
+        Address::Unix(ref path) => UnixStream::connect(path).map(Socket::Unix),
+        #[cfg(not(unix))]
+        Address::Unix(_) => Err(io::Error::other("no Unix sockets here")),
+    }
+}
+
+/// An entry of the authority file: address family, address, display number, name and data.
+type Authority = (u16, Vec<u8>, String, Vec<u8>, Vec<u8>);
+
+/// Authority file entries for a host by name, and for any host.
+const FAMILY_LOCAL: u16 = 256;
+const FAMILY_WILD: u16 = 65535;
+
+/// The entries of an authority file like `~/.Xauthority`, as far as they could be read.
+fn parse_authority(bytes: &[u8]) -> Vec<Authority> {
+    let mut entries = Vec::new();
+    let mut rest = bytes;
+    let field = |rest: &mut &[u8]| -> Option<Vec<u8>> {
+        let len = usize::from(u16::from_be_bytes([*rest.first()?, *rest.get(1)?]));
+        let value = rest.get(2..2 + len)?.to_vec();
+        *rest = &rest[2 + len..];
+        Some(value)
+    };
+    while rest.len() >= 2 {
+        let family = u16::from_be_bytes([rest[0], rest[1]]);
+        rest = &rest[2..];
+        let entry = (|| {
+            let address = field(&mut rest)?;
+            let number = String::from_utf8(field(&mut rest)?).ok()?;
+            Some((family, address, number, field(&mut rest)?, field(&mut rest)?))
+        })();
+        match entry {
+            Some(entry) => entries.push(entry),
+            None => break,
+        }
+    }
+    entries
+}
+
+/// The name and data of the cookie for display `number` on this host, if there is one.
+fn cookie(number: &str) -> (Vec<u8>, Vec<u8>) {
+    let path = match env::var_os("XAUTHORITY") {
+        Some(path) => PathBuf::from(path),
+        None => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".Xauthority"),
+            None => return (Vec::new(), Vec::new()),
+        },
+    };
+    let entries = fs::read(path).map(|bytes| parse_authority(&bytes)).unwrap_or_default();
+    let host = hostname();
+    entries
+        .into_iter()
+        .filter(|(_, _, entry, name, _)| {
+            (entry.is_empty() || entry == number) && name == b"MIT-MAGIC-COOKIE-1"
+        })
+        // An entry for this host, else one for any host, else whichever there is.
+        .min_by_key(|&(family, ref address, _, _, _)| match family {
+            FAMILY_LOCAL if Some(address) == host.as_ref() => 0,
+            FAMILY_WILD => 1,
+            _ => 2,
+        })
+        .map(|(_, _, _, name, data)| (name, data))
+        .unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn hostname() -> Option<Vec<u8>> {
+    let mut name = [0u8; 256];
+    // Safe: the buffer is as long as it is said to be.
+    let failed = unsafe { ::libc::gethostname(name.as_mut_ptr() as *mut _, name.len()) } != 0;
+    if failed {
+        return None;
+    }
+    let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    Some(name[..len].to_vec())
+}
+
+#[cfg(not(unix))]
+fn hostname() -> Option<Vec<u8>> {
+    None
+}
+
+/// `bytes` followed by zeros up to a multiple of four.
+fn padded(bytes: &[u8]) -> Vec<u8> {
+    let mut out = bytes.to_vec();
+    out.resize(bytes.len().div_ceil(4) * 4, 0);
+    out
+}
+
+/// A request: `opcode`, the byte that comes with it and `body`, behind its length in words.
+fn request(opcode: u8, data: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![opcode, data];
+    out.extend_from_slice(&((body.len().div_ceil(4) + 1) as u16).to_le_bytes());
+    out.extend(padded(body));
+    out
+}
+
+/// The words of a request body, little endian as announced when connecting.
+fn words(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+/// The message of an error packet from the server.
+fn describe_error(packet: &[u8]) -> String {
+    format!("X error {} in request {}", packet[1], packet[10])
+}
+
+/// What the server said about itself and the screen used, out of its reply to connecting.
+#[derive(Clone, Debug, PartialEq)]
+struct Setup {
+    id_base: u32,
+    id_mask: u32,
+    /// Longest request the server takes, in bytes.
+    max_request: usize,
+    big_endian: bool,
+    min_keycode: u8,
+    max_keycode: u8,
+    root: u32,
+    depth: u8,
+    /// Masks of the red, green and blue bits in a pixel.
+    masks: [u32; 3],
+    width: u16,
+    height: u16,
+}
+
+/// Read the parts of the setup reply `data`, what follows its first eight bytes, that a window
+/// needs, for `screen`.
+fn parse_setup(data: &[u8], screen: usize) -> Result<Setup, String> {
+    let short = || "The X server's setup reply is too short".to_string();
+    if data.len() < 32 {
+        return Err(short());
+    }
+    let vendor = usize::from(u16_at(data, 16));
+    let screens = usize::from(data[20]);
+    let formats = usize::from(data[21]);
+    let mut at = 32 + vendor.div_ceil(4) * 4;
+    let format_list = data.get(at..at + formats * 8).ok_or_else(short)?;
+    at += formats * 8;
+    if screen >= screens {
+        return Err(format!("The X server has no screen {}", screen));
+    }
+
+    for index in 0..=screen {
+        let header = data.get(at..at + 40).ok_or_else(short)?;
+        let mut depth_at = at + 40;
+        let mut found = None;
+        for _ in 0..header[39] {
+            let depth = data.get(depth_at..depth_at + 8).ok_or_else(short)?;
+            let visuals = usize::from(u16_at(depth, 2));
+            for v in 0..visuals {
+                let visual_at = depth_at + 8 + v * 24;
+                let visual = data.get(visual_at..visual_at + 24).ok_or_else(short)?;
+                if u32_at(visual, 0) == u32_at(header, 32) {
+                    let masks = [u32_at(visual, 8), u32_at(visual, 12), u32_at(visual, 16)];
+                    found = Some((visual[4], masks));
+                }
+            }
+            depth_at += 8 + visuals * 24;
+        }
+        if index < screen {
+            at = depth_at;
+            continue;
+        }
+
+        let depth = header[38];
+        // TrueColor, with pixels of 32 bits.
+        let bits = format_list.chunks(8).find(|format| format[0] == depth).map(|f| f[1]);
+        let masks = match found {
+            Some((4, masks)) if bits == Some(32) => masks,
+            _ => {
+                return Err(format!(
+                    "Only TrueColor screens with 32 bits per pixel are supported, screen {} is \
+                     {} bits deep",
+                    screen, depth
+                ))
+            }
+        };
+        return Ok(Setup {
+            id_base: u32_at(data, 4),
+            id_mask: u32_at(data, 8),
+            max_request: usize::from(u16_at(data, 18)) * 4,
+            big_endian: data[22] == 1,
+            min_keycode: data[26],
+            max_keycode: data[27],
+            root: u32_at(header, 0),
+            depth,
+            masks,
+            width: u16_at(header, 20),
+            height: u16_at(header, 22),
+        });
+    }
+    Err(short())
+}
+
+/// The key `keysym` stands for, if it is one the key map knows.
+fn key(keysym: u32) -> Option<Key> {
+    match keysym {
+        0x20..=0x7e => Some(Key::Char(keysym as u8 as char)),
+        0xff51 => Some(Key::Left),
+        0xff52 => Some(Key::Up),
+        0xff53 => Some(Key::Right),
+        0xff54 => Some(Key::Down),
+        0xffbe..=0xffc9 => Some(Key::F((keysym - 0xffbe + 1) as u8)),
+        _ => None,
+    }
+}
+
+/// A window on the X server named by `DISPLAY`. It goes away with the connection when dropped.
+pub struct Window {
+    socket: Socket,
+    window: u32,
+    gc: u32,
+    setup: Setup,
+    /// Keysyms of every keycode from `setup.min_keycode` on, `per_keycode` each.
+    keysyms: Vec<u32>,
+    per_keycode: usize,
+    /// The atom of the message asking to close the window.
+    delete: u32,
+    /// Packets read from the server by a thread of their own.
+    packets: Receiver<io::Result<[u8; 32]>>,
+    width: u16,
+    height: u16,
+}
+
+impl Window {
+    /// Open a window called `title`, `width` by `height` pixels or as much of that as the
+    /// screen has room for.
+    pub fn open(title: &str, width: u16, height: u16) -> Result<Window, String> {
+        let display = env::var("DISPLAY")
+            .map_err(|_| "No X display to open a window on, DISPLAY isn't set".to_string())?;
+        let (address, number, screen) = parse_display(&display)?;
+        let error = |e: io::Error| format!("Could not talk to the X server at {}: {}", display, e);
+        let mut socket = connect(&address).map_err(error)?;
+
+        let (name, data) = cookie(&number);
+        let mut hello = vec![b'l', 0, 11, 0, 0, 0];
+        hello.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        hello.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        hello.extend_from_slice(&[0, 0]);
+        hello.extend(padded(&name));
+        hello.extend(padded(&data));
+        socket.write_all(&hello).map_err(error)?;
+
+        let mut header = [0; 8];
+        socket.read_exact(&mut header).map_err(error)?;
+        let mut data = vec![0; usize::from(u16_at(&header, 6)) * 4];
+        socket.read_exact(&mut data).map_err(error)?;
+        if header[0] != 1 {
+            let len = usize::from(header[1]).min(data.len());
+            let reason = String::from_utf8_lossy(&data[..len]);
+            let reason = reason.trim();
+            return Err(format!("The X server at {} refused the connection: {}", display, reason));
+        }
+        let setup = parse_setup(&data, screen)?;
+
+        // Everything with a reply comes first, before there are events to tell apart from it.
+        let protocols = intern(&mut socket, "WM_PROTOCOLS")?;
+        let delete = intern(&mut socket, "WM_DELETE_WINDOW")?;
+        let (first, last) = (setup.min_keycode, setup.max_keycode);
+        send(&mut socket, &request(101, 0, &[first, last - first + 1, 0, 0]))?;
+        let (header, body) = reply(&mut socket)?;
+        let per_keycode = usize::from(header[1]);
+        let keysyms = body.chunks(4).map(|word| u32_at(word, 0)).collect();
+
+        let shift = setup.id_mask.trailing_zeros();
+        let (window, gc) = (setup.id_base | 1 << shift, setup.id_base | 2 << shift);
+        let (width, height) = (width.min(setup.width).max(1), height.min(setup.height).max(1));
+        // An InputOutput window with a black background, told about key presses, exposure and
+        // changes to its size.
+        let mut body = words(&[window, setup.root, 0]);
+        body.extend_from_slice(&width.to_le_bytes());
+        body.extend_from_slice(&height.to_le_bytes());
+        body.extend_from_slice(&[0, 0, 1, 0]);
+        body.extend(words(&[0, 0x0802, 0, 0x0002_8001]));
+        send(&mut socket, &request(1, setup.depth, &body))?;
+        // WM_NAME as a STRING, and WM_PROTOCOLS as the ATOM of closing the window.
+        let mut body = words(&[window, 39, 31, 8, title.len() as u32]);
+        body.extend_from_slice(title.as_bytes());
+        send(&mut socket, &request(18, 0, &body))?;
+        send(&mut socket, &request(18, 0, &words(&[window, protocols, 4, 32, 1, delete])))?;
+        send(&mut socket, &request(55, 0, &words(&[gc, window, 0])))?;
+        send(&mut socket, &request(8, 0, &words(&[window])))?;
+
+        let (sender, packets) = mpsc::channel();
+        let mut reader = socket.try_clone().map_err(error)?;
+        thread::spawn(move || loop {
+            let mut packet = [0; 32];
+            let read = reader.read_exact(&mut packet).and_then(|_| {
+                // Nothing sent from here on asks for a reply, but skip over any that come.
+                if packet[0] == 1 {
+                    let mut rest = vec![0; u32_at(&packet, 4) as usize * 4];
+                    reader.read_exact(&mut rest)?;
+                }
+                Ok(packet)
+            });
+            let failed = read.is_err();
+            if sender.send(read).is_err() || failed {
+                return;
+            }
+        });
+        Ok(Window {
+            socket,
+            window,
+            gc,
+            setup,
+            keysyms,
+            per_keycode,
+            delete,
+            packets,
+            width,
+            height,
+        })
+    }
+
+    /// The size of the window in pixels.
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    /// The events that came in since the last call.
+    pub fn poll(&mut self) -> Result<Vec<Event>, String> {
+        let mut events = Vec::new();
+        while let Ok(packet) = self.packets.try_recv() {
+            let packet = packet.map_err(lost)?;
+            match packet[0] & 0x7f {
+                0 => return Err(describe_error(&packet)),
+                // A key press.
+                2 => {
+                    let shift = u16_at(&packet, 28) & 1 == 1;
+                    if let Some(key) = self.keysym(packet[1], shift).and_then(key) {
+                        events.push(Event::Key(key));
+                    }
+                }
+                // Exposure, once the last of a series of rectangles came in.
+                12 if u16_at(&packet, 16) == 0 => events.push(Event::Exposed),
+                // The window was resized or moved.
+                22 => {
+                    let size = (u16_at(&packet, 20), u16_at(&packet, 22));
+                    if size != (self.width, self.height) {
+                        self.width = size.0;
+                        self.height = size.1;
+                        events.push(Event::Resized(size.0, size.1));
+                    }
+                }
+                // The window manager asking to close the window, or the window being gone.
+                33 if u32_at(&packet, 12) == self.delete => events.push(Event::Closed),
+                17 => events.push(Event::Closed),
+                _ => {}
+            }
+        }
+        Ok(events)
+    }
+
+    /// Show `pixels` in the top left corner, `width` RGB pixels per row, row by row.
+    pub fn draw(&mut self, pixels: &[[u8; 3]], width: u16) -> Result<(), String> {
+        let width = usize::from(width).max(1);
+        let [red, green, blue] = self.setup.masks;
+        let channel = |value: u8, mask| (u32::from(value) << 24 >> u32::leading_zeros(mask)) & mask;
+        // Images go in strips of as many rows as fit into a request.
+        let rows = ((self.setup.max_request - 24) / (width * 4)).max(1);
+        for (strip, chunk) in pixels.chunks(rows * width).enumerate() {
+            let mut body = words(&[self.window, self.gc]);
+            body.extend_from_slice(&(width as u16).to_le_bytes());
+            body.extend_from_slice(&((chunk.len() / width) as u16).to_le_bytes());
+            body.extend_from_slice(&0u16.to_le_bytes());
+            body.extend_from_slice(&((strip * rows) as u16).to_le_bytes());
+            body.extend_from_slice(&[0, self.setup.depth, 0, 0]);
+            body.reserve(chunk.len() * 4);
+            for rgb in chunk {
+                let pixel = channel(rgb[0], red) | channel(rgb[1], green) | channel(rgb[2], blue);
+                if self.setup.big_endian {
+                    body.extend_from_slice(&pixel.to_be_bytes());
+                } else {
+                    body.extend_from_slice(&pixel.to_le_bytes());
+                }
+            }
+            // ZPixmap.
+            self.send(&request(72, 2, &body))?;
+        }
+        Ok(())
+    }
+
+    /// The keysym of `keycode`, the shifted one with `shift`.
+    fn keysym(&self, keycode: u8, shift: bool) -> Option<u32> {
+        let first = usize::from(keycode.checked_sub(self.setup.min_keycode)?) * self.per_keycode;
+        let syms = self.keysyms.get(first..first + self.per_keycode)?;
+        let plain = *syms.first()?;
+        match syms.get(1) {
+            Some(&shifted) if shift && shifted != 0 => Some(shifted),
+            // Letters often only list their lower case.
+            _ if shift && (0x61..=0x7a).contains(&plain) => Some(plain - 0x20),
+            _ => Some(plain),
+        }
+    }
+
+    fn send(&mut self, request: &[u8]) -> Result<(), String> {
+        send(&mut self.socket, request)
+    }
+}
+
+fn lost(e: io::Error) -> String {
+    format!("Lost the connection to the X server: {}", e)
+}
+
+fn send(socket: &mut Socket, request: &[u8]) -> Result<(), String> {
+    socket.write_all(request).map_err(lost)
+}
+
+/// The reply to the last request sent over `socket`, its first 32 bytes and the rest.
+fn reply(socket: &mut Socket) -> Result<([u8; 32], Vec<u8>), String> {
+    loop {
+        let mut header = [0; 32];
+        socket.read_exact(&mut header).map_err(lost)?;
+        match header[0] {
+            0 => return Err(describe_error(&header)),
+            1 => {
+                let mut body = vec![0; u32_at(&header, 4) as usize * 4];
+                socket.read_exact(&mut body).map_err(lost)?;
+                return Ok((header, body));
+            }
+            // Events can't be about the window before it is made.
+            _ => {}
+        }
+    }
+}
+
+/// The atom called `name`, made if it doesn't exist yet.
+fn intern(socket: &mut Socket, name: &str) -> Result<u32, String> {
+    let mut body = (name.len() as u16).to_le_bytes().to_vec();
+    body.extend_from_slice(&[0, 0]);
+    body.extend_from_slice(name.as_bytes());
+    send(socket, &request(16, 0, &body))?;
+    let (header, _) = reply(socket)?;
+    Ok(u32_at(&header, 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_displays() {
+        let unix = |path: &str| Address::Unix(PathBuf::from(path));
+        let displays = [
+            (":0", unix("/tmp/.X11-unix/X0"), "0", 0),
+            ("unix:1.2", unix("/tmp/.X11-unix/X1"), "1", 2),
+            ("localhost:10.0", Address::Tcp("localhost".to_string(), 6010), "10", 0),
+            ("/tmp/launch-x/org.xquartz:0", unix("/tmp/launch-x/org.xquartz:0"), "0", 0),
+        ];
+        for &(display, ref address, number, screen) in &displays {
+            let parsed = parse_display(display);
+            assert_eq!(parsed, Ok((address.clone(), number.to_string(), screen)), "{}", display);
+        }
+        for display in &["", "0", ":", ":x", "host:0.x", "host:65000"] {
+            assert!(parse_display(display).is_err(), "{}", display);
+        }
+    }
+
+    #[test]
+    fn reads_authority_files() {
+        let mut bytes = Vec::new();
+        for &(family, address, number, data) in
+            &[(FAMILY_LOCAL, "box", "0", [1, 2]), (FAMILY_WILD, "", "1", [3, 4])]
+        {
+            bytes.extend_from_slice(&u16::to_be_bytes(family));
+            let name = "MIT-MAGIC-COOKIE-1";
+            for field in &[address.as_bytes(), number.as_bytes(), name.as_bytes(), &data] {
+                bytes.extend_from_slice(&(field.len() as u16).to_be_bytes());
+                bytes.extend_from_slice(field);
+            }
+        }
+        let entries = parse_authority(&bytes);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0, FAMILY_LOCAL);
+        assert_eq!(entries[0].1, b"box");
+        assert_eq!(entries[1].2, "1");
+        assert_eq!(entries[1].4, vec![3, 4]);
+        // A cut off entry is left out.
+        assert_eq!(parse_authority(&bytes[..bytes.len() - 1]).len(), 1);
+    }
+
+    /// A setup reply for a screen of `depth` bits with pixels of `bits`.
+    fn setup_reply(depth: u8, bits: u8) -> Vec<u8> {
+        let mut data = words(&[0, 0x0020_0000, 0x001f_ffff, 0]);
+        data.extend_from_slice(&[4, 0, 0xff, 0xff, 1, 1, 0, 0, 32, 32, 8, 255, 0, 0, 0, 0]);
+        data.extend_from_slice(b"fake");
+        data.extend_from_slice(&[depth, bits, 32, 0, 0, 0, 0, 0]);
+        data.extend(words(&[0x100, 0x20, 0xff_ffff, 0, 0]));
+        data.extend(words(&[600 << 16 | 800, 0, 0x1_0001, 0x21]));
+        data.extend_from_slice(&[0, 0, depth, 1, depth, 0, 1, 0, 0, 0, 0, 0]);
+        data.extend(words(&[0x21, 0x0100_0804, 0xff_0000, 0xff00, 0xff, 0]));
+        data
+    }
+
+    #[test]
+    fn reads_setup_replies() {
+        let setup = parse_setup(&setup_reply(24, 32), 0).unwrap();
+        assert_eq!(
+            setup,
+            Setup {
+                id_base: 0x0020_0000,
+                id_mask: 0x001f_ffff,
+                max_request: 0x3_fffc,
+                big_endian: false,
+                min_keycode: 8,
+                max_keycode: 255,
+                root: 0x100,
+                depth: 24,
+                masks: [0xff_0000, 0xff00, 0xff],
+                width: 800,
+                height: 600,
+            }
+        );
+        assert!(parse_setup(&setup_reply(24, 32), 1).unwrap_err().contains("no screen 1"));
+        assert!(parse_setup(&setup_reply(24, 24), 0).unwrap_err().contains("TrueColor"));
+        let reply = setup_reply(24, 32);
+        for len in 0..reply.len() {
+            assert!(parse_setup(&reply[..len], 0).is_err(), "{}", len);
+        }
+    }
+
+    #[test]
+    fn pads_requests() {
+        assert_eq!(request(8, 0, &[1, 2, 3, 4]), [8, 0, 2, 0, 1, 2, 3, 4]);
+        assert_eq!(request(16, 0, &[1, 2, 3, 4, 5]), [16, 0, 3, 0, 1, 2, 3, 4, 5, 0, 0, 0]);
+    }
+
+    #[test]
+    fn maps_keysyms() {
+        assert_eq!(key(0x71), Some(Key::Char('q')));
+        assert_eq!(key(0x5a), Some(Key::Char('Z')));
+        assert_eq!(key(0xff52), Some(Key::Up));
+        assert_eq!(key(0xffbe), Some(Key::F(1)));
+        assert_eq!(key(0xffc9), Some(Key::F(12)));
+        // Shift.
+        assert_eq!(key(0xffe1), None);
+    }
+}
@@ -0,0 +1,235 @@
+//! Exact repeats of a whole run, found with a hash of the grid and the ants kept up to date as
+//! cells change rather than computed again every step.
+//!
+//! Every painted cell has a key made from its position and color, and the hash of the grid is
+//! all of them combined with xor, so a change of color only swaps one key for another. Cells
+//! are placed from the grid's first top left corner, so growing the grid changes nothing, and
+//! black cells have no key, like the endless black all around. The ants are hashed on top of
+//! that when asked, a few of them being cheaper to go through than to follow.
+//!
+//! Repeats are spotted with Brent's algorithm, which compares every hash with a single one
+//! taken further and further back, so nothing grows with the length of the run. Once a period
+//! is known, a copy of the run from the start finds where it first came around. Two different
+//! runs could share a hash, but with 64 bits that is left to chance.
+
+use std::sync::{Arc, Mutex};
+
+use simulation::{Background, Observer, Simulation, State};
+
+/// A repeat of the run: after `start`, the grid and the ants are back where they were every
+/// `period` steps, for good.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Cycle {
+    /// The step the run first reached a state it comes back to, when its cycle begins.
+    pub start: u64,
+    /// Steps it takes to come back.
+    pub period: u64,
+}
+
+/// The hash of the cells of a run, following it as an observer.
+pub struct Zobrist {
+    cells: u64,
+    /// `Simulation::origin` as the hash last saw it.
+    origin: (i64, i64),
+}
+
+impl Zobrist {
+    /// The hash of the cells of `sim` as they are now.
+    pub fn of(sim: &Simulation) -> Zobrist {
+        let origin = sim.origin();
+        let mut cells = 0;
+        for (y, row) in sim.rows().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                cells ^= cell_key(x as i64 - origin.0, y as i64 - origin.1, cell.state);
+            }
+        }
+        Zobrist { cells, origin }
+    }
+
+    /// The hash of the whole of `sim`, the run this hash followed: its cells, ants, and where
+    /// the background is in between two of its generations.
+    pub fn hash(&self, sim: &Simulation) -> u64 {
+        let mut hash = self.cells;
+        for (i, ant) in sim.ants.iter().enumerate() {
+            let (x, y) = (ant.x - self.origin.0, ant.y - self.origin.1);
+            let looks = (ant.heading as u64) << 32 | (ant.rule as u64) << 8 | u64::from(ant.state);
+            hash ^= mix(mix(mix(mix(i as u64) ^ x as u64) ^ y as u64) ^ looks);
+        }
+        if sim.background == Background::Life {
+            hash ^= mix(sim.steps % sim.background_every.max(1));
+        }
+        hash
+    }
+}
+
+impl Observer for Zobrist {
+    fn on_cell_changed(&mut self, x: usize, y: usize, from: State, to: State) {
+        let (x, y) = (x as i64 - self.origin.0, y as i64 - self.origin.1);
+        self.cells ^= cell_key(x, y, from) ^ cell_key(x, y, to);
+    }
+
+    fn on_grew(&mut self, sim: &Simulation) {
+        self.origin = sim.origin();
+    }
+}
+
+/// Watches a run for a state it was in before, as an observer.
+pub struct Cycles {
+    zobrist: Zobrist,
+    /// The run as it was when watching started, to find where the cycle begins.
+    initial: Simulation,
+    /// Brent's tortoise: the hash it is compared with, and the step it was taken at.
+    saved: (u64, u64),
+    /// Steps after `saved` at which it is taken again.
+    power: u64,
+    found: Option<Cycle>,
+}
+
+impl Cycles {
+    /// Start watching `sim` from the state it is in. Runs that don't follow from their state
+    /// alone, with noise, hooks or a schedule of rules, can't be watched.
+    pub fn watch(sim: &mut Simulation) -> Result<Arc<Mutex<Cycles>>, String> {
+        if sim.noise > 0.0 || sim.hooks.is_some() || !sim.schedule().is_empty() {
+            return Err(
+                "Cycles can only be found in runs without noise, scripts or a schedule"
+                    .to_string(),
+            );
+        }
+        let zobrist = Zobrist::of(sim);
+        let saved = (zobrist.hash(sim), sim.steps);
+        let cycles = Arc::new(Mutex::new(Cycles {
+            zobrist,
+            initial: sim.clone(),
+            saved,
+            power: 1,
+            found: None,
+        }));
+        sim.observe(cycles.clone());
+        Ok(cycles)
+    }
+
+    /// Start over from the state `sim` is in, after it was changed directly.
+    pub fn restart(&mut self, sim: &Simulation) {
+        self.zobrist = Zobrist::of(sim);
+        self.initial = sim.clone();
+        self.saved = (self.zobrist.hash(sim), sim.steps);
+        self.power = 1;
+        self.found = None;
+    }
+
+    /// The cycle, once found.
+    pub fn found(&self) -> Option<Cycle> {
+        self.found
+    }
+
+    /// The first step whose state comes back `period` steps later, going over the run again
+    /// with two copies that far apart. It is no later than `seen`, the step the repeat was seen
+    /// at, less a period.
+    fn start(&self, period: u64, seen: u64) -> u64 {
+        let mut early = self.initial.clone();
+        let mut late = self.initial.clone();
+        let early_zobrist = Arc::new(Mutex::new(Zobrist::of(&early)));
+        let late_zobrist = Arc::new(Mutex::new(Zobrist::of(&late)));
+        early.observe(early_zobrist.clone());
+        late.observe(late_zobrist.clone());
+        // The copies step the way the run did, so they can't fail where it didn't.
+        for _ in 0..period {
+            let _ = late.step();
+        }
+        let hash =
+            |zobrist: &Arc<Mutex<Zobrist>>, sim: &Simulation| zobrist.lock().unwrap().hash(sim);
+        while early.steps + period < seen
+            && hash(&early_zobrist, &early) != hash(&late_zobrist, &late)
+        {
+            let _ = early.step();
+            let _ = late.step();
+        }
+        early.steps
+    }
+}
+
+impl Observer for Cycles {
+    fn on_step(&mut self, sim: &Simulation) {
+        if self.found.is_some() {
+            return;
+        }
+        let hash = self.zobrist.hash(sim);
+        let (saved, at) = self.saved;
+        if sim.steps <= at {
+            // Back in time and forward again, the tortoise is ahead now.
+            self.saved = (hash, sim.steps);
+            self.power = 1;
+        } else if hash == saved {
+            let period = sim.steps - at;
+            self.found = Some(Cycle {
+                start: self.start(period, sim.steps),
+                period,
+            });
+        } else if sim.steps - at >= self.power {
+            self.saved = (hash, sim.steps);
+            self.power *= 2;
+        }
+    }
+
+    fn on_cell_changed(&mut self, x: usize, y: usize, from: State, to: State) {
+        self.zobrist.on_cell_changed(x, y, from, to);
+    }
+
+    fn on_grew(&mut self, sim: &Simulation) {
+        self.zobrist.on_grew(sim);
+    }
+}
+
+/// The key of a cell at column `x` and row `y` from the grid's first top left corner, painted
+/// `state`.
+fn cell_key(x: i64, y: i64, state: State) -> u64 {
+    if state.is_blank() {
+        0
+    } else {
+        mix(mix(mix(x as u64) ^ y as u64) ^ u64::from(state.0))
+    }
+}
+
+/// SplitMix64's finalizer, spreading every bit of `z` over all of the result.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rule;
+    use simulation::Boundary;
+
+    #[test]
+    fn finds_the_cycle_of_a_turmite() {
+        let mut sim = Simulation::new(21, 21);
+        sim.set_rule(rule::parse("turmite:0R1-1R0/1N0-0L1").unwrap()).unwrap();
+        let cycles = Cycles::watch(&mut sim).unwrap();
+        while cycles.lock().unwrap().found().is_none() && sim.steps < 1000 {
+            sim.step().unwrap();
+        }
+        // As found by keeping every state of the run and looking for the first repeat.
+        let cycle = Cycle {
+            start: 49,
+            period: 4,
+        };
+        assert_eq!(cycles.lock().unwrap().found(), Some(cycle));
+    }
+
+    #[test]
+    fn follows_the_grid_as_it_grows() {
+        let mut sim = Simulation::new(4, 4);
+        sim.boundary = Boundary::Grow;
+        let zobrist = Arc::new(Mutex::new(Zobrist::of(&sim)));
+        sim.observe(zobrist.clone());
+        for _ in 0..1000 {
+            sim.step().unwrap();
+        }
+        assert_ne!(sim.origin(), (0, 0));
+        let hash = zobrist.lock().unwrap().hash(&sim);
+        assert_eq!(hash, Zobrist::of(&sim).hash(&sim));
+    }
+}
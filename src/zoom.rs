@@ -0,0 +1,69 @@
+//! Showing square blocks of cells as one character, for grids bigger than the terminal.
+
+use std::str::FromStr;
+
+use langton_ant::simulation::{Cell, Simulation, State};
+
+/// How a block of cells picks the one cell it is shown as.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Blend {
+    /// The most common color, painted colors winning ties. Walls count as a color of their own.
+    Majority,
+    /// The highest color, or a wall if there is one, so nothing painted disappears.
+    Max,
+}
+
+impl Blend {
+    /// The name accepted by `--zoom-blend`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Blend::Majority => "majority",
+            Blend::Max => "max",
+        }
+    }
+}
+
+impl FromStr for Blend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Blend, String> {
+        match s {
+            "majority" => Ok(Blend::Majority),
+            "max" => Ok(Blend::Max),
+            _ => Err(format!("Unknown zoom blend {}, expected majority or max", s)),
+        }
+    }
+}
+
+/// The cell standing for the `zoom` by `zoom` block behind terminal `row` and `column`.
+pub fn block(sim: &Simulation, row: usize, column: usize, zoom: usize, blend: Blend) -> Cell {
+    let (left, top) = (column * zoom, row * zoom);
+    let right = (left + zoom).min(sim.width());
+    let bottom = (top + zoom).min(sim.height());
+
+    // Walls are counted after the 256 colors.
+    let mut counts = [0u32; 257];
+    for y in top..bottom {
+        for x in left..right {
+            let cell = sim.cell(x, y);
+            let kind = if cell.wall { 256 } else { usize::from(cell.state.0) };
+            counts[kind] += 1;
+        }
+    }
+    let kind = match blend {
+        // The last of the most common, so painted colors win over black.
+        Blend::Majority => (0..counts.len()).max_by_key(|&kind| counts[kind]).unwrap_or(0),
+        Blend::Max => (0..counts.len()).rev().find(|&kind| counts[kind] > 0).unwrap_or(0),
+    };
+    if kind == 256 {
+        Cell {
+            state: State::BLACK,
+            wall: true,
+        }
+    } else {
+        Cell {
+            state: State(kind as u8),
+            wall: false,
+        }
+    }
+}
@@ -0,0 +1,85 @@
+// Drives the wasm build of the simulation core and draws it onto a canvas.
+"use strict";
+
+const canvas = document.getElementById("canvas");
+const form = document.getElementById("options");
+const stepsLabel = document.getElementById("steps");
+const context = canvas.getContext("2d");
+
+let ant = null;
+let frame = null;
+
+function option(name) {
+    return Number(form.elements[name].value);
+}
+
+// Copies `value` into the wasm buffer and returns where it went and its length in bytes.
+function text(value) {
+    const bytes = new TextEncoder().encode(value);
+    const pointer = ant.ant_buffer(bytes.length);
+    new Uint8Array(ant.memory.buffer, pointer, bytes.length).set(bytes);
+    return [pointer, bytes.length];
+}
+
+// Hands the rule, start and ant count to the wasm side. Returns false if the rule is invalid.
+function setup() {
+    const rule = form.elements.rule;
+    if (!ant.ant_rule(...text(rule.value))) {
+        rule.setCustomValidity("Not a rule, try letters like LLRR");
+        rule.reportValidity();
+        return false;
+    }
+    rule.setCustomValidity("");
+
+    // An empty start is the middle of the grid.
+    const start = (name, side) =>
+        form.elements[name].value === "" ? Math.floor(option(side) / 2) : option(name);
+    ant.ant_start(start("x", "width"), start("y", "height"), option("heading"));
+    ant.ant_count(option("ants"));
+    return true;
+}
+
+function restart() {
+    if (!setup()) {
+        return;
+    }
+    if (frame !== null) {
+        cancelAnimationFrame(frame);
+    }
+    ant.ant_new(option("width"), option("height"), option("density"), option("seed"));
+
+    const width = ant.ant_width();
+    const height = ant.ant_height();
+    canvas.width = width;
+    canvas.height = height;
+    canvas.style.width = width * option("scale") + "px";
+    canvas.style.height = height * option("scale") + "px";
+
+    const tick = () => {
+        const speed = option("speed");
+        const taken = ant.ant_step(speed);
+        draw(width, height);
+        // Stop once the ant has walked off the grid.
+        frame = taken === speed ? requestAnimationFrame(tick) : null;
+    };
+    draw(width, height);
+    frame = requestAnimationFrame(tick);
+}
+
+function draw(width, height) {
+    const pointer = ant.ant_pixels();
+    // Memory can grow between frames, so the view is made fresh every time.
+    const pixels = new Uint8ClampedArray(ant.memory.buffer, pointer, width * height * 4);
+    context.putImageData(new ImageData(pixels, width, height), 0, 0);
+    stepsLabel.textContent = ant.ant_steps();
+}
+
+form.addEventListener("submit", event => {
+    event.preventDefault();
+    restart();
+});
+
+WebAssembly.instantiateStreaming(fetch("langton_ant.wasm"), {}).then(result => {
+    ant = result.instance.exports;
+    restart();
+});